@@ -0,0 +1,207 @@
+use std::{io, path::Path};
+
+use skia_safe::{Color, EncodedImageFormat, surfaces};
+
+use crate::assets::{Handle, SpriteImage, write_image};
+use crate::ecs::{Adjustment, Bounds, Entity, Icon, Name, Outline, Quad, Shape, Sprite, Text, World, render_entity_content};
+use crate::filters::{Dither, GradientMap, GradientPreset, Noise, Pixels, halftone};
+use crate::rng::Rng;
+
+const NOISE_AMOUNT: f32 = 0.15;
+const PERLIN_SCALE: f32 = 24.0;
+const HALFTONE_CELL: f32 = 8.0;
+// 45 degrees, like a black plate.
+const HALFTONE_ANGLE: f32 = std::f32::consts::FRAC_PI_4;
+const POSTERIZE_LEVELS: u8 = 4;
+
+// Filters that rewrite a layer's pixels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Filter {
+    Noise(Noise),
+    Halftone,
+    Posterize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FilterCommand {
+    // Baked into an image that replaces what the layer drew.
+    Apply(Filter),
+    // Set as the layer's Adjustment, or with None, removes it.
+    Effect(Option<Adjustment>),
+}
+
+// What the filter menu offers, in order.
+pub fn filter_commands() -> Vec<(&'static str, FilterCommand)> {
+    vec![
+        ("White noise", FilterCommand::Apply(Filter::Noise(Noise::White))),
+        ("Perlin noise", FilterCommand::Apply(Filter::Noise(Noise::Perlin { scale: PERLIN_SCALE }))),
+        ("Halftone", FilterCommand::Apply(Filter::Halftone)),
+        ("Posterize", FilterCommand::Apply(Filter::Posterize)),
+        ("Posterize effect", FilterCommand::Effect(Some(Adjustment::Posterize { levels: POSTERIZE_LEVELS }))),
+        ("Remove effect", FilterCommand::Effect(None)),
+    ]
+}
+
+// Runs `command` on each of `entities`; baked images are written under
+// `root`, the document's directory. Returns how many entities changed.
+pub fn run_filter_command(world: &mut World, entities: &[Entity], command: &FilterCommand, rng: &mut Rng, root: &Path) -> io::Result<usize> {
+    let mut changed = 0;
+    for &entity in entities {
+        match command {
+            FilterCommand::Apply(filter) => {
+                if bake_filter(world, entity, *filter, rng, root)? {
+                    changed += 1;
+                }
+            }
+            FilterCommand::Effect(Some(adjustment)) => {
+                world.insert(entity, adjustment.clone());
+                changed += 1;
+            }
+            FilterCommand::Effect(None) => {
+                if world.remove::<Adjustment>(entity).is_some() {
+                    changed += 1;
+                }
+            }
+        }
+    }
+    Ok(changed)
+}
+
+// Renders what `entity` draws over its Bounds, runs `filter` on the pixels
+// and makes the result its Sprite, in place of its quad, shape, icon and
+// text. Its Transform, Opacity and Adjustment still apply on top. False
+// for entities that draw nothing of their own, like groups.
+fn bake_filter(world: &mut World, entity: Entity, filter: Filter, rng: &mut Rng, root: &Path) -> io::Result<bool> {
+    let drawn = world.storage::<Quad>().is_some_and(|quads| quads.get(entity).is_some())
+        || world.storage::<Sprite>().is_some_and(|sprites| sprites.get(entity).is_some());
+    let rect = world.storage::<Bounds>().and_then(|bounds| bounds.get(entity).map(|bounds| bounds.rect));
+    let (true, Some(rect)) = (drawn, rect) else { return Ok(false); };
+
+    let size = (rect.width().ceil().max(1.0) as i32, rect.height().ceil().max(1.0) as i32);
+    let mut surface = surfaces::raster_n32_premul(size)
+        .ok_or_else(|| io::Error::other("failed to allocate filter surface"))?;
+    let canvas = surface.canvas();
+    canvas.clear(Color::TRANSPARENT);
+    canvas.translate((-rect.left, -rect.top));
+    render_entity_content(world, entity, canvas);
+
+    let png = match filter {
+        Filter::Halftone => halftone(&surface.image_snapshot(), HALFTONE_CELL, HALFTONE_ANGLE)
+            .and_then(|image| image.encode(None, EncodedImageFormat::PNG, None)),
+        Filter::Noise(noise) => Pixels::read(&mut surface).and_then(|mut pixels| {
+            pixels.add_noise(rng, NOISE_AMOUNT, noise);
+            pixels.encode_png()
+        }),
+        Filter::Posterize => Pixels::read(&mut surface).and_then(|mut pixels| {
+            pixels.posterize(POSTERIZE_LEVELS, Dither::Ordered);
+            pixels.encode_png()
+        }),
+    };
+    let png = png.ok_or_else(|| io::Error::other(format!("failed to apply {:?}", filter)))?;
+    let name = world.storage::<Name>().and_then(|names| names.get(entity).map(|name| name.0.clone()))
+        .unwrap_or_else(|| "layer".to_string());
+    let path = write_image(root, Path::new(&format!("{} filtered.png", name)), png.as_bytes())?;
+
+    world.remove::<Quad>(entity);
+    world.remove::<Shape>(entity);
+    world.remove::<Outline>(entity);
+    world.remove::<Icon>(entity);
+    world.remove::<Text>(entity);
+    world.remove::<SpriteImage>(entity);
+    world.insert(entity, Sprite { image: Handle::new(&path), src_rect: None, dst_rect: rect });
+    Ok(true)
+}
+
+// Moves `entities` to the preset after the one the first of them has (the
+// first preset if it has none), and past the last preset removes their
+// gradient maps again. Returns the name of the preset now applied.
+pub fn cycle_gradient_map(world: &mut World, entities: &[Entity], presets: &[GradientPreset]) -> Option<String> {
+    let first = *entities.first()?;
+    let current = world.storage::<Adjustment>().and_then(|storage| match storage.get(first) {
+        Some(Adjustment::GradientMap(map)) => presets.iter().position(|preset| preset.map == *map),
+        _ => None,
+    });
+    let next = match current {
+        Some(index) => presets.get(index + 1),
+        None => presets.first(),
+    };
+    for &entity in entities {
+        match next {
+            Some(preset) => { world.insert(entity, Adjustment::GradientMap(preset.map.clone())); }
+            None => { world.remove::<Adjustment>(entity); }
+        }
+    }
+    next.map(|preset| preset.name.clone())
+}
+
+// A gradient through the fill colors of `entities`, darkest first; how a
+// user gradient is picked without a gradient editor.
+pub fn gradient_from_fills(world: &World, entities: &[Entity]) -> Option<GradientMap> {
+    let quads = world.storage::<Quad>()?;
+    let colors: Vec<Color> = entities.iter()
+        .filter_map(|entity| quads.get(*entity).map(|quad| quad.color.to_color()))
+        .collect();
+    GradientMap::from_colors(&colors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use skia_safe::{Color4f, Rect};
+
+    use crate::filters::gradient_presets;
+
+    fn quad(world: &mut World, color: Color) -> Entity {
+        let entity = world.spawn();
+        world.insert(entity, Quad { color: Color4f::from(color), rect: Rect::from_wh(10.0, 10.0) });
+        entity
+    }
+
+    #[test]
+    fn cycling_walks_the_presets_then_removes_the_map() {
+        let mut world = World::new();
+        let entities = [quad(&mut world, Color::WHITE), quad(&mut world, Color::BLACK)];
+        let presets = gradient_presets(&[]);
+
+        for preset in &presets {
+            assert_eq!(cycle_gradient_map(&mut world, &entities, &presets).as_deref(), Some(preset.name.as_str()));
+            let adjustments = world.storage::<Adjustment>().unwrap();
+            for entity in entities {
+                assert_eq!(adjustments.get(entity), Some(&Adjustment::GradientMap(preset.map.clone())));
+            }
+        }
+        assert_eq!(cycle_gradient_map(&mut world, &entities, &presets), None);
+        assert!(entities.iter().all(|entity| world.storage::<Adjustment>().unwrap().get(*entity).is_none()));
+    }
+
+    #[test]
+    fn effect_commands_set_and_remove_the_adjustment() {
+        let mut world = World::new();
+        let entities = [quad(&mut world, Color::WHITE), quad(&mut world, Color::BLACK)];
+        let commands = filter_commands();
+        let command = |name: &str| &commands.iter().find(|(label, _)| *label == name).unwrap().1;
+        let mut rng = Rng::new(1);
+
+        let posterize = command("Posterize effect");
+        assert_eq!(run_filter_command(&mut world, &entities, posterize, &mut rng, Path::new(".")).unwrap(), 2);
+        let adjustment = Some(Adjustment::Posterize { levels: POSTERIZE_LEVELS });
+        assert!(entities.iter().all(|entity| world.storage::<Adjustment>().unwrap().get(*entity).cloned() == adjustment));
+
+        let remove = command("Remove effect");
+        assert_eq!(run_filter_command(&mut world, &entities[..1], remove, &mut rng, Path::new(".")).unwrap(), 1);
+        assert!(world.storage::<Adjustment>().unwrap().get(entities[0]).is_none());
+        assert!(world.storage::<Adjustment>().unwrap().get(entities[1]).is_some());
+    }
+
+    #[test]
+    fn user_gradient_runs_from_the_darkest_fill() {
+        let mut world = World::new();
+        let light = Color::from_rgb(240, 230, 200);
+        let dark = Color::from_rgb(20, 10, 40);
+        let middle = Color::from_rgb(200, 40, 40);
+        let entities = [quad(&mut world, light), quad(&mut world, dark), quad(&mut world, middle)];
+
+        let map = gradient_from_fills(&world, &entities).unwrap();
+        assert_eq!(map.stops, vec![(0.0, dark), (0.5, middle), (1.0, light)]);
+    }
+}
@@ -0,0 +1,99 @@
+use skia_safe::{Rect, Vector};
+
+use crate::ecs::{Bounds, Entity, Quad, Sprite, World};
+use crate::geometry::union_all;
+use crate::hierarchy::children_of;
+
+#[derive(Clone, Copy)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+    Top,
+    Middle,
+    Bottom,
+}
+
+#[derive(Clone, Copy)]
+pub enum Axis {
+    Horizontal,
+    Vertical,
+}
+
+fn bounds_of(world: &World, entities: &[Entity]) -> Vec<(Entity, Rect)> {
+    let Some(bounds) = world.storage::<Bounds>() else { return Vec::new(); };
+    entities.iter()
+        .filter_map(|entity| bounds.get(*entity).map(|bounds| (*entity, bounds.rect)))
+        .collect()
+}
+
+// Bounds and Quad each carry a rect, so moving an entity shifts both.
+// Moving a group moves everything inside it.
+pub fn translate(world: &World, entity: Entity, delta: Vector) {
+    if delta.is_zero() {
+        return;
+    }
+    if let Some(mut bounds) = world.storage_mut::<Bounds>().as_mut().and_then(|storage| storage.get_mut(entity)) {
+        bounds.rect.offset(delta);
+    }
+    if let Some(mut quad) = world.storage_mut::<Quad>().as_mut().and_then(|storage| storage.get_mut(entity)) {
+        quad.rect.offset(delta);
+    }
+    if let Some(mut sprite) = world.storage_mut::<Sprite>().as_mut().and_then(|storage| storage.get_mut(entity)) {
+        sprite.dst_rect.offset(delta);
+    }
+    for child in children_of(world, entity) {
+        translate(world, child, delta);
+    }
+}
+
+// Aligns against the union of the entities' bounds, like design tools do
+// for a multi-selection.
+pub fn align(world: &World, entities: &[Entity], align: Align) {
+    let rects = bounds_of(world, entities);
+    if rects.len() < 2 {
+        return;
+    }
+    let Some(union) = union_all(rects.iter().map(|(_, rect)| *rect)) else { return; };
+
+    for (entity, rect) in rects {
+        let delta = match align {
+            Align::Left => Vector::new(union.left() - rect.left(), 0.0),
+            Align::Center => Vector::new(union.center_x() - rect.center_x(), 0.0),
+            Align::Right => Vector::new(union.right() - rect.right(), 0.0),
+            Align::Top => Vector::new(0.0, union.top() - rect.top()),
+            Align::Middle => Vector::new(0.0, union.center_y() - rect.center_y()),
+            Align::Bottom => Vector::new(0.0, union.bottom() - rect.bottom()),
+        };
+        translate(world, entity, delta);
+    }
+}
+
+// Equal gaps between neighbours along `axis`; the outermost two stay put.
+pub fn distribute(world: &World, entities: &[Entity], axis: Axis) {
+    let mut rects = bounds_of(world, entities);
+    if rects.len() < 3 {
+        return;
+    }
+    let (start, size): (fn(&Rect) -> f32, fn(&Rect) -> f32) = match axis {
+        Axis::Horizontal => (Rect::left, Rect::width),
+        Axis::Vertical => (Rect::top, Rect::height),
+    };
+    rects.sort_by(|(_, a), (_, b)| start(a).total_cmp(&start(b)));
+
+    let first = rects[0].1;
+    let span_end = rects.iter().map(|(_, rect)| start(rect) + size(rect)).fold(f32::MIN, f32::max);
+    let occupied: f32 = rects.iter().map(|(_, rect)| size(rect)).sum();
+    let gap = (span_end - start(&first) - occupied) / (rects.len() - 1) as f32;
+
+    let mut cursor = start(&first);
+    for (entity, rect) in rects {
+        let offset = cursor - start(&rect);
+        let delta = match axis {
+            Axis::Horizontal => Vector::new(offset, 0.0),
+            Axis::Vertical => Vector::new(0.0, offset),
+        };
+        translate(world, entity, delta);
+        cursor += size(&rect) + gap;
+    }
+}
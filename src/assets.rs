@@ -0,0 +1,225 @@
+use std::{collections::{HashMap, HashSet}, fmt, fs, hash::{Hash, Hasher}, io, marker::PhantomData, path::{Path, PathBuf}, rc::Rc};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use skia_safe::{Canvas, Data, Image, gpu::{self, Budgeted, DirectContext, Mipmapped}, images};
+
+use crate::ecs::{Component, Entity, Sprite, World};
+
+// Names an asset by the file it's loaded from, so components holding one
+// save and undo like any other. The asset itself lives in Assets.
+pub struct Handle<T> {
+    path: Rc<Path>,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Handle<T> {
+    pub fn new(path: &Path) -> Self {
+        Handle { path: Rc::from(path), marker: PhantomData }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+// By hand, since deriving would require T: Clone and so on.
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Handle { path: self.path.clone(), marker: PhantomData }
+    }
+}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> Hash for Handle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.path.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Handle({})", self.path.display())
+    }
+}
+
+impl<T> Serialize for Handle<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.path.serialize(serializer)
+    }
+}
+
+impl<'de, T> Deserialize<'de> for Handle<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Handle::new(&PathBuf::deserialize(deserializer)?))
+    }
+}
+
+// A decoded image and, given a GPU context when it loaded, its texture.
+// Also a derived component: sprite_asset_system gives it to each Sprite
+// whose image is loaded, so the render paths only need the World.
+#[derive(Clone)]
+pub struct SpriteImage {
+    pub image: Image,
+    pub texture: Option<Image>,
+}
+
+impl Component for SpriteImage {}
+
+impl SpriteImage {
+    // The texture on GPU canvases, where drawing it is just a texture bind;
+    // the decoded image anywhere else (exports, thumbnails, recordings),
+    // which can't read a texture back.
+    pub fn for_canvas(&self, canvas: &Canvas) -> &Image {
+        match &self.texture {
+            Some(texture) if canvas.recording_context().is_some() => texture,
+            _ => &self.image,
+        }
+    }
+}
+
+// Decoded images (PNG, JPEG, anything skia's codecs read) by path, each
+// uploaded once as it loads when there's a GPU context. Relative paths are
+// relative to `root`, the open document's directory.
+pub struct Assets {
+    root: PathBuf,
+    images: HashMap<Handle<Image>, SpriteImage>,
+    // Loads that failed, so sprite_asset_system doesn't retry them every
+    // frame. An explicit load_image still does.
+    failed: HashSet<Handle<Image>>,
+}
+
+impl Assets {
+    pub fn new() -> Self {
+        Assets { root: PathBuf::from("."), images: HashMap::new(), failed: HashSet::new() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    // Resolves paths against `root` from now on. Images loaded against the
+    // old one are dropped, since the same relative path may now name a
+    // different file.
+    pub fn set_root(&mut self, root: &Path) {
+        if self.root != root {
+            self.root = root.to_path_buf();
+            self.images.clear();
+            self.failed.clear();
+        }
+    }
+
+    // Loads `path` unless it's already loaded.
+    pub fn load_image(&mut self, path: &Path, context: Option<&mut DirectContext>) -> io::Result<Handle<Image>> {
+        let handle = Handle::new(path);
+        self.load(&handle, context)?;
+        Ok(handle)
+    }
+
+    fn load(&mut self, handle: &Handle<Image>, context: Option<&mut DirectContext>) -> io::Result<()> {
+        if self.images.contains_key(handle) {
+            return Ok(());
+        }
+        match decode(&self.root.join(handle.path())) {
+            Ok(image) => {
+                // A failed upload still leaves a drawable image.
+                let texture = context
+                    .and_then(|context| gpu::images::texture_from_image(context, &image, Mipmapped::No, Budgeted::Yes));
+                self.failed.remove(handle);
+                self.images.insert(handle.clone(), SpriteImage { image, texture });
+                Ok(())
+            }
+            Err(e) => {
+                self.failed.insert(handle.clone());
+                Err(e)
+            }
+        }
+    }
+
+    pub fn image(&self, handle: &Handle<Image>) -> Option<&SpriteImage> {
+        self.images.get(handle)
+    }
+
+    pub fn len(&self) -> usize {
+        self.images.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty()
+    }
+}
+
+// Brings an image from anywhere on disk into the document at `root`: one
+// already under it is used where it is, any other is copied into its
+// "images" directory, renamed if a different file has the name. Returns
+// the path to give its Handle, relative to `root`.
+pub fn import_image(root: &Path, source: &Path) -> io::Result<PathBuf> {
+    let bytes = fs::read(source)?;
+    if let Ok(relative) = source.canonicalize()?.strip_prefix(root.canonicalize()?) {
+        return Ok(relative.to_path_buf());
+    }
+    let name = source.file_name().ok_or_else(|| io::Error::other(format!("no file name in {}", source.display())))?;
+    write_image(root, Path::new(name), &bytes)
+}
+
+// Writes `bytes` as `name` into the "images" directory under `root`,
+// numbered if a different file has the name; the same bytes already there
+// are reused. Returns the path relative to `root`.
+pub fn write_image(root: &Path, name: &Path, bytes: &[u8]) -> io::Result<PathBuf> {
+    let stem = name.file_stem().unwrap_or(name.as_os_str()).to_string_lossy().into_owned();
+    let extension = name.extension().map(|extension| format!(".{}", extension.to_string_lossy())).unwrap_or_default();
+    let directory = root.join("images");
+    fs::create_dir_all(&directory)?;
+    let mut relative = Path::new("images").join(name);
+    let mut number = 2;
+    loop {
+        match fs::read(root.join(&relative)) {
+            Ok(existing) if existing == *bytes => return Ok(relative),
+            Ok(_) => {
+                relative = Path::new("images").join(format!("{} {}{}", stem, number, extension));
+                number += 1;
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => break,
+            Err(e) => return Err(e),
+        }
+    }
+    fs::write(root.join(&relative), bytes)?;
+    Ok(relative)
+}
+
+fn decode(path: &Path) -> io::Result<Image> {
+    let bytes = fs::read(path)?;
+    images::deferred_from_encoded_data(Data::new_copy(&bytes), None)
+        .ok_or_else(|| io::Error::other(format!("failed to decode {}", path.display())))
+}
+
+// Loads the images of sprites added or changed since the last frame, e.g.
+// by opening a scene, and gives each sprite its SpriteImage. Returns the
+// sprites that got one.
+pub fn sprite_asset_system(world: &mut World, assets: &mut Assets, mut context: Option<&mut DirectContext>) -> Vec<Entity> {
+    let changed: Vec<(Entity, Handle<Image>)> = world.query::<Sprite>().changed::<Sprite>().iter()
+        .map(|(entity, sprite)| (entity, sprite.image.clone()))
+        .collect();
+    let mut loaded = Vec::new();
+    for (entity, handle) in changed {
+        if !assets.failed.contains(&handle) {
+            if let Err(e) = assets.load(&handle, context.as_deref_mut()) {
+                eprintln!("Failed to load image {}: {:?}", handle.path().display(), e);
+            }
+        }
+        match assets.image(&handle) {
+            Some(image) => {
+                world.insert(entity, image.clone());
+                loaded.push(entity);
+            }
+            None => { world.remove::<SpriteImage>(entity); }
+        }
+    }
+    loaded
+}
@@ -0,0 +1,147 @@
+use std::{collections::HashMap, fs, io, path::Path};
+
+use skia_safe::{Canvas, Color, Data, Image, Paint, Rect, SamplingOptions, canvas::SrcRectConstraint, images, surfaces};
+
+use crate::ecs::{Component, Entity, Icon, World};
+use crate::packer::{self, PackedRect};
+
+pub const ATLAS_PAGE_SIZE: i32 = 1024;
+// Keeps linear filtering from bleeding neighbours into an entry's edges.
+const ATLAS_PADDING: i32 = 2;
+
+#[derive(Clone, Copy, Debug)]
+pub struct AtlasRegion {
+    pub page: usize,
+    pub src: Rect,
+}
+
+// An Icon's entry in the atlas, kept by atlas_icon_system: the page it's
+// on and where. Icons without one draw their vector outline.
+#[derive(Clone)]
+pub struct AtlasIcon {
+    pub page: Image,
+    pub src: Rect,
+}
+
+impl Component for AtlasIcon {}
+
+// Small images (icons, brush tips, swatches) packed into a few shared
+// pages at load time. Each page is a single image, so skia uploads it once
+// and every entry drawn from it shares the texture.
+pub struct TextureAtlas {
+    pages: Vec<Image>,
+    regions: HashMap<String, AtlasRegion>,
+}
+
+impl TextureAtlas {
+    // Packs `images` into pages of at most `page_size` square. Fails if an
+    // image can't fit on a page on its own.
+    pub fn build(images: Vec<(String, Image)>, page_size: i32) -> io::Result<Self> {
+        if let Some((name, _)) = images.iter().find(|(_, image)| image.width().max(image.height()) + 2 * ATLAS_PADDING > page_size) {
+            return Err(io::Error::other(format!("image {} is too large for a {}px atlas page", name, page_size)));
+        }
+        let mut atlas = TextureAtlas { pages: Vec::new(), regions: HashMap::new() };
+        let mut remaining = images;
+        while !remaining.is_empty() {
+            let sizes: Vec<(i32, i32)> = remaining.iter().map(|(_, image)| (image.width(), image.height())).collect();
+            let packing = packer::pack(&sizes, page_size, ATLAS_PADDING);
+            // Shelves are packed top down; whatever runs off the bottom goes
+            // on the next page.
+            let (fits, overflow): (Vec<PackedRect>, Vec<PackedRect>) = packing.rects.iter()
+                .partition(|rect| rect.y + rect.height + ATLAS_PADDING <= page_size);
+            let height = fits.iter().map(|rect| rect.y + rect.height + ATLAS_PADDING).max().unwrap_or(1);
+
+            let mut surface = surfaces::raster_n32_premul((packing.width.max(1), height))
+                .ok_or_else(|| io::Error::other("failed to allocate atlas page"))?;
+            let canvas = surface.canvas();
+            canvas.clear(Color::TRANSPARENT);
+            let page = atlas.pages.len();
+            for rect in &fits {
+                let (name, image) = &remaining[rect.index];
+                canvas.draw_image(image, (rect.x as f32, rect.y as f32), None);
+                let src = Rect::from_xywh(rect.x as f32, rect.y as f32, rect.width as f32, rect.height as f32);
+                atlas.regions.insert(name.clone(), AtlasRegion { page, src });
+            }
+            atlas.pages.push(surface.image_snapshot());
+
+            let overflow: Vec<usize> = overflow.iter().map(|rect| rect.index).collect();
+            remaining = remaining.into_iter().enumerate()
+                .filter(|(index, _)| overflow.contains(index))
+                .map(|(_, entry)| entry)
+                .collect();
+        }
+        Ok(atlas)
+    }
+
+    // Every .png in `dir`, named by file stem.
+    pub fn load_dir(dir: &Path, page_size: i32) -> io::Result<Self> {
+        let mut images = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|extension| extension != "png") {
+                continue;
+            }
+            let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else { continue; };
+            let image = images::deferred_from_encoded_data(Data::new_copy(&fs::read(&path)?), None)
+                .ok_or_else(|| io::Error::other(format!("failed to decode {}", path.display())))?;
+            images.push((name.to_string(), image));
+        }
+        TextureAtlas::build(images, page_size)
+    }
+
+    pub fn len(&self) -> usize {
+        self.regions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.regions.is_empty()
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn region(&self, name: &str) -> Option<AtlasRegion> {
+        self.regions.get(name).copied()
+    }
+
+    // Draws the entry `name` stretched over `dst`. False if there's no such
+    // entry.
+    pub fn draw(&self, canvas: &Canvas, name: &str, dst: Rect, paint: &Paint) -> bool {
+        let Some(region) = self.region(name) else { return false; };
+        draw_region(canvas, &self.pages[region.page], region.src, dst, paint);
+        true
+    }
+
+    fn icon(&self, name: &str) -> Option<AtlasIcon> {
+        let region = self.region(name)?;
+        Some(AtlasIcon { page: self.pages[region.page].clone(), src: region.src })
+    }
+}
+
+// The `src` part of an atlas page, stretched over `dst`.
+pub fn draw_region(canvas: &Canvas, page: &Image, src: Rect, dst: Rect, paint: &Paint) {
+    canvas.draw_image_rect_with_sampling_options(
+        page,
+        Some((&src, SrcRectConstraint::Strict)),
+        dst,
+        SamplingOptions::default(),
+        paint,
+    );
+}
+
+// Looks up Icons added or changed since the last frame in `atlas`, so the
+// render paths, which only see the World, can draw them from it. Returns
+// the icons looked up.
+pub fn atlas_icon_system(world: &mut World, atlas: &TextureAtlas) -> Vec<Entity> {
+    let changed: Vec<(Entity, String)> = world.query::<Icon>().changed::<Icon>().iter()
+        .map(|(entity, icon)| (entity, icon.name.clone()))
+        .collect();
+    for (entity, name) in &changed {
+        match atlas.icon(name) {
+            Some(icon) => { world.insert(*entity, icon); }
+            None => { world.remove::<AtlasIcon>(*entity); }
+        }
+    }
+    changed.into_iter().map(|(entity, _)| entity).collect()
+}
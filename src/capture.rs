@@ -0,0 +1,56 @@
+use std::{fs, io, path::Path};
+
+use skia_safe::{Picture, PictureRecorder, Rect};
+
+use crate::ecs::{Entity, Selection, World, draw_order, render_entity};
+use crate::quality::Quality;
+use crate::systems::{render_selection, render_system};
+
+// What one entity contributes to a frame, from recording it on its own.
+pub struct EntityOps {
+    pub entity: Entity,
+    pub ops: usize,
+    pub bytes: usize,
+    pub cull: Rect,
+}
+
+// The frame exactly as RedrawRequested draws it, minus the damage clip.
+pub fn record_frame(world: &World, selection: &Selection, viewport: Rect, quality: Quality) -> Option<Picture> {
+    let mut recorder = PictureRecorder::new();
+    let canvas = recorder.begin_recording(viewport, None);
+    render_system(world, canvas, quality);
+    render_selection(world, selection, canvas);
+    recorder.finish_recording_as_picture(None)
+}
+
+// Writes an .skp, which skia's debugger (debugger.skia.org) opens.
+pub fn write_skp(picture: &Picture, path: &Path) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, picture.serialize().as_bytes())
+}
+
+pub fn capture_frame(world: &World, selection: &Selection, viewport: Rect, quality: Quality, path: &Path) -> io::Result<()> {
+    let picture = record_frame(world, selection, viewport, quality)
+        .ok_or_else(|| io::Error::other("failed to record frame"))?;
+    write_skp(&picture, path)
+}
+
+// Every drawn entity in render order (see draw_order), each recorded into
+// its own picture.
+pub fn entity_ops(world: &World, viewport: Rect) -> Vec<EntityOps> {
+    let mut ops = Vec::new();
+    for entity in draw_order(world) {
+        let mut recorder = PictureRecorder::new();
+        render_entity(world, entity, recorder.begin_recording(viewport, None));
+        let Some(picture) = recorder.finish_recording_as_picture(None) else { continue; };
+        ops.push(EntityOps {
+            entity,
+            ops: picture.approximate_op_count(),
+            bytes: picture.approximate_bytes_used(),
+            cull: picture.cull_rect(),
+        });
+    }
+    ops
+}
@@ -0,0 +1,53 @@
+use std::{borrow::Cow, io};
+
+use arboard::{Clipboard, ImageData};
+use skia_safe::{Rect, Vector};
+
+use crate::ecs::{Entity, World};
+use crate::export::render_region_surface;
+use crate::filters::Pixels;
+use crate::hierarchy::paste;
+use crate::scene::SceneData;
+
+// Renders a document region offscreen and places it on the system clipboard
+// as RGBA; arboard hands it to the platform as PNG/DIB as appropriate.
+pub fn copy_region_image(world: &World, rect: Rect) -> io::Result<()> {
+    let mut surface = render_region_surface(world, rect, 1.0)
+        .ok_or_else(|| io::Error::other("failed to render region"))?;
+    let pixels = Pixels::read(&mut surface)
+        .ok_or_else(|| io::Error::other("failed to read region pixels"))?;
+
+    let image = ImageData {
+        width: pixels.width,
+        height: pixels.height,
+        bytes: Cow::Owned(pixels.data),
+    };
+    Clipboard::new()
+        .and_then(|mut clipboard| clipboard.set_image(image))
+        .map_err(io::Error::other)
+}
+
+// Puts `entities`, each with everything inside it, on the system clipboard
+// as scene JSON (see World::copy_subtree), so they paste into any document.
+pub fn copy_entities(world: &World, entities: &[Entity]) -> io::Result<()> {
+    let scenes = entities.iter()
+        .map(|entity| world.copy_subtree(*entity))
+        .collect::<io::Result<Vec<SceneData>>>()?;
+    let json = serde_json::to_string(&scenes)?;
+    Clipboard::new()
+        .and_then(|mut clipboard| clipboard.set_text(json))
+        .map_err(io::Error::other)
+}
+
+// Pastes what copy_entities put on the clipboard under `parent`, moved by
+// `offset`. Returns the pasted roots.
+pub fn paste_entities(world: &mut World, parent: Option<Entity>, offset: Vector) -> io::Result<Vec<Entity>> {
+    let json = Clipboard::new()
+        .and_then(|mut clipboard| clipboard.get_text())
+        .map_err(io::Error::other)?;
+    let scenes: Vec<SceneData> = serde_json::from_str(&json)
+        .map_err(|e| io::Error::other(format!("clipboard doesn't hold layers: {}", e)))?;
+    scenes.into_iter()
+        .map(|scene| paste(world, scene, parent, offset))
+        .collect()
+}
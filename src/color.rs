@@ -0,0 +1,215 @@
+use std::f32::consts::TAU;
+
+use skia_safe::{Color, Color4f};
+
+// WCAG 2 minimum contrast for normal and large text.
+pub const CONTRAST_AA: f32 = 4.5;
+pub const CONTRAST_AA_LARGE: f32 = 3.0;
+
+// Perceptually uniform color (Björn Ottosson's OKLab): equal steps in
+// l/a/b look like equal changes, so lerping here avoids the muddy middles
+// of sRGB lerps.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Oklab {
+    pub l: f32,
+    pub a: f32,
+    pub b: f32,
+    pub alpha: f32,
+}
+
+// OKLab in polar form; `h` is in radians.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Oklch {
+    pub l: f32,
+    pub c: f32,
+    pub h: f32,
+    pub alpha: f32,
+}
+
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+impl Oklab {
+    pub fn from_color4f(color: Color4f) -> Self {
+        let r = srgb_to_linear(color.r);
+        let g = srgb_to_linear(color.g);
+        let b = srgb_to_linear(color.b);
+
+        let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+        let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+        let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+
+        Oklab {
+            l: 0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+            a: 1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+            b: 0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+            alpha: color.a,
+        }
+    }
+
+    // Out of gamut results are clamped per channel.
+    pub fn to_color4f(self) -> Color4f {
+        let l = (self.l + 0.3963377774 * self.a + 0.2158037573 * self.b).powi(3);
+        let m = (self.l - 0.1055613458 * self.a - 0.0638541728 * self.b).powi(3);
+        let s = (self.l - 0.0894841775 * self.a - 1.2914855480 * self.b).powi(3);
+
+        let r = 4.0767416621 * l - 3.3077115913 * m + 0.2309699292 * s;
+        let g = -1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s;
+        let b = -0.0041960863 * l - 0.7034186147 * m + 1.7076147010 * s;
+
+        Color4f::new(
+            linear_to_srgb(r).clamp(0.0, 1.0),
+            linear_to_srgb(g).clamp(0.0, 1.0),
+            linear_to_srgb(b).clamp(0.0, 1.0),
+            self.alpha.clamp(0.0, 1.0),
+        )
+    }
+
+    pub fn from_color(color: Color) -> Self {
+        Oklab::from_color4f(Color4f::from(color))
+    }
+
+    pub fn to_color(self) -> Color {
+        self.to_color4f().to_color()
+    }
+
+    pub fn lerp(self, other: Oklab, t: f32) -> Oklab {
+        Oklab {
+            l: lerp(self.l, other.l, t),
+            a: lerp(self.a, other.a, t),
+            b: lerp(self.b, other.b, t),
+            alpha: lerp(self.alpha, other.alpha, t),
+        }
+    }
+
+    pub fn to_oklch(self) -> Oklch {
+        Oklch {
+            l: self.l,
+            c: self.a.hypot(self.b),
+            h: self.b.atan2(self.a).rem_euclid(TAU),
+            alpha: self.alpha,
+        }
+    }
+}
+
+impl Oklch {
+    pub fn from_color4f(color: Color4f) -> Self {
+        Oklab::from_color4f(color).to_oklch()
+    }
+
+    pub fn to_color4f(self) -> Color4f {
+        self.to_oklab().to_color4f()
+    }
+
+    pub fn to_oklab(self) -> Oklab {
+        Oklab { l: self.l, a: self.c * self.h.cos(), b: self.c * self.h.sin(), alpha: self.alpha }
+    }
+
+    // Hue takes the short way round the circle.
+    pub fn lerp(self, other: Oklch, t: f32) -> Oklch {
+        let mut delta = (other.h - self.h).rem_euclid(TAU);
+        if delta > TAU / 2.0 {
+            delta -= TAU;
+        }
+        Oklch {
+            l: lerp(self.l, other.l, t),
+            c: lerp(self.c, other.c, t),
+            h: (self.h + delta * t).rem_euclid(TAU),
+            alpha: lerp(self.alpha, other.alpha, t),
+        }
+    }
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+// The default for animating between two colors.
+pub fn lerp_color(from: Color4f, to: Color4f, t: f32) -> Color4f {
+    Oklab::from_color4f(from).lerp(Oklab::from_color4f(to), t).to_color4f()
+}
+
+// Keeps saturation up between distant hues, where an OKLab lerp passes
+// near gray.
+pub fn lerp_color_hue(from: Color4f, to: Color4f, t: f32) -> Color4f {
+    Oklch::from_color4f(from).lerp(Oklch::from_color4f(to), t).to_color4f()
+}
+
+// WCAG relative luminance; alpha is ignored.
+pub fn relative_luminance(color: Color4f) -> f32 {
+    0.2126 * srgb_to_linear(color.r) + 0.7152 * srgb_to_linear(color.g) + 0.0722 * srgb_to_linear(color.b)
+}
+
+// From 1 (same luminance) to 21 (black on white), in either order.
+pub fn contrast_ratio(a: Color4f, b: Color4f) -> f32 {
+    let a = relative_luminance(a);
+    let b = relative_luminance(b);
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+pub fn meets_contrast(foreground: Color4f, background: Color4f, minimum: f32) -> bool {
+    contrast_ratio(foreground, background) >= minimum
+}
+
+// Whichever of black or white reads better on `background`.
+pub fn readable_on(background: Color4f) -> Color4f {
+    let black = Color4f::new(0.0, 0.0, 0.0, 1.0);
+    let white = Color4f::new(1.0, 1.0, 1.0, 1.0);
+    if contrast_ratio(black, background) >= contrast_ratio(white, background) { black } else { white }
+}
+
+// Evenly spaced color stops, sampled perceptually; a gradient or a ramp of
+// theme shades.
+#[derive(Clone, Debug)]
+pub struct Palette {
+    stops: Vec<Oklab>,
+}
+
+impl Palette {
+    pub fn new(colors: &[Color4f]) -> Self {
+        Palette { stops: colors.iter().map(|color| Oklab::from_color4f(*color)).collect() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.stops.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stops.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<Color4f> {
+        self.stops.get(index).map(|stop| stop.to_color4f())
+    }
+
+    // `t` runs from the first stop at 0 to the last at 1.
+    pub fn sample(&self, t: f32) -> Option<Color4f> {
+        let last = self.stops.len().checked_sub(1)?;
+        let position = t.clamp(0.0, 1.0) * last as f32;
+        let index = (position.floor() as usize).min(last.saturating_sub(1));
+        let Some(next) = self.stops.get(index + 1) else { return Some(self.stops[index].to_color4f()); };
+        Some(self.stops[index].lerp(*next, position - index as f32).to_color4f())
+    }
+
+    // `count` colors spread evenly across the palette.
+    pub fn ramp(&self, count: usize) -> Vec<Color4f> {
+        match count {
+            0 => Vec::new(),
+            1 => self.sample(0.0).into_iter().collect(),
+            _ => (0..count).filter_map(|i| self.sample(i as f32 / (count - 1) as f32)).collect(),
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use skia_safe::{Rect, Vector};
+
+use crate::align::translate;
+use crate::ecs::{Bounds, Constraint, Entity, Quad, World};
+use crate::hierarchy::{depth, is_group, parent_of};
+
+// Resolves one axis: `start`/`end` are the entity's edges, `min`/`max` the
+// parent's. Returns the new edges.
+fn resolve_axis(start: f32, end: f32, min: f32, max: f32, pin_start: Option<f32>, pin_end: Option<f32>) -> (f32, f32) {
+    let size = end - start;
+    match (pin_start, pin_end) {
+        (Some(a), Some(b)) => (min + a, max - b),
+        (Some(a), None) => (min + a, min + a + size),
+        (None, Some(b)) => (max - b - size, max - b),
+        (None, None) => (start, end),
+    }
+}
+
+// Shifts [start, end] inside [min, max]; too-large spans stick to `min`.
+fn clamp_axis(start: f32, end: f32, min: f32, max: f32) -> f32 {
+    if end - start > max - min || start < min {
+        min - start
+    } else if end > max {
+        max - end
+    } else {
+        0.0
+    }
+}
+
+fn constrain(rect: Rect, parent: Rect, constraint: &Constraint) -> Rect {
+    let (left, right) = resolve_axis(rect.left(), rect.right(), parent.left(), parent.right(), constraint.pin_left, constraint.pin_right);
+    let (mut top, mut bottom) = resolve_axis(rect.top(), rect.bottom(), parent.top(), parent.bottom(), constraint.pin_top, constraint.pin_bottom);
+
+    if let Some(ratio) = constraint.aspect_ratio.filter(|ratio| *ratio > 0.0) {
+        let height = (right - left) / ratio;
+        if constraint.pin_bottom.is_some() && constraint.pin_top.is_none() {
+            top = bottom - height;
+        } else {
+            bottom = top + height;
+        }
+    }
+
+    let mut result = Rect::new(left, top, right, bottom);
+    if constraint.clamp_to_parent {
+        result.offset(Vector::new(
+            clamp_axis(result.left(), result.right(), parent.left(), parent.right()),
+            clamp_axis(result.top(), result.bottom(), parent.top(), parent.bottom()),
+        ));
+    }
+    result
+}
+
+// Applies every Constraint, outermost entities first so children resolve
+// against their parent's final rect. Groups are only moved, never resized,
+// since their size follows their children. Returns the rects entities were
+// moved away from, which still need repainting.
+pub fn constraint_system(world: &World, viewport: Rect) -> Vec<Rect> {
+    let mut constrained: Vec<(usize, Entity, Constraint)> = world.query::<Constraint>().iter()
+        .map(|(entity, constraint)| (depth(world, entity), entity, constraint.clone()))
+        .collect();
+    constrained.sort_by_key(|(depth, _, _)| *depth);
+
+    let mut vacated = Vec::new();
+    for (_, entity, constraint) in constrained {
+        let Some(bounds) = world.storage::<Bounds>() else { break; };
+        let Some(rect) = bounds.get(entity).map(|bounds| bounds.rect) else { continue; };
+        let parent = parent_of(world, entity)
+            .and_then(|parent| bounds.get(parent).map(|bounds| bounds.rect))
+            .unwrap_or(viewport);
+        drop(bounds);
+
+        let target = constrain(rect, parent, &constraint);
+        if target == rect {
+            continue;
+        }
+        vacated.push(rect);
+        if is_group(world, entity) {
+            translate(world, entity, Vector::new(target.left() - rect.left(), target.top() - rect.top()));
+            continue;
+        }
+        if let Some(mut bounds) = world.storage_mut::<Bounds>().as_mut().and_then(|storage| storage.get_mut(entity)) {
+            bounds.rect = target;
+        }
+        if let Some(mut quad) = world.storage_mut::<Quad>().as_mut().and_then(|storage| storage.get_mut(entity)) {
+            quad.rect = target;
+        }
+    }
+    vacated
+}
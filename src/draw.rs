@@ -0,0 +1,121 @@
+use skia_safe::{Canvas, ColorFilter, Matrix, Paint, Rect, canvas::SaveLayerRec};
+
+use crate::ecs::{Adjustment, Entity, Opacity, Transform, World};
+use crate::graphics::ParallaxOffset;
+
+// A canvas save that restores itself when dropped, so an early return or
+// a missed restore() can't leave state behind for the next draw. Scopes
+// nest by opening a new one on scope.canvas().
+pub struct DrawScope<'a> {
+    canvas: &'a Canvas,
+    save_count: usize,
+}
+
+impl<'a> DrawScope<'a> {
+    pub fn new(canvas: &'a Canvas) -> Self {
+        let save_count = canvas.save_count();
+        canvas.save();
+        DrawScope { canvas, save_count }
+    }
+
+    // The entity's Transform, Opacity and Adjustment, whichever it has,
+    // after its parallax shift.
+    pub fn for_entity(canvas: &'a Canvas, world: &World, entity: Entity) -> Self {
+        let scope = DrawScope::new(canvas);
+        if let Some(ParallaxOffset(shift)) = world.storage::<ParallaxOffset>().and_then(|storage| storage.get(entity).copied()) {
+            scope.transform(&Matrix::translate(shift));
+        }
+        if let Some(transform) = world.storage::<Transform>().and_then(|storage| storage.get(entity).copied()) {
+            scope.transform(&transform.local_to_parent);
+        }
+        if let Some(Opacity(alpha)) = world.storage::<Opacity>().and_then(|storage| storage.get(entity).copied()) {
+            scope.opacity(alpha);
+        }
+        if let Some(filter) = world.storage::<Adjustment>().and_then(|storage| storage.get(entity).and_then(Adjustment::color_filter)) {
+            scope.color_filter(filter);
+        }
+        scope
+    }
+
+    pub fn canvas(&self) -> &'a Canvas {
+        self.canvas
+    }
+
+    pub fn transform(&self, matrix: &Matrix) -> &Self {
+        self.canvas.concat(matrix);
+        self
+    }
+
+    pub fn clip(&self, rect: Rect) -> &Self {
+        self.canvas.clip_rect(rect, None, None);
+        self
+    }
+
+    // Everything drawn in the scope is composited at `alpha` as a whole,
+    // so overlapping shapes don't show through each other.
+    pub fn opacity(&self, alpha: f32) -> &Self {
+        if alpha < 1.0 {
+            self.canvas.save_layer_alpha_f(None, alpha.max(0.0));
+        }
+        self
+    }
+
+    // Like opacity, a layer the filter applies to once everything in the
+    // scope is drawn.
+    pub fn color_filter(&self, filter: ColorFilter) -> &Self {
+        let mut paint = Paint::default();
+        paint.set_color_filter(filter);
+        self.canvas.save_layer(&SaveLayerRec::default().paint(&paint));
+        self
+    }
+}
+
+impl Drop for DrawScope<'_> {
+    fn drop(&mut self) {
+        self.canvas.restore_to_count(self.save_count);
+    }
+}
+
+// The renderer's root: hands out DrawScopes and checks, once the frame is
+// drawn, that everything pushed was popped.
+pub struct TransformStack<'a> {
+    canvas: &'a Canvas,
+    base: usize,
+}
+
+impl<'a> TransformStack<'a> {
+    pub fn new(canvas: &'a Canvas) -> Self {
+        TransformStack { canvas, base: canvas.save_count() }
+    }
+
+    pub fn canvas(&self) -> &'a Canvas {
+        self.canvas
+    }
+
+    pub fn depth(&self) -> usize {
+        self.canvas.save_count().saturating_sub(self.base)
+    }
+
+    pub fn push(&self) -> DrawScope<'a> {
+        DrawScope::new(self.canvas)
+    }
+
+    pub fn push_transform(&self, matrix: &Matrix) -> DrawScope<'a> {
+        let scope = self.push();
+        scope.transform(matrix);
+        scope
+    }
+
+    pub fn push_entity(&self, world: &World, entity: Entity) -> DrawScope<'a> {
+        DrawScope::for_entity(self.canvas, world, entity)
+    }
+}
+
+impl Drop for TransformStack<'_> {
+    fn drop(&mut self) {
+        if !std::thread::panicking() {
+            debug_assert_eq!(self.depth(), 0, "canvas saves leaked out of a TransformStack");
+        }
+        self.canvas.restore_to_count(self.base);
+    }
+}
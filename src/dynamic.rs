@@ -0,0 +1,110 @@
+use std::io;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldKind {
+    Bool,
+    Number,
+    Text,
+    // Raw bytes, kept as an array of 0..=255.
+    Bytes,
+    // Any JSON value, unchecked.
+    Json,
+}
+
+impl FieldKind {
+    fn default_value(self) -> Value {
+        match self {
+            FieldKind::Bool => Value::Bool(false),
+            FieldKind::Number => Value::from(0.0),
+            FieldKind::Text => Value::String(String::new()),
+            FieldKind::Bytes => Value::Array(Vec::new()),
+            FieldKind::Json => Value::Null,
+        }
+    }
+
+    fn accepts(self, value: &Value) -> bool {
+        match self {
+            FieldKind::Bool => value.is_boolean(),
+            FieldKind::Number => value.is_number(),
+            FieldKind::Text => value.is_string(),
+            FieldKind::Bytes => value.as_array().is_some_and(|bytes| {
+                bytes.iter().all(|byte| byte.as_u64().is_some_and(|byte| byte <= u8::MAX as u64))
+            }),
+            FieldKind::Json => true,
+        }
+    }
+}
+
+// A component declared at runtime, e.g. by a script, instead of by a Rust
+// type. Values are JSON objects with exactly these fields; World keeps one
+// DynamicStorage per layout, looked up by name.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct DynamicLayout {
+    pub name: String,
+    pub fields: Vec<(String, FieldKind)>,
+}
+
+impl DynamicLayout {
+    pub fn new(name: &str) -> Self {
+        DynamicLayout { name: name.to_string(), fields: Vec::new() }
+    }
+
+    pub fn field(mut self, name: &str, kind: FieldKind) -> Self {
+        self.fields.retain(|(existing, _)| existing != name);
+        self.fields.push((name.to_string(), kind));
+        self
+    }
+
+    // A layout as a script would write it, e.g.
+    // {"name": "Health", "fields": [["current", "number"], ["max", "number"]]}
+    pub fn from_json(text: &str) -> io::Result<Self> {
+        Ok(serde_json::from_str(text)?)
+    }
+
+    pub fn kind(&self, field: &str) -> Option<FieldKind> {
+        self.fields.iter().find(|(name, _)| name == field).map(|(_, kind)| *kind)
+    }
+
+    pub fn default_value(&self) -> Value {
+        let fields = self.fields.iter()
+            .map(|(name, kind)| (name.clone(), kind.default_value()))
+            .collect::<Map<_, _>>();
+        Value::Object(fields)
+    }
+
+    // `value` as a complete component: missing fields get their defaults,
+    // unknown fields and wrongly typed ones are errors.
+    pub fn validate(&self, value: Value) -> io::Result<Value> {
+        let Value::Object(mut given) = value else {
+            return Err(io::Error::other(format!("{} must be an object", self.name)));
+        };
+        let mut fields = Map::new();
+        for (name, kind) in &self.fields {
+            let value = given.remove(name).unwrap_or_else(|| kind.default_value());
+            self.check_field(name, *kind, &value)?;
+            fields.insert(name.clone(), value);
+        }
+        if let Some(unknown) = given.keys().next() {
+            return Err(io::Error::other(format!("{} has no field {}", self.name, unknown)));
+        }
+        Ok(Value::Object(fields))
+    }
+
+    pub fn validate_field(&self, field: &str, value: &Value) -> io::Result<()> {
+        let kind = self.kind(field)
+            .ok_or_else(|| io::Error::other(format!("{} has no field {}", self.name, field)))?;
+        self.check_field(field, kind, value)
+    }
+
+    fn check_field(&self, field: &str, kind: FieldKind, value: &Value) -> io::Result<()> {
+        if kind.accepts(value) {
+            Ok(())
+        } else {
+            Err(io::Error::other(format!("{}.{} expects {:?}, got {}", self.name, field, kind, value)))
+        }
+    }
+}
@@ -1,17 +1,117 @@
-use std::{any::{Any, TypeId, type_name}, cell::{Ref, RefCell, RefMut}, collections::HashMap, rc::Rc};
+use std::{any::{Any, TypeId, type_name}, cell::{Cell, Ref, RefCell, RefMut}, collections::{HashMap, HashSet, VecDeque}, fmt, io, marker::PhantomData, ops::{Deref, DerefMut}, rc::Rc, time::{Duration, Instant}};
 
 use glutin::{config::Config, context::PossiblyCurrentContext, surface::{WindowSurface}};
-use skia_safe::{Canvas, Color4f, Image, Matrix, Paint, Rect, Surface, Vector, gpu::DirectContext};
-use winit::{dpi::PhysicalPosition, event::Modifiers, window::Window};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use skia_safe::{Canvas, Color, Color4f, ColorFilter, Image, Matrix, Paint, PaintStyle, Path, PathFillType, Point, Rect, SamplingOptions, Surface, TextBlob, Vector, canvas::SrcRectConstraint, gpu::DirectContext};
+use winit::{event::Modifiers, window::Window};
 
-use crate::InteractableState;
+use crate::assets::{Assets, Handle, SpriteImage};
+use crate::atlas::{AtlasIcon, TextureAtlas};
+use crate::draw::{DrawScope, TransformStack};
+use crate::dynamic::DynamicLayout;
+use crate::filters::{GradientMap, OutlinePosition, outline_filter, posterize_color_filter};
+use crate::geometry::{contains_inclusive, oval_contains, point_in_polygon, round_rect_contains};
+use crate::gpu_timer::GpuTimer;
+use crate::hierarchy::is_hidden;
+use crate::icons::draw_icon;
+use crate::prefab::Prefab;
+use crate::quality::{Quality, QualityScaler};
+use crate::registry::ComponentRegistry;
+use crate::rng::{DEFAULT_SEED, Rng};
+use crate::snapshot::UndoHistory;
+use crate::timers::{TimerId, Timers};
+use crate::units::Document;
+use crate::scene;
+use crate::text::{FontManager, draw_text};
+use crate::wakeup::{WakeSchedule, Waker};
 
-#[derive(Eq, Hash, PartialEq, Copy, Clone)]
-pub struct Entity(pub u32);
+// A slot index plus the generation the slot had when the entity was spawned.
+// Despawning bumps the slot's generation, so stale handles never alias the
+// entity that reuses the slot: storages are keyed by the full Entity.
+#[derive(Eq, Hash, PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct Entity {
+    pub index: u32,
+    pub generation: u32,
+}
+
+impl fmt::Display for Entity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}v{}", self.index, self.generation)
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Debug, Default, Serialize, Deserialize)]
+pub enum InteractableState {
+    #[default]
+    DEFAULT,
+    HOVERED,
+    PRESSED,
+}
+
+impl InteractableState {
+    pub fn color(&self) -> Color4f {
+        match self {
+            InteractableState::DEFAULT => Color4f::new(0.5, 0.5, 0.5, 1.0),
+            InteractableState::HOVERED => Color4f::new(0.6, 0.6, 0.6, 1.0),
+            InteractableState::PRESSED => Color4f::new(0.3, 0.3, 0.3, 1.0),
+        }
+    }
+}
 
 pub struct World {
-    pub entities: Vec<Entity>,
-    pub storages: HashMap<TypeId, RefCell<Box<dyn Any>>>,
+    slots: Vec<EntitySlot>,
+    free: Vec<u32>,
+    pub storages: HashMap<TypeId, RefCell<Box<dyn AnyStorage>>>,
+    // Change detection ticks: `tick` stamps writes made this frame and
+    // Changed/Added match anything stamped after `last_tick`.
+    tick: u64,
+    last_tick: u64,
+    // Entities handed out by Commands::spawn that don't have a slot yet.
+    reserved: Cell<u32>,
+    commands: RefCell<Vec<Command>>,
+    registry: ComponentRegistry,
+    prefabs: HashMap<String, Prefab>,
+    // Name -> entities with it, in the order they were named; maintained by
+    // insert/remove/despawn.
+    names: HashMap<String, Vec<Entity>>,
+    hooks: HashMap<TypeId, ComponentHooks>,
+    document: Document,
+    // Marker types removed from every entity by clear_trackers.
+    frame_markers: Vec<(TypeId, fn(&mut World))>,
+    // Components declared at runtime, by layout name.
+    dynamic: HashMap<String, RefCell<DynamicStorage>>,
+}
+
+type Command = Box<dyn FnOnce(&mut World)>;
+
+fn clear_frame_marker<T: Component>(world: &mut World) {
+    world.clear_markers::<T>();
+}
+
+type Hook = Rc<dyn Fn(&mut World, Entity)>;
+
+#[derive(Default)]
+struct ComponentHooks {
+    on_insert: Vec<Hook>,
+    on_remove: Vec<Hook>,
+}
+
+// Chainable spawning: world.spawn_entity().with(a).with(b).id().
+pub struct EntityBuilder<'w> {
+    world: &'w mut World,
+    entity: Entity,
+}
+
+// Records structural changes while the World is only borrowed, e.g. from
+// inside a query loop. Nothing happens until World::flush.
+pub struct Commands<'w> {
+    world: &'w World,
+}
+
+struct EntitySlot {
+    generation: u32,
+    alive: bool,
 }
 
 /*
@@ -29,7 +129,28 @@ pub struct World {
 pub struct Resources {
     pub gpu_state: Option<GpuState>,
     pub keyboard_state: KeyboardState,
-    pub mouse_state: MouseState,
+    pub pointer_state: PointerState,
+    pub time: Time,
+    pub frame_scheduler: FrameScheduler,
+    pub damage: DamageTracker,
+    pub rng: Rng,
+    pub selection: Selection,
+    pub rubber_band: Option<RubberBand>,
+    pub interactions: Events<InteractionEvent>,
+    // None without an event loop, e.g. in tests.
+    pub waker: Option<Waker>,
+    pub wake_schedule: WakeSchedule,
+    pub timers: Timers,
+    pub undo: UndoHistory,
+    pub quality: QualityScaler,
+    pub fonts: FontManager,
+    pub assets: Assets,
+    // Icons packed from image files at startup; Icons named after one draw
+    // from it.
+    pub atlas: Option<TextureAtlas>,
+    // Anything else global (themes, caches, clocks) keyed by type, so it can
+    // be added without growing this struct.
+    extra: HashMap<TypeId, Box<dyn Any>>,
 }
 
 pub struct GpuState {
@@ -39,25 +160,144 @@ pub struct GpuState {
     pub gr_context: DirectContext,
     pub skia_surface: Option<Surface>,
     pub window: Rc<Window>,
+    // Per-pass GPU timings, while profiling is switched on.
+    pub timer: Option<GpuTimer>,
 }
 
 pub struct KeyboardState {
-    modifiers: Modifiers,
+    pub modifiers: Modifiers,
+}
+
+#[derive(Eq, Hash, PartialEq, Copy, Clone)]
+pub enum PointerButton {
+    Left,
+    Right,
+    Middle,
+    Other(u16),
+}
+
+pub struct PointerState {
+    pub position: Point,
+    pub prev_position: Point,
+    pub buttons: HashSet<PointerButton>,
+    pub window_to_document: Matrix,
+    samples: Vec<Point>,
+    // The double-click window of the last click, while it's open.
+    last_click: Option<(TimerId, Point)>,
+}
+
+pub struct Time {
+    pub delta: Duration,
+    pub elapsed: Duration,
+    pub refresh_rate_hz: f32,
+    start: Instant,
+    last_frame: Instant,
+}
+
+pub struct FrameScheduler {
+    pub frames_presented: u64,
+    redraw_pending: bool,
 }
 
-pub struct MouseState {
-    prev_cursor_pos: PhysicalPosition<f32>,
+// Window-space damage of the most recent frames, newest first. `None` marks a
+// frame that repainted everything.
+pub struct DamageTracker {
+    pending: Option<Rect>,
+    full: bool,
+    history: VecDeque<Option<Rect>>,
 }
 
+// Double-buffered event channel: events sent during one frame stay
+// readable through the next, then update() drops them. Each reader keeps
+// its own cursor, so it sees every event once if it reads every frame.
+pub struct Events<T> {
+    previous: Vec<T>,
+    current: Vec<T>,
+    // Id of the first event in `previous`; ids only ever grow.
+    start: usize,
+}
+
+pub struct EventReader<T> {
+    next: usize,
+    _marker: PhantomData<T>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum InteractionEvent {
+    HoverEntered(Entity),
+    HoverExited(Entity),
+    Clicked(Entity),
+}
+
+// Selected entities in the order they were picked; the first one is the
+// key object for alignment-style commands.
+pub struct Selection {
+    entities: Vec<Entity>,
+    // The group being edited; clicks select among its children instead of
+    // picking the outermost group. `None` is the top level.
+    pub scope: Option<Entity>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum SelectionMode {
+    Replace,
+    Add,
+    Subtract,
+}
+
+// A drag-selection in progress, in document space. `base` is the selection
+// when the drag started, so the result can be recomputed on every move.
+pub struct RubberBand {
+    pub anchor: Point,
+    pub current: Point,
+    pub mode: SelectionMode,
+    base: Vec<Entity>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Bounds {
+    #[serde(with = "scene::rect")]
+    pub rect: Rect,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Outline {
+    #[serde(with = "scene::color")]
+    pub color: Color,
+    pub size: f32,
+    pub position: OutlinePosition,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Slice {
+    pub name: String,
+    #[serde(with = "scene::rect")]
     pub rect: Rect,
+    // Normalized anchor within `rect`, written to sprite sheet metadata.
+    #[serde(with = "scene::point")]
+    pub pivot: Point,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Quad {
+    #[serde(with = "scene::rect")]
     pub rect: Rect,
+    #[serde(with = "scene::color4f")]
     pub color: Color4f,
 }
 
+impl Quad {
+    // What the quad is filled with: its own color, or the hover or press
+    // color while the pointer is on it. Worked out when drawing, so
+    // hovering never edits the document.
+    pub fn fill(&self, interactable: Option<&Interactable>) -> Color4f {
+        match interactable.map(|interactable| &interactable.state) {
+            Some(state) if *state != InteractableState::DEFAULT => state.color(),
+            _ => self.color,
+        }
+    }
+}
+
 struct CanvasSurface {
     surface: Surface,
 }
@@ -72,22 +312,435 @@ struct DrawingState {
     is_drawing: bool,
 }
 
+// Sparse set: components are packed in `dense` (with their ticks and owners
+// at the same positions) so iteration is contiguous, and `sparse` maps an
+// entity's slot index to its dense position for O(1) lookups. Removal swaps
+// the last component into the hole, so order is insertion order only until
+// something is removed.
 pub struct Storage<T> {
-    pub data: HashMap<Entity, T>,
+    dense: Vec<T>,
+    ticks: Vec<ComponentTicks>,
+    owners: Vec<Entity>,
+    sparse: Vec<Option<u32>>,
+    // The World's tick as of the last mutable borrow; stamped by Mut<T>.
+    tick: u64,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ComponentTicks {
+    pub added: u64,
+    pub changed: u64,
+}
+
+// Mutable access to a component that only marks it changed when it is
+// actually written through, so systems can read via query_mut for free.
+pub struct Mut<'a, T> {
+    value: &'a mut T,
+    ticks: &'a mut ComponentTicks,
+    tick: u64,
+}
+
+impl<'a, T> Deref for Mut<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<'a, T> DerefMut for Mut<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.ticks.changed = self.tick;
+        self.value
+    }
+}
+
+impl<T> Storage<T> {
+    fn new(tick: u64) -> Self {
+        Storage { dense: Vec::new(), ticks: Vec::new(), owners: Vec::new(), sparse: Vec::new(), tick }
+    }
+
+    // Dense position of `entity`'s component; the owner check rejects stale
+    // handles whose slot now belongs to a newer generation.
+    fn position(&self, entity: Entity) -> Option<usize> {
+        let position = (*self.sparse.get(entity.index as usize)?)? as usize;
+        (self.owners[position] == entity).then_some(position)
+    }
+
+    pub fn len(&self) -> usize {
+        self.dense.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dense.is_empty()
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.position(entity).is_some()
+    }
+
+    pub fn get(&self, entity: Entity) -> Option<&T> {
+        self.position(entity).map(|position| &self.dense[position])
+    }
+
+    pub fn get_mut(&mut self, entity: Entity) -> Option<Mut<'_, T>> {
+        let position = self.position(entity)?;
+        Some(Mut { value: &mut self.dense[position], ticks: &mut self.ticks[position], tick: self.tick })
+    }
+
+    pub fn ticks(&self, entity: Entity) -> Option<ComponentTicks> {
+        self.position(entity).map(|position| self.ticks[position])
+    }
+
+    pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.owners.iter().copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &T)> {
+        self.owners.iter().copied().zip(self.dense.iter())
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, Mut<'_, T>)> {
+        let tick = self.tick;
+        self.owners.iter().copied()
+            .zip(self.dense.iter_mut().zip(self.ticks.iter_mut()))
+            .map(move |(entity, (value, ticks))| (entity, Mut { value, ticks, tick }))
+    }
+
+    // Replacing a component counts as adding it again.
+    fn insert(&mut self, entity: Entity, value: T) {
+        let ticks = ComponentTicks { added: self.tick, changed: self.tick };
+        let index = entity.index as usize;
+        if let Some(Some(position)) = self.sparse.get(index) {
+            let position = *position as usize;
+            self.dense[position] = value;
+            self.ticks[position] = ticks;
+            self.owners[position] = entity;
+            return;
+        }
+        if self.sparse.len() <= index {
+            self.sparse.resize(index + 1, None);
+        }
+        self.sparse[index] = Some(self.dense.len() as u32);
+        self.dense.push(value);
+        self.ticks.push(ticks);
+        self.owners.push(entity);
+    }
+
+    // Room for `additional` more components, with the sparse array already
+    // covering `max_index`.
+    fn reserve(&mut self, additional: usize, max_index: u32) {
+        self.dense.reserve(additional);
+        self.ticks.reserve(additional);
+        self.owners.reserve(additional);
+        if self.sparse.len() <= max_index as usize {
+            self.sparse.resize(max_index as usize + 1, None);
+        }
+    }
+
+    fn remove(&mut self, entity: Entity) -> Option<T> {
+        let position = self.position(entity)?;
+        self.sparse[entity.index as usize] = None;
+        self.ticks.swap_remove(position);
+        self.owners.swap_remove(position);
+        if let Some(moved) = self.owners.get(position) {
+            self.sparse[moved.index as usize] = Some(position as u32);
+        }
+        Some(self.dense.swap_remove(position))
+    }
+}
+
+// Type-erased view of a Storage<T>, so the World can act on every storage
+// (e.g. despawning an entity) without knowing the component types.
+pub trait AnyStorage {
+    fn contains_entity(&self, entity: Entity) -> bool;
+    fn remove_entity(&mut self, entity: Entity);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: 'static> AnyStorage for Storage<T> {
+    fn contains_entity(&self, entity: Entity) -> bool {
+        self.contains(entity)
+    }
+    fn remove_entity(&mut self, entity: Entity) {
+        self.remove(entity);
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// The values of one dynamic component, with the same sparse set and change
+// ticks as a typed Storage. Writes go through `set` so they're checked
+// against the layout.
+pub struct DynamicStorage {
+    layout: DynamicLayout,
+    values: Storage<Value>,
+}
+
+impl DynamicStorage {
+    pub fn layout(&self) -> &DynamicLayout {
+        &self.layout
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.values.contains(entity)
+    }
+
+    pub fn get(&self, entity: Entity) -> Option<&Value> {
+        self.values.get(entity)
+    }
+
+    pub fn field(&self, entity: Entity, field: &str) -> Option<&Value> {
+        self.get(entity)?.get(field)
+    }
+
+    pub fn ticks(&self, entity: Entity) -> Option<ComponentTicks> {
+        self.values.ticks(entity)
+    }
+
+    pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.values.entities()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &Value)> {
+        self.values.iter()
+    }
+
+    // False if `entity` doesn't have the component.
+    pub fn set(&mut self, entity: Entity, field: &str, value: Value) -> io::Result<bool> {
+        self.layout.validate_field(field, &value)?;
+        let Some(mut component) = self.values.get_mut(entity) else { return Ok(false); };
+        if let Some(fields) = component.as_object_mut() {
+            fields.insert(field.to_string(), value);
+        }
+        Ok(true)
+    }
 }
 
+// Marker for types that can be stored on entities and queried.
+pub trait Component: 'static {}
+
+impl Component for Bounds {}
+impl Component for Outline {}
+impl Component for Slice {}
+impl Component for Quad {}
+impl Component for DirtyVisual {}
+impl Component for Interactable {}
+impl Component for Parallax {}
+impl Component for Transform {}
+impl Component for Parent {}
+impl Component for Children {}
+impl Component for Locked {}
+impl Component for Hidden {}
+impl Component for Constraint {}
+impl Component for Name {}
+impl Component for Opacity {}
+impl Component for Adjustment {}
+impl Component for Shape {}
+impl Component for InteractionPriority {}
+impl Component for BlocksInput {}
+impl Component for ZIndex {}
+impl Component for Icon {}
+impl Component for Text {}
+impl Component for TextLayout {}
+impl Component for TextAlign {}
+impl Component for Sprite {}
+
+// Set on entities whose look changed this frame; a frame marker, so
+// clear_trackers drops it.
+#[derive(Debug)]
 pub struct DirtyVisual;
 
+// Hover and press state is the pointer's, not the document's, so it isn't
+// saved or captured for undo; a loaded Interactable starts at DEFAULT.
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Interactable {
+    #[serde(skip)]
     pub state: InteractableState,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Parallax {
     pub strength: f32,
 }
 
-#[derive(Clone, Copy)]
+// Groups are plain entities with Children and Bounds covering them; each
+// child points back with Parent.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Parent(pub Entity);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Children(pub Vec<Entity>);
+
+// Layout rules resolved against the parent's Bounds, or the window for
+// top-level entities. Pins are offsets from the matching parent edge;
+// pinning both opposite edges stretches the entity between them.
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+pub struct Constraint {
+    pub clamp_to_parent: bool,
+    pub aspect_ratio: Option<f32>,
+    pub pin_left: Option<f32>,
+    pub pin_top: Option<f32>,
+    pub pin_right: Option<f32>,
+    pub pin_bottom: Option<f32>,
+}
+
+// A label systems can look entities up by with World::find_by_name. Names
+// are expected to be unique; when they aren't, the entity named last that
+// still has the name wins.
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub struct Name(pub String);
+
+// The outline of an entity within its rect, for drawing and hit testing
+// alike; entities without one are plain rects. Polygon points are
+// normalized to the rect, so they follow it when it moves or resizes.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Shape {
+    RoundRect { radius: f32 },
+    Oval,
+    Polygon(Vec<(f32, f32)>),
+}
+
+impl Shape {
+    pub fn contains(&self, rect: Rect, point: Point) -> bool {
+        match self {
+            Shape::RoundRect { radius } => round_rect_contains(rect, *radius, point),
+            Shape::Oval => oval_contains(rect, point),
+            Shape::Polygon(points) => contains_inclusive(rect, point) && point_in_polygon(point, &Shape::polygon_points(rect, points)),
+        }
+    }
+
+    pub fn draw(&self, canvas: &Canvas, rect: Rect, paint: &Paint) {
+        match self {
+            Shape::RoundRect { radius } => { canvas.draw_round_rect(rect, *radius, *radius, paint); }
+            Shape::Oval => { canvas.draw_oval(rect, paint); }
+            Shape::Polygon(points) => {
+                // Even-odd, to match point_in_polygon.
+                let path = Path::polygon(&Shape::polygon_points(rect, points), true, PathFillType::EvenOdd, None);
+                canvas.draw_path(&path, paint);
+            }
+        }
+    }
+
+    fn polygon_points(rect: Rect, points: &[(f32, f32)]) -> Vec<Point> {
+        points.iter()
+            .map(|(x, y)| Point::new(rect.left + x * rect.width(), rect.top + y * rect.height()))
+            .collect()
+    }
+}
+
+// Overlapping entities get input in order of priority, highest first;
+// entities without one are at 0. Overlays (popups, gizmos, dialogs) sit
+// above the content they cover.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct InteractionPriority(pub i32);
+
+// Nothing below this entity where it is hit gets input, interactive or
+// not, e.g. a modal dialog's backdrop.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct BlocksInput;
+
+// A bundled vector icon (see icons::icon_names), or the icon atlas image
+// of that name when there is one, `size` pixels square, centered on the
+// entity's quad, or on its left end next to a Text.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Icon {
+    pub name: String,
+    #[serde(with = "scene::color")]
+    pub tint: Color,
+    pub size: f32,
+}
+
+// A single line of text, `size` pixels tall, centered on the entity's quad
+// (right of its Icon, if any) and clipped to it. `font` is a family name; unknown families fall back
+// to the system default.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Text {
+    pub content: String,
+    pub size: f32,
+    #[serde(with = "scene::color")]
+    pub color: Color,
+    pub font: String,
+}
+
+// Where a Text sits across its rect; centered without one.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum TextAlign {
+    Left,
+    Center,
+}
+
+// Shaped Text, rebuilt by text_layout_system whenever the Text changes.
+#[derive(Clone)]
+pub struct TextLayout {
+    pub blob: Option<TextBlob>,
+    pub width: f32,
+    // Distance from the top of the line to the baseline.
+    pub ascent: f32,
+    pub descent: f32,
+}
+
+// An image from Assets stretched over `dst_rect`, or just its `src_rect`
+// part when there is one, e.g. a frame of a sprite sheet.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Sprite {
+    pub image: Handle<Image>,
+    #[serde(default, with = "scene::option_rect")]
+    pub src_rect: Option<Rect>,
+    #[serde(with = "scene::rect")]
+    pub dst_rect: Rect,
+}
+
+// Draw order: higher is drawn later, on top. Entities without one are
+// below every entity that has one, in storage order.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
+pub struct ZIndex(pub i32);
+
+// Alpha applied to the entity as a whole when it's drawn.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Opacity(pub f32);
+
+// A color filter applied to the entity as a whole when it's drawn, like
+// Opacity, so it can be changed or removed later.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Adjustment {
+    GradientMap(GradientMap),
+    Posterize { levels: u8 },
+}
+
+impl Adjustment {
+    pub fn color_filter(&self) -> Option<ColorFilter> {
+        match self {
+            Adjustment::GradientMap(map) => map.color_filter(),
+            Adjustment::Posterize { levels } => posterize_color_filter(*levels),
+        }
+    }
+}
+
+// Editor flags, inherited by everything inside a flagged group. Hidden
+// entities aren't drawn, exported or hit; locked ones can't be picked.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Locked;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Hidden;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct Transform {
+    #[serde(with = "scene::matrix")]
     pub local_to_parent: Matrix,
     pub z: f32,
 }
@@ -100,146 +753,1401 @@ pub struct ViewMut<'a, T> {
 }
 impl<'a, T> View<'a, T> {
     pub fn get(&self, entity: Entity) -> Option<&T> {
-        self.storage.data.get(&entity)
+        self.storage.get(entity)
     }
     pub fn iter(&self) -> impl Iterator<Item = (Entity, &T)> {
-        self.storage.data.iter().map(|(e, c)| (*e, c))
+        self.storage.iter()
     }
 }
 impl<'a, T> ViewMut<'a, T> {
-    pub fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
-        self.storage.data.get_mut(&entity)
+    pub fn get_mut(&mut self, entity: Entity) -> Option<Mut<'_, T>> {
+        self.storage.get_mut(entity)
     }
-    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
-        self.storage.data.iter_mut().map(|(e, c)| (*e, c))
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, Mut<'_, T>)> {
+        self.storage.iter_mut()
     }
 }
 
-impl World {
-    pub fn new() -> Self {
-        World {
-            entities: vec!(),
-            storages: HashMap::new(),
+pub struct Query<'w, Q: QueryData> {
+    world: &'w World,
+    fetch: Option<Q::Fetch<'w>>,
+    filters: Vec<Box<dyn Fn(Entity) -> bool + 'w>>,
+}
+
+impl<'w, Q: QueryData> Query<'w, Q> {
+    pub fn filter<F: QueryFilter>(mut self) -> Self {
+        match F::build(self.world) {
+            Some(filter) => self.filters.push(filter),
+            None => self.fetch = None,
         }
+        self
     }
 
-    pub fn spawn(self: &mut Self) -> Entity {
-        let next = self.entities.last().unwrap_or(&Entity(0)).0 + 1;
-        let new_entity = Entity(next);
-        self.entities.push(new_entity);
-        new_entity
+    // Only entities that also have a `T`, without borrowing `T` for the items.
+    pub fn with<T: Component>(self) -> Self {
+        self.filter::<With<T>>()
     }
 
-    pub fn insert<T: 'static>(self: &mut Self, entity: Entity, component: T) {
-        let type_id = TypeId::of::<T>();
+    pub fn without<T: Component>(self) -> Self {
+        self.filter::<Without<T>>()
+    }
 
-        let cell = self.storages.entry(type_id).or_insert_with(|| {
-            RefCell::new(Box::new(Storage::<T> {
-                data: HashMap::new(),
-            }))
-        });
+    // Entities whose `T` was added, or written through a Mut<T>, since the
+    // last World::clear_trackers.
+    pub fn changed<T: Component>(self) -> Self {
+        self.filter::<Changed<T>>()
+    }
 
-        let mut storage_any = cell.borrow_mut();
-        let storage = storage_any.downcast_mut::<Storage<T>>().unwrap();
-        storage.data.insert(entity, component);
+    pub fn added<T: Component>(self) -> Self {
+        self.filter::<Added<T>>()
     }
 
-    pub fn storage<T: 'static>(&self) -> Option<Ref<Storage<T>>> {
-        let cell = self.storages.get(&TypeId::of::<T>())?;
+    // The same filters for dynamic components, by name. An unregistered
+    // name matches nothing, or everything for without_dynamic.
+    pub fn with_dynamic(mut self, name: &str) -> Self {
+        match self.world.dynamic_storage(name) {
+            Some(storage) => self.filters.push(Box::new(move |entity| storage.contains(entity))),
+            None => self.fetch = None,
+        }
+        self
+    }
 
-        Some(Ref::map(cell.borrow(), |boxed| {
-            boxed.downcast_ref::<Storage<T>>().unwrap()
-        }))
+    pub fn without_dynamic(mut self, name: &str) -> Self {
+        if let Some(storage) = self.world.dynamic_storage(name) {
+            self.filters.push(Box::new(move |entity| !storage.contains(entity)));
+        }
+        self
     }
 
-    pub fn storage_mut<T: 'static>(&self) -> Option<RefMut<Storage<T>>> {
-        let cell = self.storages.get(&TypeId::of::<T>())?;
+    pub fn changed_dynamic(mut self, name: &str) -> Self {
+        let last_tick = self.world.last_tick;
+        match self.world.dynamic_storage(name) {
+            Some(storage) => self.filters.push(Box::new(move |entity| {
+                storage.ticks(entity).is_some_and(|ticks| ticks.changed > last_tick)
+            })),
+            None => self.fetch = None,
+        }
+        self
+    }
 
-        Some(RefMut::map(cell.borrow_mut(), |boxed| {
-            boxed.downcast_mut::<Storage<T>>().unwrap()
-        }))
+    // Empty when any of the queried storages doesn't exist yet.
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, Q::Item<'_>)> + '_ {
+        self.fetch.iter().flat_map(|fetch| {
+            Q::driver(fetch)
+                .filter(|entity| self.filters.iter().all(|filter| filter(*entity)))
+                .filter_map(move |entity| Q::get(fetch, entity).map(|item| (entity, item)))
+        })
     }
 
-    pub fn view<T: 'static>(&self) -> View<T> {
-        println!("{}", type_name::<T>());
-        View {
-            storage: self.storage::<T>().expect("Storage not initialized")
-        }
+    // The items in ascending order of each entity's `T`, entities without
+    // one first. Ties keep query order.
+    pub fn sorted_by_component<T: Component + Ord>(&self) -> Vec<(Entity, Q::Item<'_>)> {
+        let keys = self.world.storage::<T>();
+        let key = |entity| keys.as_ref().and_then(|storage| storage.get(entity));
+        let mut items: Vec<_> = self.iter().collect();
+        items.sort_by(|(a, _), (b, _)| key(*a).cmp(&key(*b)));
+        items
     }
-    pub fn view_mut<T: 'static>(&self) -> ViewMut<T> {
-        ViewMut { 
-            storage: self.storage_mut::<T>().expect("Storage not initialized (mut version)")
-        }
+}
+
+impl<'q, 'w, Q: QueryData> IntoIterator for &'q Query<'w, Q> {
+    type Item = (Entity, Q::Item<'q>);
+    type IntoIter = Box<dyn Iterator<Item = Self::Item> + 'q>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Box::new(self.iter())
     }
+}
 
-    pub fn query<T: 'static, F>(&self, mut f: F) 
-    where 
-        F: FnMut(Entity, &T) 
-    {
-        if let Some(store) = self.storage::<T>() {
-            for (entity, component) in store.data.iter() {
-                f(*entity, component);
-            }
+pub trait QueryFilter {
+    // `None` when nothing can match, e.g. the storage doesn't exist yet.
+    fn build(world: &World) -> Option<Box<dyn Fn(Entity) -> bool + '_>>;
+}
+
+pub struct With<T>(PhantomData<T>);
+pub struct Without<T>(PhantomData<T>);
+pub struct Changed<T>(PhantomData<T>);
+pub struct Added<T>(PhantomData<T>);
+
+impl<T: Component> QueryFilter for With<T> {
+    fn build(world: &World) -> Option<Box<dyn Fn(Entity) -> bool + '_>> {
+        let storage = world.storage::<T>()?;
+        Some(Box::new(move |entity| storage.contains(entity)))
+    }
+}
+
+impl<T: Component> QueryFilter for Without<T> {
+    fn build(world: &World) -> Option<Box<dyn Fn(Entity) -> bool + '_>> {
+        match world.storage::<T>() {
+            Some(storage) => Some(Box::new(move |entity| !storage.contains(entity))),
+            None => Some(Box::new(|_| true)),
         }
     }
+}
 
-    pub fn query2<A: 'static, B: 'static, F>(&self, mut f: F)
-    where
-        F: FnMut(Entity, &A, &B)
-    {
-        let a_store = self.storage::<A>();
-        let b_store = self.storage::<B>();
+impl<T: Component> QueryFilter for Changed<T> {
+    fn build(world: &World) -> Option<Box<dyn Fn(Entity) -> bool + '_>> {
+        let storage = world.storage::<T>()?;
+        let last_tick = world.last_tick;
+        Some(Box::new(move |entity| storage.ticks(entity).is_some_and(|ticks| ticks.changed > last_tick)))
+    }
+}
 
-        if let (Some(a), Some(b)) = (a_store, b_store) {
-            for (entity, a_comp) in a.data.iter() {
-                if let Some(b_comp) = b.data.get(entity) {
-                    f(*entity, a_comp, b_comp);
-                }
-            }
-        }
+impl<T: Component> QueryFilter for Added<T> {
+    fn build(world: &World) -> Option<Box<dyn Fn(Entity) -> bool + '_>> {
+        let storage = world.storage::<T>()?;
+        let last_tick = world.last_tick;
+        Some(Box::new(move |entity| storage.ticks(entity).is_some_and(|ticks| ticks.added > last_tick)))
     }
+}
 
-    pub fn query3<A: 'static, B: 'static, C: 'static, F>(&self, mut f: F)
-    where
-        F: FnMut(Entity, &A, &B, &C)
-    {
-        let a_store = self.storage::<A>();
-        let b_store = self.storage::<B>();
-        let c_store = self.storage::<C>();
-
-        if let (Some(a), Some(b), Some(c)) = (a_store, b_store, c_store) {
-            for (entity, a_comp) in a.data.iter() {
-                if let Some(b_comp) = b.data.get(entity) {
-                    if let Some(c_comp) = c.data.get(entity) {
-                        f(*entity, a_comp, b_comp, c_comp);
-                    }
-                }
+pub trait QueryData {
+    type Fetch<'w>;
+    type Item<'f>;
+
+    fn fetch(world: &World) -> Option<Self::Fetch<'_>>;
+    // Candidate entities; the smallest storage involved, to keep lookups down.
+    fn driver<'f>(fetch: &'f Self::Fetch<'_>) -> Box<dyn Iterator<Item = Entity> + 'f>;
+    fn get<'f>(fetch: &'f Self::Fetch<'_>, entity: Entity) -> Option<Self::Item<'f>>;
+}
+
+impl<T: Component> QueryData for T {
+    type Fetch<'w> = Ref<'w, Storage<T>>;
+    type Item<'f> = &'f T;
+
+    fn fetch(world: &World) -> Option<Self::Fetch<'_>> {
+        world.storage::<T>()
+    }
+    fn driver<'f>(fetch: &'f Self::Fetch<'_>) -> Box<dyn Iterator<Item = Entity> + 'f> {
+        Box::new(fetch.entities())
+    }
+    fn get<'f>(fetch: &'f Self::Fetch<'_>, entity: Entity) -> Option<Self::Item<'f>> {
+        fetch.get(entity)
+    }
+}
+
+macro_rules! impl_query_data_tuple {
+    ($(($T:ident, $i:tt)),+) => {
+        impl<$($T: Component),+> QueryData for ($($T,)+) {
+            type Fetch<'w> = ($(Ref<'w, Storage<$T>>,)+);
+            type Item<'f> = ($(&'f $T,)+);
+
+            fn fetch(world: &World) -> Option<Self::Fetch<'_>> {
+                Some(($(world.storage::<$T>()?,)+))
+            }
+            fn driver<'f>(fetch: &'f Self::Fetch<'_>) -> Box<dyn Iterator<Item = Entity> + 'f> {
+                let lens = [$(fetch.$i.len()),+];
+                let smallest = (0..lens.len()).min_by_key(|i| lens[*i]).unwrap_or(0);
+                $(if smallest == $i { return Box::new(fetch.$i.entities()); })+
+                unreachable!()
+            }
+            fn get<'f>(fetch: &'f Self::Fetch<'_>, entity: Entity) -> Option<Self::Item<'f>> {
+                Some(($(fetch.$i.get(entity)?,)+))
             }
         }
+    };
+}
+
+impl_query_data_tuple!((A, 0));
+impl_query_data_tuple!((A, 0), (B, 1));
+impl_query_data_tuple!((A, 0), (B, 1), (C, 2));
+impl_query_data_tuple!((A, 0), (B, 1), (C, 2), (D, 3));
+impl_query_data_tuple!((A, 0), (B, 1), (C, 2), (D, 3), (E, 4));
+impl_query_data_tuple!((A, 0), (B, 1), (C, 2), (D, 3), (E, 4), (F, 5));
+impl_query_data_tuple!((A, 0), (B, 1), (C, 2), (D, 3), (E, 4), (F, 5), (G, 6));
+impl_query_data_tuple!((A, 0), (B, 1), (C, 2), (D, 3), (E, 4), (F, 5), (G, 6), (H, 7));
+impl_query_data_tuple!((A, 0), (B, 1), (C, 2), (D, 3), (E, 4), (F, 5), (G, 6), (H, 7), (I, 8));
+impl_query_data_tuple!((A, 0), (B, 1), (C, 2), (D, 3), (E, 4), (F, 5), (G, 6), (H, 7), (I, 8), (J, 9));
+impl_query_data_tuple!((A, 0), (B, 1), (C, 2), (D, 3), (E, 4), (F, 5), (G, 6), (H, 7), (I, 8), (J, 9), (K, 10));
+impl_query_data_tuple!((A, 0), (B, 1), (C, 2), (D, 3), (E, 4), (F, 5), (G, 6), (H, 7), (I, 8), (J, 9), (K, 10), (L, 11));
+
+// The values of a dynamic component. Its storage is picked by name, so
+// only World::query_dynamic makes these queries; query::<Dynamic> is empty.
+pub struct Dynamic;
+
+impl QueryData for Dynamic {
+    type Fetch<'w> = Ref<'w, DynamicStorage>;
+    type Item<'f> = &'f Value;
+
+    fn fetch(_world: &World) -> Option<Self::Fetch<'_>> {
+        None
+    }
+    fn driver<'f>(fetch: &'f Self::Fetch<'_>) -> Box<dyn Iterator<Item = Entity> + 'f> {
+        Box::new(fetch.entities())
+    }
+    fn get<'f>(fetch: &'f Self::Fetch<'_>, entity: Entity) -> Option<Self::Item<'f>> {
+        fetch.get(entity)
     }
 }
 
-impl Resources {
-    pub fn new(gpu_state: GpuState) -> Self {
-        Resources { 
-            gpu_state: Some(gpu_state),
-            keyboard_state: KeyboardState { modifiers: Modifiers::default() },
-            mouse_state: MouseState { prev_cursor_pos: PhysicalPosition { x: 0.0, y: 0.0 } } 
+// Components inserted together. Implemented for single components and
+// tuples; a named bundle implements it by forwarding to a tuple.
+pub trait Bundle: 'static {
+    fn insert_into(self, world: &mut World, entity: Entity);
+
+    // Many bundles at once for World::spawn_batch. The default inserts them
+    // one by one; components and tuples fill each storage in one go.
+    fn insert_batch(batch: Vec<(Entity, Self)>, world: &mut World) where Self: Sized {
+        for (entity, bundle) in batch {
+            bundle.insert_into(world, entity);
         }
     }
 }
 
-pub fn render_quads(world: &World, canvas: &Canvas) {
-    let mut q_view = world.view_mut::<Quad>();
-    let t_view = world.view::<Transform>();
+impl<T: Component> Bundle for T {
+    fn insert_into(self, world: &mut World, entity: Entity) {
+        world.insert(entity, self);
+    }
+
+    fn insert_batch(batch: Vec<(Entity, Self)>, world: &mut World) {
+        world.insert_many(batch);
+    }
+}
+
+macro_rules! impl_bundle_tuple {
+    ($(($T:ident, $i:tt)),+) => {
+        impl<$($T: Component),+> Bundle for ($($T,)+) {
+            fn insert_into(self, world: &mut World, entity: Entity) {
+                $(world.insert(entity, self.$i);)+
+            }
 
-    for (entity, quad) in q_view.iter_mut() {
-        canvas.save();
-        if let Some(transform) = t_view.storage.data.get(&entity) {
-            canvas.concat(&transform.local_to_parent);
+            fn insert_batch(batch: Vec<(Entity, Self)>, world: &mut World) {
+                let mut columns = ($(Vec::<(Entity, $T)>::with_capacity(batch.len()),)+);
+                for (entity, bundle) in batch {
+                    $(columns.$i.push((entity, bundle.$i));)+
+                }
+                $(world.insert_many(columns.$i);)+
+            }
         }
-        let paint = Paint::new(quad.color, None);
-        canvas.draw_rect(quad.rect, &paint);
-        canvas.restore();
+    };
+}
+
+impl_bundle_tuple!((A, 0));
+impl_bundle_tuple!((A, 0), (B, 1));
+impl_bundle_tuple!((A, 0), (B, 1), (C, 2));
+impl_bundle_tuple!((A, 0), (B, 1), (C, 2), (D, 3));
+impl_bundle_tuple!((A, 0), (B, 1), (C, 2), (D, 3), (E, 4));
+impl_bundle_tuple!((A, 0), (B, 1), (C, 2), (D, 3), (E, 4), (F, 5));
+impl_bundle_tuple!((A, 0), (B, 1), (C, 2), (D, 3), (E, 4), (F, 5), (G, 6));
+impl_bundle_tuple!((A, 0), (B, 1), (C, 2), (D, 3), (E, 4), (F, 5), (G, 6), (H, 7));
+impl_bundle_tuple!((A, 0), (B, 1), (C, 2), (D, 3), (E, 4), (F, 5), (G, 6), (H, 7), (I, 8));
+impl_bundle_tuple!((A, 0), (B, 1), (C, 2), (D, 3), (E, 4), (F, 5), (G, 6), (H, 7), (I, 8), (J, 9));
+impl_bundle_tuple!((A, 0), (B, 1), (C, 2), (D, 3), (E, 4), (F, 5), (G, 6), (H, 7), (I, 8), (J, 9), (K, 10));
+impl_bundle_tuple!((A, 0), (B, 1), (C, 2), (D, 3), (E, 4), (F, 5), (G, 6), (H, 7), (I, 8), (J, 9), (K, 10), (L, 11));
+
+impl World {
+    pub fn new() -> Self {
+        let mut world = World {
+            slots: vec!(),
+            free: vec!(),
+            storages: HashMap::new(),
+            tick: 1,
+            last_tick: 0,
+            reserved: Cell::new(0),
+            commands: RefCell::new(Vec::new()),
+            registry: ComponentRegistry::with_builtin(),
+            prefabs: HashMap::new(),
+            names: HashMap::new(),
+            hooks: HashMap::new(),
+            document: Document::default(),
+            frame_markers: Vec::new(),
+            dynamic: HashMap::new(),
+        };
+        world.frame_marker::<DirtyVisual>();
+        world
+    }
+
+    pub fn spawn(self: &mut Self) -> Entity {
+        self.spawn_reserved();
+        if let Some(index) = self.free.pop() {
+            let slot = &mut self.slots[index as usize];
+            slot.alive = true;
+            return Entity { index, generation: slot.generation };
+        }
+        let index = self.slots.len() as u32;
+        self.slots.push(EntitySlot { generation: 0, alive: true });
+        Entity { index, generation: 0 }
+    }
+
+    // Reserved entities always get fresh slots past the end, so their
+    // handles can be given out without touching the free list.
+    fn spawn_reserved(self: &mut Self) {
+        for _ in 0..self.reserved.take() {
+            self.slots.push(EntitySlot { generation: 0, alive: true });
+        }
+    }
+
+    pub fn commands(&self) -> Commands<'_> {
+        Commands { world: self }
+    }
+
+    // Applies everything recorded through commands() in order. Reserved
+    // entities become alive first so commands can insert onto them.
+    pub fn flush(self: &mut Self) {
+        self.spawn_reserved();
+        let commands = std::mem::take(self.commands.get_mut());
+        for command in commands {
+            command(self);
+        }
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.slots.get(entity.index as usize)
+            .is_some_and(|slot| slot.alive && slot.generation == entity.generation)
+    }
+
+    pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.slots.iter().enumerate()
+            .filter(|(_, slot)| slot.alive)
+            .map(|(index, slot)| Entity { index: index as u32, generation: slot.generation })
+    }
+
+    // Component types known by name; World::save and World::load go
+    // through it.
+    pub fn registry(&self) -> &ComponentRegistry {
+        &self.registry
+    }
+
+    pub fn registry_mut(&mut self) -> &mut ComponentRegistry {
+        &mut self.registry
+    }
+
+    // Renaming through storage_mut bypasses the index, so insert a new Name
+    // instead; a stale entry is never returned.
+    pub fn find_by_name(&self, name: &str) -> Option<Entity> {
+        let names = self.storage::<Name>()?;
+        self.names.get(name)?.iter().rev()
+            .find(|entity| names.get(**entity).is_some_and(|current| current.0 == name))
+            .copied()
+    }
+
+    fn index_name(self: &mut Self, name: String, entity: Entity) {
+        let previous = self.storage::<Name>().and_then(|names| names.get(entity).cloned());
+        if let Some(previous) = previous {
+            self.unindex_name(&previous.0, entity);
+        }
+        self.names.entry(name).or_default().push(entity);
+    }
+
+    fn unindex_name(self: &mut Self, name: &str, entity: Entity) {
+        if let Some(entities) = self.names.get_mut(name) {
+            entities.retain(|named| *named != entity);
+            if entities.is_empty() {
+                self.names.remove(name);
+            }
+        }
+    }
+
+    pub fn prefabs(&self) -> &HashMap<String, Prefab> {
+        &self.prefabs
+    }
+
+    pub fn prefabs_mut(&mut self) -> &mut HashMap<String, Prefab> {
+        &mut self.prefabs
+    }
+
+    pub fn document(&self) -> &Document {
+        &self.document
+    }
+
+    pub fn document_mut(&mut self) -> &mut Document {
+        &mut self.document
+    }
+
+    // Inserting onto a despawned (stale) entity is refused, which keeps every
+    // storage free of dead keys and lets queries iterate without checking.
+    pub fn insert<T: Component>(self: &mut Self, entity: Entity, component: T) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+        let type_id = TypeId::of::<T>();
+        if self.has_component(type_id, entity) {
+            self.run_hooks(type_id, entity, |hooks| &hooks.on_remove);
+            // A remove hook may have despawned the entity.
+            if !self.is_alive(entity) {
+                return false;
+            }
+        }
+        if let Some(name) = (&component as &dyn Any).downcast_ref::<Name>() {
+            self.index_name(name.0.clone(), entity);
+        }
+        let tick = self.tick;
+
+        {
+            let cell = self.storages.entry(type_id).or_insert_with(|| {
+                RefCell::new(Box::new(Storage::<T>::new(tick)))
+            });
+
+            let mut storage_any = cell.borrow_mut();
+            let storage = storage_any.as_any_mut().downcast_mut::<Storage<T>>().unwrap();
+            storage.tick = tick;
+            storage.insert(entity, component);
+        }
+        self.run_hooks(type_id, entity, |hooks| &hooks.on_insert);
+        true
+    }
+
+    // Runs after a T is added to an entity. Replacing a T counts as removing
+    // the old one and inserting the new one.
+    pub fn on_insert<T: Component>(self: &mut Self, hook: impl Fn(&mut World, Entity) + 'static) {
+        self.hooks.entry(TypeId::of::<T>()).or_default().on_insert.push(Rc::new(hook));
+    }
+
+    // Runs while the T is still there, so the hook can read it. Despawning
+    // counts as removing every component.
+    pub fn on_remove<T: Component>(self: &mut Self, hook: impl Fn(&mut World, Entity) + 'static) {
+        self.hooks.entry(TypeId::of::<T>()).or_default().on_remove.push(Rc::new(hook));
+    }
+
+    fn has_component(&self, type_id: TypeId, entity: Entity) -> bool {
+        self.storages.get(&type_id).is_some_and(|cell| cell.borrow().contains_entity(entity))
+    }
+
+    // Hooks get the whole World, so they're cloned out of it first.
+    fn run_hooks(self: &mut Self, type_id: TypeId, entity: Entity, select: fn(&ComponentHooks) -> &Vec<Hook>) {
+        let Some(hooks) = self.hooks.get(&type_id) else { return; };
+        for hook in select(hooks).clone() {
+            hook(self, entity);
+        }
+    }
+
+    pub fn spawn_bundle<B: Bundle>(self: &mut Self, bundle: B) -> Entity {
+        let entity = self.spawn();
+        bundle.insert_into(self, entity);
+        entity
+    }
+
+    // Spawns one entity per bundle, filling each component storage in a
+    // single pass instead of a lookup per entity.
+    pub fn spawn_batch<B: Bundle>(self: &mut Self, bundles: impl IntoIterator<Item = B>) -> Vec<Entity> {
+        let bundles: Vec<B> = bundles.into_iter().collect();
+        self.slots.reserve(bundles.len().saturating_sub(self.free.len()));
+        let batch: Vec<(Entity, B)> = bundles.into_iter().map(|bundle| (self.spawn(), bundle)).collect();
+        let entities = batch.iter().map(|(entity, _)| *entity).collect();
+        B::insert_batch(batch, self);
+        entities
+    }
+
+    // insert() for many entities with one storage borrow. Hooks and the
+    // Name index need per-entity handling, so those types take the slow path.
+    pub fn insert_many<T: Component>(self: &mut Self, components: Vec<(Entity, T)>) {
+        let type_id = TypeId::of::<T>();
+        if self.hooks.contains_key(&type_id) || type_id == TypeId::of::<Name>() {
+            for (entity, component) in components {
+                self.insert(entity, component);
+            }
+            return;
+        }
+        let tick = self.tick;
+        let Some(max_index) = components.iter().map(|(entity, _)| entity.index).max() else { return; };
+        let cell = self.storages.entry(type_id).or_insert_with(|| {
+            RefCell::new(Box::new(Storage::<T>::new(tick)))
+        });
+        let mut storage_any = cell.borrow_mut();
+        let storage = storage_any.as_any_mut().downcast_mut::<Storage<T>>().unwrap();
+        storage.tick = tick;
+        storage.reserve(components.len(), max_index);
+        for (entity, component) in components {
+            let alive = self.slots.get(entity.index as usize)
+                .is_some_and(|slot| slot.alive && slot.generation == entity.generation);
+            if alive {
+                storage.insert(entity, component);
+            }
+        }
+    }
+
+    pub fn spawn_entity(self: &mut Self) -> EntityBuilder<'_> {
+        let entity = self.spawn();
+        EntityBuilder { world: self, entity }
+    }
+
+    pub fn insert_bundle<B: Bundle>(self: &mut Self, entity: Entity, bundle: B) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+        bundle.insert_into(self, entity);
+        true
+    }
+
+    pub fn remove<T: Component>(self: &mut Self, entity: Entity) -> Option<T> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+        let type_id = TypeId::of::<T>();
+        if self.has_component(type_id, entity) {
+            self.run_hooks(type_id, entity, |hooks| &hooks.on_remove);
+        }
+        let removed = self.storage_mut::<T>()?.remove(entity);
+        if let Some(name) = removed.as_ref().and_then(|removed| (removed as &dyn Any).downcast_ref::<Name>()) {
+            self.unindex_name(&name.0, entity);
+        }
+        removed
+    }
+
+    pub fn despawn(self: &mut Self, entity: Entity) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+        let hooked: Vec<TypeId> = self.hooks.keys()
+            .filter(|type_id| self.has_component(**type_id, entity))
+            .copied()
+            .collect();
+        for type_id in hooked {
+            self.run_hooks(type_id, entity, |hooks| &hooks.on_remove);
+        }
+        if !self.is_alive(entity) {
+            return true;
+        }
+        let name = self.storage::<Name>().and_then(|names| names.get(entity).cloned());
+        if let Some(name) = name {
+            self.unindex_name(&name.0, entity);
+        }
+        let slot = &mut self.slots[entity.index as usize];
+        slot.alive = false;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(entity.index);
+        for cell in self.storages.values() {
+            cell.borrow_mut().remove_entity(entity);
+        }
+        for cell in self.dynamic.values() {
+            cell.borrow_mut().values.remove(entity);
+        }
+        true
+    }
+
+    // Despawns every entity, for switching to another scene. Returns how
+    // many were despawned.
+    pub fn clear(self: &mut Self) -> usize {
+        self.clear_except(&[])
+    }
+
+    // Despawns everything except `keep` and whatever is inside them, e.g. a
+    // toolbar shared between scenes. Queued commands are applied first, so
+    // nothing the old scene queued lands in the new one. The registry,
+    // prefabs and hooks stay, and old handles stay dead since slots are
+    // reused with a new generation.
+    pub fn clear_except(self: &mut Self, keep: &[Entity]) -> usize {
+        self.flush();
+        let mut kept = HashSet::new();
+        let mut pending: Vec<Entity> = keep.iter().copied().filter(|entity| self.is_alive(*entity)).collect();
+        while let Some(entity) = pending.pop() {
+            if kept.insert(entity) {
+                if let Some(children) = self.storage::<Children>().and_then(|storage| storage.get(entity).map(|children| children.0.clone())) {
+                    pending.extend(children);
+                }
+            }
+        }
+        let cleared: Vec<Entity> = self.entities().filter(|entity| !kept.contains(entity)).collect();
+        let count = cleared.into_iter().filter(|entity| self.despawn(*entity)).count();
+        // Kept entities whose group was cleared move to the top level.
+        for entity in kept {
+            let parent = self.storage::<Parent>().and_then(|storage| storage.get(entity).map(|parent| parent.0));
+            if parent.is_some_and(|parent| !self.is_alive(parent)) {
+                self.remove::<Parent>(entity);
+            }
+        }
+        count
+    }
+
+    // Each storage is its own RefCell, so a system can hold storages of
+    // different types at once, shared or mutable, through a plain &World.
+    // Borrowing the same type mutably twice (or mutably while it's read)
+    // panics, naming the component.
+    pub fn storage<T: Component>(&self) -> Option<Ref<Storage<T>>> {
+        let cell = self.storages.get(&TypeId::of::<T>())?;
+        let borrowed = cell.try_borrow()
+            .unwrap_or_else(|_| panic!("{} storage is already borrowed mutably", type_name::<T>()));
+
+        Some(Ref::map(borrowed, |boxed| {
+            boxed.as_any().downcast_ref::<Storage<T>>().unwrap()
+        }))
+    }
+
+    pub fn storage_mut<T: Component>(&self) -> Option<RefMut<Storage<T>>> {
+        let cell = self.storages.get(&TypeId::of::<T>())?;
+        let borrowed = cell.try_borrow_mut()
+            .unwrap_or_else(|_| panic!("{} storage is already borrowed", type_name::<T>()));
+
+        let mut storage = RefMut::map(borrowed, |boxed| {
+            boxed.as_any_mut().downcast_mut::<Storage<T>>().unwrap()
+        });
+        storage.tick = self.tick;
+        Some(storage)
+    }
+
+    // Declares a component by name for code without a Rust type for it.
+    // Registering the same layout again is a no-op; reusing a name with a
+    // different layout, or a typed component's name, is an error.
+    pub fn register_dynamic(self: &mut Self, layout: DynamicLayout) -> io::Result<()> {
+        if !self.is_new_dynamic(&layout)? {
+            return Ok(());
+        }
+        let values = Storage::new(self.tick);
+        self.dynamic.insert(layout.name.clone(), RefCell::new(DynamicStorage { layout, values }));
+        Ok(())
+    }
+
+    // Ok(false) if exactly this layout is already registered.
+    fn is_new_dynamic(&self, layout: &DynamicLayout) -> io::Result<bool> {
+        if self.registry.get_by_name(&layout.name).is_some() {
+            return Err(io::Error::other(format!("{} is already a typed component", layout.name)));
+        }
+        match self.dynamic.get(&layout.name) {
+            Some(cell) if cell.borrow().layout != *layout => {
+                Err(io::Error::other(format!("{} is already registered with another layout", layout.name)))
+            }
+            Some(_) => Ok(false),
+            None => Ok(true),
+        }
+    }
+
+    // The layouts register_dynamic would add, checked all together without
+    // registering any, so a scene can reject its layouts before it changes
+    // anything. Errors as register_dynamic would, or if two of them share a
+    // name.
+    pub fn stage_dynamic(&self, layouts: &[DynamicLayout]) -> io::Result<Vec<DynamicLayout>> {
+        let mut staged: Vec<DynamicLayout> = Vec::new();
+        for layout in layouts {
+            if staged.iter().any(|other| other.name == layout.name && other != layout) {
+                return Err(io::Error::other(format!("{} is declared twice with different layouts", layout.name)));
+            }
+            if self.is_new_dynamic(layout)? && !staged.contains(layout) {
+                staged.push(layout.clone());
+            }
+        }
+        Ok(staged)
+    }
+
+    // Forgets a dynamic component along with its values, e.g. one staged
+    // for a load that then failed.
+    pub fn unregister_dynamic(self: &mut Self, name: &str) -> bool {
+        self.dynamic.remove(name).is_some()
+    }
+
+    pub fn dynamic_names(&self) -> impl Iterator<Item = &str> {
+        self.dynamic.keys().map(|name| name.as_str())
+    }
+
+    // `value` is checked against the layout, with missing fields defaulted.
+    // Ok(false) for a despawned entity, like insert.
+    pub fn insert_dynamic(self: &mut Self, entity: Entity, name: &str, value: Value) -> io::Result<bool> {
+        let tick = self.tick;
+        let cell = self.dynamic.get(name)
+            .ok_or_else(|| io::Error::other(format!("no dynamic component {}", name)))?;
+        if !self.is_alive(entity) {
+            return Ok(false);
+        }
+        let mut storage = cell.borrow_mut();
+        let value = storage.layout.validate(value)?;
+        storage.values.tick = tick;
+        storage.values.insert(entity, value);
+        Ok(true)
+    }
+
+    pub fn remove_dynamic(self: &mut Self, entity: Entity, name: &str) -> Option<Value> {
+        self.dynamic.get(name)?.borrow_mut().values.remove(entity)
+    }
+
+    // None if nothing registered `name`. Borrowed like a typed storage.
+    pub fn dynamic_storage(&self, name: &str) -> Option<Ref<DynamicStorage>> {
+        let cell = self.dynamic.get(name)?;
+        Some(cell.try_borrow()
+            .unwrap_or_else(|_| panic!("dynamic {} storage is already borrowed mutably", name)))
+    }
+
+    pub fn dynamic_storage_mut(&self, name: &str) -> Option<RefMut<DynamicStorage>> {
+        let cell = self.dynamic.get(name)?;
+        let mut storage = cell.try_borrow_mut()
+            .unwrap_or_else(|_| panic!("dynamic {} storage is already borrowed", name));
+        storage.values.tick = self.tick;
+        Some(storage)
+    }
+
+    // Ends a change detection frame. Changed/Added filters match what was
+    // written since the previous call, so this runs once after all systems.
+    // Frame markers are dropped here too, once everything had a chance to
+    // see them.
+    pub fn clear_trackers(self: &mut Self) {
+        for (_, clear) in self.frame_markers.clone() {
+            clear(self);
+        }
+        self.last_tick = self.tick;
+        self.tick += 1;
+    }
+
+    // Removes `T` from every entity, returning the entities that had it.
+    pub fn clear_markers<T: Component>(self: &mut Self) -> Vec<Entity> {
+        let Some(entities) = self.storage::<T>().map(|storage| storage.entities().collect::<Vec<_>>()) else {
+            return Vec::new();
+        };
+        for entity in &entities {
+            self.remove::<T>(*entity);
+        }
+        entities
+    }
+
+    // Makes `T` a per-frame marker: whatever has one at the end of the
+    // frame loses it in clear_trackers.
+    pub fn frame_marker<T: Component>(self: &mut Self) {
+        if !self.frame_markers.iter().any(|(type_id, _)| *type_id == TypeId::of::<T>()) {
+            self.frame_markers.push((TypeId::of::<T>(), clear_frame_marker::<T>));
+        }
+    }
+
+    // Returns every entity that has all of the components in `Q`, which is
+    // either a single component type or a tuple of up to twelve of them.
+    pub fn query<Q: QueryData>(&self) -> Query<'_, Q> {
+        Query { world: self, fetch: Q::fetch(self), filters: Vec::new() }
+    }
+
+    // Like query, for the values of the dynamic component `name`. Empty if
+    // nothing registered it.
+    pub fn query_dynamic(&self, name: &str) -> Query<'_, Dynamic> {
+        Query { world: self, fetch: self.dynamic_storage(name), filters: Vec::new() }
+    }
+
+    // `None` until the first `T` is inserted, like query yielding nothing.
+    pub fn view<T: Component>(&self) -> Option<View<T>> {
+        Some(View { storage: self.storage::<T>()? })
+    }
+
+    pub fn view_mut<T: Component>(&self) -> Option<ViewMut<T>> {
+        Some(ViewMut { storage: self.storage_mut::<T>()? })
+    }
+
+    // Mutable variants hold a RefMut on each storage for the whole pass, so
+    // asking for the same component type twice panics instead of aliasing.
+    pub fn query_mut<T: Component, F>(&self, mut f: F)
+    where
+        F: FnMut(Entity, Mut<T>)
+    {
+        if let Some(mut store) = self.storage_mut::<T>() {
+            for (entity, component) in store.iter_mut() {
+                f(entity, component);
+            }
+        }
+    }
+
+    pub fn query2_mut<A: Component, B: Component, F>(&self, mut f: F)
+    where
+        F: FnMut(Entity, Mut<A>, Mut<B>)
+    {
+        let a_store = self.storage_mut::<A>();
+        let b_store = self.storage_mut::<B>();
+
+        if let (Some(mut a), Some(mut b)) = (a_store, b_store) {
+            for (entity, a_comp) in a.iter_mut() {
+                if let Some(b_comp) = b.get_mut(entity) {
+                    f(entity, a_comp, b_comp);
+                }
+            }
+        }
+    }
+}
+
+impl<'w> EntityBuilder<'w> {
+    pub fn with<T: Component>(self, component: T) -> Self {
+        self.world.insert(self.entity, component);
+        self
+    }
+
+    pub fn with_bundle<B: Bundle>(self, bundle: B) -> Self {
+        bundle.insert_into(self.world, self.entity);
+        self
+    }
+
+    pub fn id(self) -> Entity {
+        self.entity
+    }
+}
+
+impl<'w> Commands<'w> {
+    pub fn spawn(&self) -> Entity {
+        let reserved = self.world.reserved.get();
+        self.world.reserved.set(reserved + 1);
+        Entity { index: self.world.slots.len() as u32 + reserved, generation: 0 }
+    }
+
+    pub fn insert<T: Component>(&self, entity: Entity, component: T) {
+        self.push(move |world| { world.insert(entity, component); });
+    }
+
+    pub fn insert_bundle<B: Bundle>(&self, entity: Entity, bundle: B) {
+        self.push(move |world| { world.insert_bundle(entity, bundle); });
+    }
+
+    pub fn remove<T: Component>(&self, entity: Entity) {
+        self.push(move |world| { world.remove::<T>(entity); });
+    }
+
+    pub fn despawn(&self, entity: Entity) {
+        self.push(move |world| { world.despawn(entity); });
+    }
+
+    fn push(&self, command: impl FnOnce(&mut World) + 'static) {
+        self.world.commands.borrow_mut().push(Box::new(command));
+    }
+}
+
+impl Resources {
+    pub fn new(gpu_state: GpuState, waker: Waker) -> Self {
+        let mut resources = Resources::headless();
+        resources.time.update_refresh_rate(&gpu_state.window);
+        resources.gpu_state = Some(gpu_state);
+        resources.waker = Some(waker);
+        resources
+    }
+
+    // No window or event loop, for running systems in tests.
+    pub fn headless() -> Self {
+        Resources {
+            gpu_state: None,
+            keyboard_state: KeyboardState { modifiers: Modifiers::default() },
+            pointer_state: PointerState::new(),
+            time: Time::new(),
+            frame_scheduler: FrameScheduler::new(),
+            damage: DamageTracker::new(),
+            rng: Rng::new(DEFAULT_SEED),
+            selection: Selection::new(),
+            rubber_band: None,
+            interactions: Events::new(),
+            waker: None,
+            wake_schedule: WakeSchedule::new(),
+            timers: Timers::new(),
+            undo: UndoHistory::new(),
+            quality: QualityScaler::new(),
+            fonts: FontManager::new(),
+            assets: Assets::new(),
+            atlas: None,
+            extra: HashMap::new(),
+        }
+    }
+
+    // Returns the previous value of this type, if any.
+    pub fn insert<T: 'static>(&mut self, resource: T) -> Option<T> {
+        self.extra.insert(TypeId::of::<T>(), Box::new(resource))
+            .map(|previous| *previous.downcast::<T>().unwrap())
+    }
+
+    pub fn get<T: 'static>(&self) -> Option<&T> {
+        self.extra.get(&TypeId::of::<T>())?.downcast_ref::<T>()
+    }
+
+    pub fn get_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.extra.get_mut(&TypeId::of::<T>())?.downcast_mut::<T>()
+    }
+
+    pub fn remove<T: 'static>(&mut self) -> Option<T> {
+        self.extra.remove(&TypeId::of::<T>())
+            .map(|resource| *resource.downcast::<T>().unwrap())
+    }
+}
+
+impl RubberBand {
+    pub fn new(anchor: Point, mode: SelectionMode, selection: &Selection) -> Self {
+        let base = match mode {
+            SelectionMode::Replace => Vec::new(),
+            SelectionMode::Add | SelectionMode::Subtract => selection.entities().to_vec(),
+        };
+        RubberBand { anchor, current: anchor, mode, base }
+    }
+
+    pub fn rect(&self) -> Rect {
+        Rect::new(self.anchor.x, self.anchor.y, self.current.x, self.current.y).sorted()
+    }
+
+    // The selection this band produces given the entities it touches.
+    pub fn apply(&self, hits: &[Entity]) -> Vec<Entity> {
+        match self.mode {
+            SelectionMode::Replace => hits.to_vec(),
+            SelectionMode::Add => {
+                let mut entities = self.base.clone();
+                entities.extend(hits.iter().filter(|entity| !self.base.contains(entity)));
+                entities
+            }
+            SelectionMode::Subtract => {
+                self.base.iter().copied().filter(|entity| !hits.contains(entity)).collect()
+            }
+        }
+    }
+}
+
+impl<T> Events<T> {
+    pub fn new() -> Self {
+        Events { previous: Vec::new(), current: Vec::new(), start: 0 }
+    }
+
+    pub fn send(&mut self, event: T) {
+        self.current.push(event);
+    }
+
+    // A reader that only sees events sent from now on.
+    pub fn reader(&self) -> EventReader<T> {
+        EventReader { next: self.end(), _marker: PhantomData }
+    }
+
+    // Called once per frame; drops the events sent the frame before last.
+    pub fn update(&mut self) {
+        self.start += self.previous.len();
+        std::mem::swap(&mut self.previous, &mut self.current);
+        self.current.clear();
+    }
+
+    fn end(&self) -> usize {
+        self.start + self.previous.len() + self.current.len()
+    }
+}
+
+impl<T> EventReader<T> {
+    // Events this reader hasn't seen yet. Anything older than the previous
+    // frame is gone, so a reader that skips frames misses events.
+    pub fn read<'a>(&mut self, events: &'a Events<T>) -> impl Iterator<Item = &'a T> {
+        let skip = self.next.saturating_sub(events.start);
+        self.next = events.end();
+        events.previous.iter().chain(events.current.iter()).skip(skip)
+    }
+}
+
+impl Selection {
+    pub fn new() -> Self {
+        Selection { entities: Vec::new(), scope: None }
+    }
+
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.entities.contains(&entity)
+    }
+
+    pub fn set(&mut self, entity: Entity) {
+        self.entities.clear();
+        self.entities.push(entity);
+    }
+
+    pub fn toggle(&mut self, entity: Entity) {
+        match self.entities.iter().position(|selected| *selected == entity) {
+            Some(index) => { self.entities.remove(index); }
+            None => self.entities.push(entity),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entities.clear();
+    }
+
+    pub fn replace(&mut self, entities: Vec<Entity>) {
+        self.entities = entities;
+    }
+
+    // Drops handles that were despawned since they were selected.
+    pub fn retain_alive(&mut self, world: &World) {
+        self.entities.retain(|entity| world.is_alive(*entity));
+        if self.scope.is_some_and(|scope| !world.is_alive(scope)) {
+            self.scope = None;
+        }
+    }
+}
+
+impl PointerState {
+    pub fn new() -> Self {
+        PointerState {
+            position: Point::new(0.0, 0.0),
+            prev_position: Point::new(0.0, 0.0),
+            buttons: HashSet::new(),
+            window_to_document: Matrix::new_identity(),
+            samples: Vec::new(),
+            last_click: None,
+        }
+    }
+
+    // Records a click and reports whether it completes a double-click: one
+    // that lands close by while the last click's window is still open. A
+    // third click starts over instead of counting as another double.
+    pub fn register_click(&mut self, position: Point, timers: &mut Timers) -> bool {
+        let double = self.last_click.is_some_and(|(window, last)| {
+            timers.is_pending(window) && (position - last).length() <= DOUBLE_CLICK_DISTANCE
+        });
+        if let Some((window, _)) = self.last_click.take() {
+            timers.cancel(window);
+        }
+        if !double {
+            self.last_click = Some((timers.after(DOUBLE_CLICK_INTERVAL), position));
+        }
+        double
+    }
+
+    pub fn push_sample(&mut self, position: Point) {
+        self.position = position;
+        self.samples.push(position);
+    }
+
+    // Every CursorMoved since the last frame, oldest first. Per-frame systems
+    // only need `position`, but stroke input should walk all of them.
+    pub fn samples(&self) -> &[Point] {
+        &self.samples
+    }
+
+    pub fn moved(&self) -> bool {
+        !self.samples.is_empty()
+    }
+
+    pub fn document_position(&self) -> Point {
+        self.window_to_document.map_point(self.position)
+    }
+
+    pub fn set_button(&mut self, button: PointerButton, pressed: bool) {
+        if pressed {
+            self.buttons.insert(button);
+        } else {
+            self.buttons.remove(&button);
+        }
+    }
+
+    pub fn is_held(&self, button: PointerButton) -> bool {
+        self.buttons.contains(&button)
+    }
+
+    pub fn end_frame(&mut self) {
+        self.prev_position = self.position;
+        self.samples.clear();
+    }
+}
+
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+const DOUBLE_CLICK_DISTANCE: f32 = 4.0;
+
+const DEFAULT_REFRESH_RATE_HZ: f32 = 60.0;
+
+impl Time {
+    pub fn new() -> Self {
+        let now = Instant::now();
+        Time {
+            delta: Duration::ZERO,
+            elapsed: Duration::ZERO,
+            refresh_rate_hz: DEFAULT_REFRESH_RATE_HZ,
+            start: now,
+            last_frame: now,
+        }
+    }
+
+    pub fn tick(&mut self) {
+        let now = Instant::now();
+        self.delta = now - self.last_frame;
+        self.elapsed = now - self.start;
+        self.last_frame = now;
+    }
+
+    // Fixed step for animation integration and the target frame pacing,
+    // matched to whatever monitor the window is currently on.
+    pub fn frame_interval(&self) -> Duration {
+        Duration::from_secs_f32(1.0 / self.refresh_rate_hz)
+    }
+
+    pub fn update_refresh_rate(&mut self, window: &Window) {
+        self.refresh_rate_hz = window.current_monitor()
+            .and_then(|monitor| monitor.refresh_rate_millihertz())
+            .map(|millihertz| millihertz as f32 / 1000.0)
+            .unwrap_or(DEFAULT_REFRESH_RATE_HZ);
+    }
+}
+
+impl FrameScheduler {
+    pub fn new() -> Self {
+        FrameScheduler { frames_presented: 0, redraw_pending: false }
+    }
+
+    pub fn request_redraw(&mut self) {
+        self.redraw_pending = true;
+    }
+
+    pub fn take_redraw(&mut self) -> bool {
+        std::mem::take(&mut self.redraw_pending)
+    }
+}
+
+const DAMAGE_HISTORY: usize = 4;
+
+impl DamageTracker {
+    pub fn new() -> Self {
+        DamageTracker { pending: None, full: true, history: VecDeque::new() }
+    }
+
+    pub fn add(&mut self, rect: Rect) {
+        // Outset by a pixel so anti-aliased edges are repainted too.
+        let rect = rect.with_outset((1.0, 1.0));
+        match &mut self.pending {
+            Some(pending) => pending.join(rect),
+            None => self.pending = Some(rect),
+        }
+    }
+
+    pub fn add_full(&mut self) {
+        self.full = true;
+    }
+
+    pub fn add_entity(&mut self, world: &World, entity: Entity) {
+        let Some(bounds_storage) = world.storage::<Bounds>() else { return; };
+        if let Some(bounds) = bounds_storage.get(entity) {
+            self.add(bounds.rect);
+        }
+    }
+
+    pub fn add_entities(&mut self, world: &World, entities: &[Entity]) {
+        for entity in entities {
+            self.add_entity(world, *entity);
+        }
+    }
+
+    // Closes the current frame and returns the region that has to be repainted
+    // into a back buffer last presented `buffer_age` frames ago, or `None` if
+    // the whole surface must be repainted (age 0 means undefined contents).
+    pub fn begin_frame(&mut self, buffer_age: u32) -> Option<Rect> {
+        let current = if self.full { None } else { Some(self.pending.unwrap_or_default()) };
+        self.pending = None;
+        self.full = false;
+
+        self.history.push_front(current);
+        self.history.truncate(DAMAGE_HISTORY);
+
+        let age = buffer_age as usize;
+        if age == 0 || age > self.history.len() {
+            return None;
+        }
+        let mut region = Rect::default();
+        for damage in self.history.iter().take(age) {
+            region.join((*damage)?);
+        }
+        Some(region)
+    }
+}
+
+// What render_quads draws, back to front: every visible entity with a Quad
+// or a Sprite, by ZIndex, in storage order among equals.
+pub fn draw_order(world: &World) -> Vec<Entity> {
+    let mut entities: Vec<Entity> = world.storage::<Quad>().map_or_else(Vec::new, |storage| storage.entities().collect());
+    if let Some(sprites) = world.storage::<Sprite>() {
+        let quads = world.storage::<Quad>();
+        entities.extend(sprites.entities().filter(|entity| !quads.as_ref().is_some_and(|quads| quads.contains(*entity))));
+    }
+    let z = world.storage::<ZIndex>();
+    entities.sort_by_key(|entity| z.as_ref().and_then(|storage| storage.get(*entity).copied()));
+    entities.retain(|entity| !is_hidden(world, *entity));
+    entities
+}
+
+// The storages entities are drawn from, borrowed once per pass.
+struct DrawData<'w> {
+    quads: Option<Ref<'w, Storage<Quad>>>,
+    outlines: Option<Ref<'w, Storage<Outline>>>,
+    shapes: Option<Ref<'w, Storage<Shape>>>,
+    interactables: Option<Ref<'w, Storage<Interactable>>>,
+    sprites: Option<Ref<'w, Storage<Sprite>>>,
+    sprite_images: Option<Ref<'w, Storage<SpriteImage>>>,
+    icons: Option<Ref<'w, Storage<Icon>>>,
+    atlas_icons: Option<Ref<'w, Storage<AtlasIcon>>>,
+    texts: Option<Ref<'w, Storage<Text>>>,
+    layouts: Option<Ref<'w, Storage<TextLayout>>>,
+    aligns: Option<Ref<'w, Storage<TextAlign>>>,
+}
+
+fn get<'a, T>(storage: &'a Option<Ref<'_, Storage<T>>>, entity: Entity) -> Option<&'a T> {
+    storage.as_ref().and_then(|storage| storage.get(entity))
+}
+
+impl<'w> DrawData<'w> {
+    fn new(world: &'w World) -> Self {
+        DrawData {
+            quads: world.storage(),
+            outlines: world.storage(),
+            shapes: world.storage(),
+            interactables: world.storage(),
+            sprites: world.storage(),
+            sprite_images: world.storage(),
+            icons: world.storage(),
+            atlas_icons: world.storage(),
+            texts: world.storage(),
+            layouts: world.storage(),
+            aligns: world.storage(),
+        }
+    }
+
+    // The quad, then the sprite's image over it, then the icon and text.
+    fn draw(&self, canvas: &Canvas, entity: Entity, quality: Quality) {
+        let quad = get(&self.quads, entity);
+        if let Some(quad) = quad {
+            let fill = quad.fill(get(&self.interactables, entity));
+            draw_quad(canvas, quad.rect, fill, get(&self.outlines, entity), get(&self.shapes, entity), quality);
+        }
+        if let (Some(sprite), Some(image)) = (get(&self.sprites, entity), get(&self.sprite_images, entity)) {
+            canvas.draw_image_rect_with_sampling_options(
+                image.for_canvas(canvas),
+                sprite.src_rect.as_ref().map(|src| (src, SrcRectConstraint::Strict)),
+                sprite.dst_rect,
+                SamplingOptions::default(),
+                &Paint::default(),
+            );
+        }
+        let Some(quad) = quad else { return; };
+        let icon = get(&self.icons, entity);
+        let text = get(&self.texts, entity);
+        let (icon_rect, text_rect) = content_rects(quad.rect, icon.is_some(), text.is_some());
+        if let Some(icon) = icon {
+            draw_icon(canvas, icon, get(&self.atlas_icons, entity), icon_rect);
+        }
+        if let (Some(text), Some(layout)) = (text, get(&self.layouts, entity)) {
+            let align = get(&self.aligns, entity).copied().unwrap_or(TextAlign::Center);
+            draw_text(canvas, text, layout, align, text_rect);
+        }
+    }
+}
+
+// Quads, with their icons and text, and sprites, in one pass in
+// draw_order.
+pub fn render_quads(world: &World, canvas: &Canvas, quality: Quality) {
+    let data = DrawData::new(world);
+    let stack = TransformStack::new(canvas);
+    for entity in draw_order(world) {
+        let scope = stack.push_entity(world, entity);
+        data.draw(scope.canvas(), entity, quality);
+    }
+}
+
+// One entity on its own, as render_quads would draw it; for per-entity
+// captures.
+pub fn render_entity(world: &World, entity: Entity, canvas: &Canvas) {
+    let scope = DrawScope::for_entity(canvas, world, entity);
+    DrawData::new(world).draw(scope.canvas(), entity, Quality::Full);
+}
+
+// Just what the entity itself draws, in its own space: no Transform,
+// Opacity or Adjustment. For baking it into an image.
+pub fn render_entity_content(world: &World, entity: Entity, canvas: &Canvas) {
+    DrawData::new(world).draw(canvas, entity, Quality::Full);
+}
+
+// Where an entity's icon and label go: each centered on the quad, unless
+// it has both, when the icon takes a square on the left and the label the
+// rest (nothing, on a square button).
+fn content_rects(rect: Rect, icon: bool, text: bool) -> (Rect, Rect) {
+    if !(icon && text) {
+        return (rect, rect);
+    }
+    let side = rect.height().min(rect.width());
+    (
+        Rect::new(rect.left, rect.top, rect.left + side, rect.bottom),
+        Rect::new(rect.left + side, rect.top, rect.right, rect.bottom),
+    )
+}
+
+fn draw_quad(canvas: &Canvas, rect: Rect, fill: Color4f, outline: Option<&Outline>, shape: Option<&Shape>, quality: Quality) {
+    let draw = |rect: Rect, paint: &Paint| match shape {
+        Some(shape) => shape.draw(canvas, rect, paint),
+        None => { canvas.draw_rect(rect, paint); }
+    };
+    let mut paint = Paint::new(fill, None);
+    match (outline, quality) {
+        (Some(outline), Quality::Full) => {
+            paint.set_image_filter(outline_filter(outline.color, outline.size, outline.position));
+            draw(rect, &paint);
+        }
+        // A stroke over the same ring the filter would produce.
+        (Some(outline), Quality::Reduced) => {
+            draw(rect, &paint);
+            let offset = match outline.position {
+                OutlinePosition::Outside => outline.size * 0.5,
+                OutlinePosition::Inside => -outline.size * 0.5,
+                OutlinePosition::Center => 0.0,
+            };
+            let mut stroke = Paint::new(Color4f::from(outline.color), None);
+            stroke.set_style(PaintStyle::Stroke);
+            stroke.set_stroke_width(outline.size);
+            draw(rect.with_outset((offset, offset)), &stroke);
+        }
+        (None, _) => draw(rect, &paint),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Never inserted anywhere, so its storage never exists.
+    struct Missing;
+    impl Component for Missing {}
+
+    fn world_with_bounds() -> World {
+        let mut world = World::new();
+        let entity = world.spawn();
+        world.insert(entity, Bounds { rect: Rect::from_wh(10.0, 10.0) });
+        world.insert(entity, ZIndex(1));
+        world
+    }
+
+    #[test]
+    fn query_with_missing_storage_is_empty() {
+        let world = world_with_bounds();
+        assert_eq!(world.query::<Missing>().iter().count(), 0);
+        assert_eq!(world.query::<(Bounds, Missing)>().iter().count(), 0);
+        assert_eq!(world.query::<(Missing, Bounds)>().iter().count(), 0);
+        assert_eq!(world.query::<(Bounds, ZIndex, Missing)>().iter().count(), 0);
+        assert!(world.query::<(Bounds, Missing)>().sorted_by_component::<ZIndex>().is_empty());
+        // The storages that do exist still answer.
+        assert_eq!(world.query::<(Bounds, ZIndex)>().iter().count(), 1);
+    }
+
+    #[test]
+    fn filters_on_missing_storage() {
+        let world = world_with_bounds();
+        assert_eq!(world.query::<Bounds>().with::<Missing>().iter().count(), 0);
+        assert_eq!(world.query::<Bounds>().without::<Missing>().iter().count(), 1);
+        assert_eq!(world.query::<Bounds>().changed::<Missing>().iter().count(), 0);
+    }
+
+    #[test]
+    fn shared_name_falls_back_to_the_other_entity() {
+        let mut world = World::new();
+        let first = world.spawn();
+        world.insert(first, Name("twin".to_string()));
+        let second = world.spawn();
+        world.insert(second, Name("twin".to_string()));
+        assert_eq!(world.find_by_name("twin"), Some(second));
+
+        world.remove::<Name>(second);
+        assert_eq!(world.find_by_name("twin"), Some(first));
+        world.insert(second, Name("twin".to_string()));
+        world.insert(second, Name("other".to_string()));
+        assert_eq!(world.find_by_name("twin"), Some(first));
+        world.despawn(first);
+        assert_eq!(world.find_by_name("twin"), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ecs::Bounds storage is already borrowed mutably")]
+    fn reading_a_storage_borrowed_mutably_names_it() {
+        let world = world_with_bounds();
+        let _bounds = world.storage_mut::<Bounds>();
+        let _ = world.storage::<Bounds>();
+    }
+
+    #[test]
+    #[should_panic(expected = "ecs::Bounds storage is already borrowed")]
+    fn writing_a_storage_borrowed_elsewhere_names_it() {
+        let world = world_with_bounds();
+        let _bounds = world.storage::<Bounds>();
+        let _ = world.storage_mut::<Bounds>();
+    }
+
+    #[test]
+    fn view_of_missing_storage_is_none() {
+        let world = world_with_bounds();
+        assert!(world.view::<Missing>().is_none());
+        assert!(world.view_mut::<Missing>().is_none());
+        assert!(world.view::<Bounds>().is_some());
+        let mut calls = 0;
+        world.query_mut::<Missing, _>(|_, _| calls += 1);
+        world.query2_mut::<Bounds, Missing, _>(|_, _, _| calls += 1);
+        assert_eq!(calls, 0);
     }
 }
@@ -0,0 +1,256 @@
+use std::{fs, io, path::{Path, PathBuf}};
+
+use serde::{Deserialize, Serialize};
+use skia_safe::{Color, Color4f, EncodedImageFormat, Image, Matrix, Rect, Surface, pdf, surfaces};
+
+use crate::filters::{Dither, INDEXED_PALETTES, Palette, export_indexed};
+use crate::packer;
+
+use crate::ecs::{Quad, Slice, Transform, World};
+use crate::hierarchy::is_hidden;
+use crate::systems::render_world;
+
+pub const SLICE_SCALES: [f32; 3] = [1.0, 2.0, 3.0];
+const ATLAS_MAX_WIDTH: i32 = 2048;
+const ATLAS_PADDING: i32 = 2;
+
+// Renders the document region `rect` into an offscreen raster surface at
+// `scale`, on a transparent background so slices keep their alpha.
+pub fn render_region_surface(world: &World, rect: Rect, scale: f32) -> Option<Surface> {
+    let width = (rect.width() * scale).ceil() as i32;
+    let height = (rect.height() * scale).ceil() as i32;
+    let mut surface = surfaces::raster_n32_premul((width.max(1), height.max(1)))?;
+
+    let canvas = surface.canvas();
+    canvas.clear(Color::TRANSPARENT);
+    canvas.scale((scale, scale));
+    canvas.translate((-rect.left(), -rect.top()));
+    render_world(world, canvas);
+
+    Some(surface)
+}
+
+pub fn render_region(world: &World, rect: Rect, scale: f32) -> Option<Image> {
+    render_region_surface(world, rect, scale).map(|mut surface| surface.image_snapshot())
+}
+
+pub fn write_png(image: &Image, path: &Path) -> io::Result<()> {
+    let data = image.encode(None, EncodedImageFormat::PNG, None)
+        .ok_or_else(|| io::Error::other("failed to encode png"))?;
+    fs::write(path, data.as_bytes())
+}
+
+pub fn slice_file_name(name: &str, scale: f32) -> String {
+    if scale == 1.0 {
+        format!("{name}.png")
+    } else {
+        format!("{name}@{scale}x.png")
+    }
+}
+
+pub fn export_slice(world: &World, slice: &Slice, scales: &[f32], dir: &Path) -> io::Result<Vec<PathBuf>> {
+    fs::create_dir_all(dir)?;
+    let mut written = Vec::new();
+    for &scale in scales {
+        let image = render_region(world, slice.rect, scale)
+            .ok_or_else(|| io::Error::other(format!("failed to render slice {}", slice.name)))?;
+        let path = dir.join(slice_file_name(&slice.name, scale));
+        write_png(&image, &path)?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+// Writes one PDF page per document rect, sized by the document's DPI so it
+// prints at its physical size. Drawing goes through skia's PDF backend, so
+// quads and other vector content stay as paths.
+pub fn export_pdf(world: &World, pages: &[Rect], path: &Path) -> io::Result<()> {
+    let scale = world.document().points_per_px();
+    let mut bytes = Vec::new();
+    let mut document = pdf::new_document(&mut bytes, None);
+    for rect in pages {
+        let mut page = document.begin_page((rect.width() * scale, rect.height() * scale), None);
+        let canvas = page.canvas();
+        canvas.scale((scale, scale));
+        canvas.translate((-rect.left(), -rect.top()));
+        render_world(world, canvas);
+        document = page.end_page();
+    }
+    document.close();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, bytes)
+}
+
+pub fn export_slices_pdf(world: &World, path: &Path) -> io::Result<()> {
+    let pages: Vec<Rect> = collect_slices(world).iter().map(|slice| slice.rect).collect();
+    if pages.is_empty() {
+        return Err(io::Error::other("no slices to export"));
+    }
+    export_pdf(world, &pages, path)
+}
+
+// Serializes the vector content inside `rect` as standalone SVG. Each quad
+// becomes a <rect>, carrying its Transform as an SVG matrix.
+pub fn world_to_svg(world: &World, rect: Rect) -> String {
+    let mut body = String::new();
+    let transforms = world.storage::<Transform>();
+    for (entity, quad) in &world.query::<Quad>() {
+        if is_hidden(world, entity) {
+            continue;
+        }
+        let transform = transforms.as_ref()
+            .and_then(|storage| storage.get(entity))
+            .map(|transform| format!(" transform=\"{}\"", svg_matrix(&transform.local_to_parent)))
+            .unwrap_or_default();
+        body.push_str(&format!(
+            "  <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" fill-opacity=\"{}\"{} />\n",
+            quad.rect.left(), quad.rect.top(), quad.rect.width(), quad.rect.height(),
+            svg_color(&quad.color), quad.color.a, transform,
+        ));
+    }
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"{} {} {} {}\">\n{}</svg>\n",
+        rect.width(), rect.height(), rect.left(), rect.top(), rect.width(), rect.height(), body,
+    )
+}
+
+pub fn export_slices_svg(world: &World, dir: &Path) -> io::Result<Vec<PathBuf>> {
+    fs::create_dir_all(dir)?;
+    let mut written = Vec::new();
+    for slice in collect_slices(world) {
+        let path = dir.join(format!("{}.svg", slice.name));
+        fs::write(&path, world_to_svg(world, slice.rect))?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+fn svg_color(color: &Color4f) -> String {
+    let channel = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+    format!("#{:02x}{:02x}{:02x}", channel(color.r), channel(color.g), channel(color.b))
+}
+
+fn svg_matrix(matrix: &Matrix) -> String {
+    format!(
+        "matrix({} {} {} {} {} {})",
+        matrix.scale_x(), matrix.skew_y(), matrix.skew_x(), matrix.scale_y(), matrix.translate_x(), matrix.translate_y(),
+    )
+}
+
+fn collect_slices(world: &World) -> Vec<Slice> {
+    let mut slices: Vec<Slice> = world.query::<Slice>().iter()
+        .map(|(_, slice)| Slice { name: slice.name.clone(), rect: slice.rect, pivot: slice.pivot })
+        .collect();
+    slices.sort_by(|a, b| a.name.cmp(&b.name));
+    slices
+}
+
+pub fn export_slices(world: &World, scales: &[f32], dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let slices = collect_slices(world);
+
+    let mut written = Vec::new();
+    for slice in &slices {
+        written.extend(export_slice(world, slice, scales, dir)?);
+    }
+    Ok(written)
+}
+
+// Which fixed palette (see INDEXED_PALETTES) and dithering indexed export
+// reduces slices to.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct IndexedExport {
+    pub palette: String,
+    pub dither: Dither,
+}
+
+impl Default for IndexedExport {
+    fn default() -> Self {
+        IndexedExport { palette: INDEXED_PALETTES[0].0.to_string(), dither: Dither::default() }
+    }
+}
+
+impl IndexedExport {
+    // An unknown name (say, from an older settings file) is the first palette.
+    pub fn palette(&self) -> Palette {
+        Palette::preset(&self.palette).unwrap_or_else(|| Palette::new(INDEXED_PALETTES[0].1.to_vec()))
+    }
+
+    pub fn next_palette(&mut self) {
+        let index = INDEXED_PALETTES.iter().position(|(name, _)| *name == self.palette).map_or(0, |index| index + 1);
+        self.palette = INDEXED_PALETTES[index % INDEXED_PALETTES.len()].0.to_string();
+    }
+}
+
+// Writes every slice at 1x, reduced to the chosen palette, as `<name>.png`
+// under `dir`.
+pub fn export_slices_indexed(world: &World, indexed: &IndexedExport, dir: &Path) -> io::Result<Vec<PathBuf>> {
+    fs::create_dir_all(dir)?;
+    let palette = indexed.palette();
+    let mut written = Vec::new();
+    for slice in collect_slices(world) {
+        let mut surface = render_region_surface(world, slice.rect, 1.0)
+            .ok_or_else(|| io::Error::other(format!("failed to render slice {}", slice.name)))?;
+        let path = dir.join(slice_file_name(&slice.name, 1.0));
+        export_indexed(&mut surface, &palette, indexed.dither, &path)?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+// Packs every slice into one atlas image and writes `<stem>.png` plus a
+// `<stem>.json` describing where each slice landed, for game engines.
+pub fn export_sprite_sheet(world: &World, scale: f32, dir: &Path, stem: &str) -> io::Result<()> {
+    let slices = collect_slices(world);
+    let mut images = Vec::with_capacity(slices.len());
+    for slice in &slices {
+        let image = render_region(world, slice.rect, scale)
+            .ok_or_else(|| io::Error::other(format!("failed to render slice {}", slice.name)))?;
+        images.push(image);
+    }
+
+    let sizes: Vec<(i32, i32)> = images.iter().map(|image| (image.width(), image.height())).collect();
+    let packing = packer::pack(&sizes, ATLAS_MAX_WIDTH, ATLAS_PADDING);
+
+    let mut surface = surfaces::raster_n32_premul((packing.width.max(1), packing.height.max(1)))
+        .ok_or_else(|| io::Error::other("failed to allocate atlas surface"))?;
+    let canvas = surface.canvas();
+    canvas.clear(Color::TRANSPARENT);
+    for rect in &packing.rects {
+        canvas.draw_image(&images[rect.index], (rect.x as f32, rect.y as f32), None);
+    }
+
+    fs::create_dir_all(dir)?;
+    let image_name = format!("{stem}.png");
+    write_png(&surface.image_snapshot(), &dir.join(&image_name))?;
+
+    let frames: Vec<String> = packing.rects.iter().map(|rect| {
+        let slice = &slices[rect.index];
+        format!(
+            "    {{ \"name\": {}, \"x\": {}, \"y\": {}, \"w\": {}, \"h\": {}, \"pivot\": {{ \"x\": {}, \"y\": {} }} }}",
+            json_string(&slice.name), rect.x, rect.y, rect.width, rect.height, slice.pivot.x, slice.pivot.y,
+        )
+    }).collect();
+    let json = format!(
+        "{{\n  \"image\": {},\n  \"size\": {{ \"w\": {}, \"h\": {} }},\n  \"scale\": {},\n  \"frames\": [\n{}\n  ]\n}}\n",
+        json_string(&image_name), packing.width, packing.height, scale, frames.join(",\n"),
+    );
+    fs::write(dir.join(format!("{stem}.json")), json)
+}
+
+fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -0,0 +1,77 @@
+use skia_safe::{Color4f, Point, Rect};
+
+use crate::adjustments::{FilterCommand, filter_commands};
+use crate::ecs::{BlocksInput, Component, Entity, InteractionPriority, World};
+use crate::systems::entity_at;
+use crate::templates::{DIALOG_PRIORITY, DIALOG_Z, spawn_button, spawn_rect};
+use crate::text::spawn_label;
+
+const WIDTH: f32 = 200.0;
+const ROW: f32 = 28.0;
+const GAP: f32 = 6.0;
+const LABEL_SIZE: f32 = 12.0;
+
+// Which command a menu row runs.
+pub struct FilterChoice(pub usize);
+
+impl Component for FilterChoice {}
+
+pub enum MenuAction {
+    Pending,
+    Run(FilterCommand),
+    Cancel,
+}
+
+// Ctrl+F: one row per filter command, for the selection, built from
+// entities in the UI world like the new-document dialog. Clicking outside
+// the menu cancels.
+pub struct FilterMenu {
+    commands: Vec<(&'static str, FilterCommand)>,
+    backdrop: Entity,
+    entities: Vec<Entity>,
+}
+
+impl FilterMenu {
+    pub fn open(ui: &mut World, viewport: Rect) -> Self {
+        let commands = filter_commands();
+        let height = commands.len() as f32 * (ROW + GAP) + GAP;
+        let panel = Rect::from_xywh(viewport.center_x() - WIDTH * 0.5, viewport.center_y() - height * 0.5, WIDTH, height);
+
+        let mut entities = Vec::new();
+        let backdrop = spawn_rect(ui, viewport, Color4f::new(0.0, 0.0, 0.0, 0.2), DIALOG_Z);
+        let panel_entity = spawn_rect(ui, panel, Color4f::new(0.95, 0.95, 0.95, 1.0), DIALOG_Z + 1);
+        for entity in [backdrop, panel_entity] {
+            ui.insert(entity, BlocksInput);
+            ui.insert(entity, InteractionPriority(DIALOG_PRIORITY));
+            entities.push(entity);
+        }
+
+        for (index, (name, _)) in commands.iter().enumerate() {
+            let row = Rect::from_xywh(panel.left + GAP, panel.top + GAP + index as f32 * (ROW + GAP), WIDTH - GAP * 2.0, ROW);
+            let button = spawn_button(ui, row, DIALOG_Z + 2);
+            ui.insert(button, FilterChoice(index));
+            entities.push(button);
+            entities.push(spawn_label(ui, row, name, LABEL_SIZE, DIALOG_Z + 3));
+        }
+
+        FilterMenu { commands, backdrop, entities }
+    }
+
+    pub fn click(&self, ui: &World, point: Point) -> MenuAction {
+        let Some(hit) = entity_at(ui, point) else { return MenuAction::Pending; };
+        if hit == self.backdrop {
+            return MenuAction::Cancel;
+        }
+        let choice = ui.storage::<FilterChoice>().and_then(|storage| storage.get(hit).map(|choice| choice.0));
+        match choice {
+            Some(index) => MenuAction::Run(self.commands[index].1.clone()),
+            None => MenuAction::Pending,
+        }
+    }
+
+    pub fn close(self, ui: &mut World) {
+        for entity in self.entities {
+            ui.despawn(entity);
+        }
+    }
+}
@@ -0,0 +1,594 @@
+use std::{fs, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+use skia_safe::{AlphaType, BlendMode, Color, ColorFilter, ColorMatrix, ColorType, Data, EncodedImageFormat, Image, ImageFilter, ImageInfo, Paint, Rect, RuntimeEffect, SamplingOptions, Surface, color_filters, image_filters, images, runtime_effect::ChildPtr, surfaces};
+
+use crate::rng::Rng;
+use crate::scene;
+
+const BAYER_4X4: [[u8; 4]; 4] = [
+    [0, 8, 2, 10],
+    [12, 4, 14, 6],
+    [3, 11, 1, 9],
+    [15, 7, 13, 5],
+];
+
+const PALETTE_ORDERED_SPREAD: f32 = 32.0;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub enum Dither {
+    None,
+    #[default]
+    Ordered,
+    ErrorDiffusion,
+}
+
+impl Dither {
+    pub fn next(self) -> Self {
+        match self {
+            Dither::None => Dither::Ordered,
+            Dither::Ordered => Dither::ErrorDiffusion,
+            Dither::ErrorDiffusion => Dither::None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Dither::None => "no dither",
+            Dither::Ordered => "ordered dither",
+            Dither::ErrorDiffusion => "error diffusion",
+        }
+    }
+}
+
+// Fixed palettes for indexed export, by name.
+pub const INDEXED_PALETTES: [(&str, &[Color]); 4] = [
+    ("PICO-8", &[
+        Color::from_rgb(0, 0, 0), Color::from_rgb(29, 43, 83), Color::from_rgb(126, 37, 83), Color::from_rgb(0, 135, 81),
+        Color::from_rgb(171, 82, 54), Color::from_rgb(95, 87, 79), Color::from_rgb(194, 195, 199), Color::from_rgb(255, 241, 232),
+        Color::from_rgb(255, 0, 77), Color::from_rgb(255, 163, 0), Color::from_rgb(255, 236, 39), Color::from_rgb(0, 228, 54),
+        Color::from_rgb(41, 173, 255), Color::from_rgb(131, 118, 156), Color::from_rgb(255, 119, 168), Color::from_rgb(255, 204, 170),
+    ]),
+    ("Game Boy", &[
+        Color::from_rgb(15, 56, 15), Color::from_rgb(48, 98, 48), Color::from_rgb(139, 172, 15), Color::from_rgb(155, 188, 15),
+    ]),
+    ("CGA", &[
+        Color::from_rgb(0, 0, 0), Color::from_rgb(85, 255, 255), Color::from_rgb(255, 85, 255), Color::from_rgb(255, 255, 255),
+    ]),
+    ("1-bit", &[Color::from_rgb(0, 0, 0), Color::from_rgb(255, 255, 255)]),
+];
+
+pub struct Palette {
+    pub colors: Vec<Color>,
+}
+
+impl Palette {
+    pub fn new(colors: Vec<Color>) -> Self {
+        assert!(!colors.is_empty(), "palette needs at least one color");
+        Palette { colors }
+    }
+
+    pub fn preset(name: &str) -> Option<Self> {
+        INDEXED_PALETTES.iter()
+            .find(|(preset, _)| *preset == name)
+            .map(|(_, colors)| Palette::new(colors.to_vec()))
+    }
+
+    pub fn nearest(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let mut best = [0.0; 3];
+        let mut best_distance = f32::MAX;
+        for color in &self.colors {
+            let candidate = [color.r() as f32, color.g() as f32, color.b() as f32];
+            let distance = (0..3).map(|i| (candidate[i] - rgb[i]).powi(2)).sum::<f32>();
+            if distance < best_distance {
+                best_distance = distance;
+                best = candidate;
+            }
+        }
+        best
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Noise {
+    White,
+    // Gradient noise with lattice cells `scale` pixels wide.
+    Perlin { scale: f32 },
+}
+
+// Dots sized by darkness on a grid rotated by `angle` (radians), sampling the
+// source once per cell centre.
+const HALFTONE_SKSL: &str = r#"
+uniform shader image;
+uniform float cell;
+uniform float angle;
+
+half4 main(float2 coord) {
+    float c = cos(angle);
+    float s = sin(angle);
+    float2 rotated = float2(c * coord.x - s * coord.y, s * coord.x + c * coord.y);
+    float2 center = (floor(rotated / cell) + 0.5) * cell;
+    float2 source = float2(c * center.x + s * center.y, -s * center.x + c * center.y);
+
+    half4 color = image.eval(source);
+    half luma = dot(color.rgb, half3(0.2126, 0.7152, 0.0722));
+    float radius = cell * 0.7071 * sqrt(1.0 - luma);
+    half ink = half(1.0 - smoothstep(radius - 0.75, radius + 0.75, distance(rotated, center)));
+    return half4(half3(1.0 - ink), 1.0) * color.a;
+}
+"#;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum OutlinePosition {
+    Outside,
+    Inside,
+    Center,
+}
+
+const LUMA: [f32; 3] = [0.2126, 0.7152, 0.0722];
+
+pub const DUOTONE_PRESETS: [(&str, Color, Color); 4] = [
+    ("Sepia", Color::from_rgb(44, 24, 16), Color::from_rgb(245, 226, 196)),
+    ("Cyanotype", Color::from_rgb(8, 30, 72), Color::from_rgb(214, 236, 246)),
+    ("Riso Pink", Color::from_rgb(60, 20, 90), Color::from_rgb(255, 120, 170)),
+    ("Newsprint", Color::from_rgb(24, 24, 24), Color::from_rgb(236, 232, 220)),
+];
+
+// Remaps luminance through a gradient; stops are (position in 0..1, color)
+// and must be sorted by position.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GradientMap {
+    #[serde(with = "scene::gradient_stops")]
+    pub stops: Vec<(f32, Color)>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GradientPreset {
+    pub name: String,
+    pub map: GradientMap,
+}
+
+// The duotone presets first, then the user's own.
+pub fn gradient_presets(user: &[GradientPreset]) -> Vec<GradientPreset> {
+    DUOTONE_PRESETS.iter()
+        .map(|(name, shadows, highlights)| GradientPreset { name: name.to_string(), map: GradientMap::duotone(*shadows, *highlights) })
+        .chain(user.iter().cloned())
+        .collect()
+}
+
+fn luma(color: Color) -> f32 {
+    LUMA[0] * color.r() as f32 + LUMA[1] * color.g() as f32 + LUMA[2] * color.b() as f32
+}
+
+impl GradientMap {
+    pub fn new(stops: Vec<(f32, Color)>) -> Self {
+        assert!(!stops.is_empty(), "gradient map needs at least one stop");
+        GradientMap { stops }
+    }
+
+    pub fn duotone(shadows: Color, highlights: Color) -> Self {
+        GradientMap::new(vec![(0.0, shadows), (1.0, highlights)])
+    }
+
+    // `colors` spread evenly from darkest to lightest, so the darkest one
+    // replaces the shadows.
+    pub fn from_colors(colors: &[Color]) -> Option<Self> {
+        let mut colors = colors.to_vec();
+        colors.sort_by(|a, b| luma(*a).total_cmp(&luma(*b)));
+        colors.dedup();
+        match colors.len() {
+            0 => None,
+            1 => Some(GradientMap::new(vec![(0.0, colors[0])])),
+            count => Some(GradientMap::new(colors.iter().enumerate()
+                .map(|(index, color)| (index as f32 / (count - 1) as f32, *color))
+                .collect())),
+        }
+    }
+
+    pub fn preset(name: &str) -> Option<Self> {
+        DUOTONE_PRESETS.iter()
+            .find(|(preset, _, _)| *preset == name)
+            .map(|(_, shadows, highlights)| GradientMap::duotone(*shadows, *highlights))
+    }
+
+    pub fn sample(&self, t: f32) -> [f32; 3] {
+        let rgb = |c: &Color| [c.r() as f32, c.g() as f32, c.b() as f32];
+        let first = &self.stops[0];
+        if t <= first.0 {
+            return rgb(&first.1);
+        }
+        for pair in self.stops.windows(2) {
+            let (p0, c0) = &pair[0];
+            let (p1, c1) = &pair[1];
+            if t <= *p1 {
+                let f = if p1 > p0 { (t - p0) / (p1 - p0) } else { 1.0 };
+                let (a, b) = (rgb(c0), rgb(c1));
+                return [0, 1, 2].map(|i| a[i] + (b[i] - a[i]) * f);
+            }
+        }
+        rgb(&self.stops[self.stops.len() - 1].1)
+    }
+
+    // Per-channel lookup tables indexed by 8-bit luminance.
+    pub fn tables(&self) -> [[u8; 256]; 3] {
+        let mut tables = [[0u8; 256]; 3];
+        for i in 0..256 {
+            let rgb = self.sample(i as f32 / 255.0);
+            for channel in 0..3 {
+                tables[channel][i] = rgb[channel].round().clamp(0.0, 255.0) as u8;
+            }
+        }
+        tables
+    }
+
+    // The same mapping as a skia color filter (luma matrix, then table), so
+    // it can be attached to a Paint when compositing instead of baked in.
+    pub fn color_filter(&self) -> Option<ColorFilter> {
+        let [r, g, b] = LUMA;
+        let luma = ColorMatrix::new(
+            r, g, b, 0.0, 0.0,
+            r, g, b, 0.0, 0.0,
+            r, g, b, 0.0, 0.0,
+            0.0, 0.0, 0.0, 1.0, 0.0,
+        );
+        let tables = self.tables();
+        let table = color_filters::table_argb(None, &tables[0], &tables[1], &tables[2])?;
+        color_filters::compose(table, color_filters::matrix(&luma, None))
+    }
+}
+
+// Tightly packed RGBA8888, unpremultiplied, so the per-channel math below
+// never has to care about alpha.
+pub struct Pixels {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<u8>,
+}
+
+impl Pixels {
+    pub fn info(&self) -> ImageInfo {
+        ImageInfo::new((self.width as i32, self.height as i32), ColorType::RGBA8888, AlphaType::Unpremul, None)
+    }
+
+    pub fn read(surface: &mut Surface) -> Option<Self> {
+        let width = surface.width() as usize;
+        let height = surface.height() as usize;
+        let mut pixels = Pixels { width, height, data: vec![0; width * height * 4] };
+        let info = pixels.info();
+        if !surface.read_pixels(&info, &mut pixels.data, width * 4, (0, 0)) {
+            return None;
+        }
+        Some(pixels)
+    }
+
+    pub fn posterize(&mut self, levels: u8, dither: Dither) {
+        let steps = (levels.max(2) - 1) as f32;
+        let spread = 255.0 / steps;
+        self.quantize_with(dither, spread, |rgb| {
+            rgb.map(|c| ((c / 255.0 * steps).round() / steps * 255.0).clamp(0.0, 255.0))
+        });
+    }
+
+    pub fn quantize(&mut self, palette: &Palette, dither: Dither) {
+        self.quantize_with(dither, PALETTE_ORDERED_SPREAD, |rgb| palette.nearest(rgb));
+    }
+
+    fn quantize_with<F>(&mut self, dither: Dither, spread: f32, nearest: F)
+    where
+        F: Fn([f32; 3]) -> [f32; 3]
+    {
+        let mut error = vec![[0.0f32; 3]; self.width * self.height];
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let index = y * self.width + x;
+                let pixel = &mut self.data[index * 4..index * 4 + 3];
+                let mut rgb = [pixel[0] as f32, pixel[1] as f32, pixel[2] as f32];
+
+                match dither {
+                    Dither::None => {}
+                    Dither::Ordered => {
+                        let threshold = (BAYER_4X4[y % 4][x % 4] as f32 + 0.5) / 16.0 - 0.5;
+                        rgb = rgb.map(|c| c + threshold * spread);
+                    }
+                    Dither::ErrorDiffusion => {
+                        for i in 0..3 { rgb[i] += error[index][i]; }
+                    }
+                }
+
+                let quantized = nearest(rgb.map(|c| c.clamp(0.0, 255.0)));
+                for i in 0..3 { pixel[i] = quantized[i] as u8; }
+
+                if dither == Dither::ErrorDiffusion {
+                    let residual = [rgb[0] - quantized[0], rgb[1] - quantized[1], rgb[2] - quantized[2]];
+                    self.diffuse(&mut error, x, y, residual);
+                }
+            }
+        }
+    }
+
+    // Floyd-Steinberg weights: 7/16 right, 3/16 below-left, 5/16 below, 1/16 below-right.
+    fn diffuse(&self, error: &mut [[f32; 3]], x: usize, y: usize, residual: [f32; 3]) {
+        let neighbours: [(isize, usize, f32); 4] = [
+            (1, 0, 7.0 / 16.0),
+            (-1, 1, 3.0 / 16.0),
+            (0, 1, 5.0 / 16.0),
+            (1, 1, 1.0 / 16.0),
+        ];
+        for (dx, dy, weight) in neighbours {
+            let nx = x as isize + dx;
+            let ny = y + dy;
+            if nx < 0 || nx as usize >= self.width || ny >= self.height {
+                continue;
+            }
+            let target = &mut error[ny * self.width + nx as usize];
+            for i in 0..3 { target[i] += residual[i] * weight; }
+        }
+    }
+
+    pub fn apply_gradient_map(&mut self, map: &GradientMap) {
+        let tables = map.tables();
+        for pixel in self.data.chunks_exact_mut(4) {
+            let luma = LUMA[0] * pixel[0] as f32 + LUMA[1] * pixel[1] as f32 + LUMA[2] * pixel[2] as f32;
+            let index = luma.round().clamp(0.0, 255.0) as usize;
+            for channel in 0..3 {
+                pixel[channel] = tables[channel][index];
+            }
+        }
+    }
+
+    pub fn add_noise(&mut self, rng: &mut Rng, amount: f32, noise: Noise) {
+        let perlin = match noise {
+            Noise::Perlin { .. } => Some(PerlinNoise::new(rng)),
+            Noise::White => None,
+        };
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let value = match (noise, &perlin) {
+                    (Noise::Perlin { scale }, Some(perlin)) => perlin.sample(x as f32 / scale, y as f32 / scale),
+                    _ => rng.range(-1.0, 1.0),
+                };
+                let offset = value * amount * 255.0;
+                let index = (y * self.width + x) * 4;
+                for pixel in &mut self.data[index..index + 3] {
+                    *pixel = (*pixel as f32 + offset).round().clamp(0.0, 255.0) as u8;
+                }
+            }
+        }
+    }
+
+    pub fn encode_png(&self) -> Option<Data> {
+        let image = images::raster_from_data(&self.info(), Data::new_copy(&self.data), self.width * 4)?;
+        image.encode(None, EncodedImageFormat::PNG, None)
+    }
+}
+
+pub fn export_indexed(surface: &mut Surface, palette: &Palette, dither: Dither, path: &Path) -> io::Result<()> {
+    let mut pixels = Pixels::read(surface)
+        .ok_or_else(|| io::Error::other("failed to read surface pixels"))?;
+    pixels.quantize(palette, dither);
+    let png = pixels.encode_png()
+        .ok_or_else(|| io::Error::other("failed to encode png"))?;
+    fs::write(path, png.as_bytes())
+}
+
+// Non-destructive counterpart of Pixels::posterize (without dithering).
+pub fn posterize_color_filter(levels: u8) -> Option<ColorFilter> {
+    let steps = (levels.max(2) - 1) as f32;
+    let mut table = [0u8; 256];
+    for (i, entry) in table.iter_mut().enumerate() {
+        *entry = ((i as f32 / 255.0 * steps).round() / steps * 255.0) as u8;
+    }
+    color_filters::table_argb(None, &table, &table, &table)
+}
+
+pub fn halftone_effect() -> Result<RuntimeEffect, String> {
+    RuntimeEffect::make_for_shader(HALFTONE_SKSL, None)
+}
+
+pub fn halftone(image: &Image, cell: f32, angle: f32) -> Option<Image> {
+    let effect = halftone_effect()
+        .map_err(|e| eprintln!("Failed to compile halftone shader: {}", e))
+        .ok()?;
+    let image_shader = image.to_shader(None, SamplingOptions::default(), None)?;
+
+    let mut uniforms = Vec::with_capacity(8);
+    uniforms.extend_from_slice(&cell.max(1.0).to_le_bytes());
+    uniforms.extend_from_slice(&angle.to_le_bytes());
+    let shader = effect.make_shader(Data::new_copy(&uniforms), &[ChildPtr::Shader(image_shader)], None)?;
+
+    let mut surface = surfaces::raster_n32_premul((image.width(), image.height()))?;
+    let mut paint = Paint::default();
+    paint.set_shader(shader);
+    surface.canvas().draw_rect(Rect::from_wh(image.width() as f32, image.height() as f32), &paint);
+    Some(surface.image_snapshot())
+}
+
+// Classic 2D gradient noise over a shuffled permutation table, so the
+// pattern is fixed by the Rng seed. Output is roughly in [-1, 1].
+struct PerlinNoise {
+    permutation: [u8; 512],
+}
+
+impl PerlinNoise {
+    fn new(rng: &mut Rng) -> Self {
+        let mut table: [u8; 256] = std::array::from_fn(|i| i as u8);
+        for i in (1..256).rev() {
+            let j = (rng.next_u64() % (i as u64 + 1)) as usize;
+            table.swap(i, j);
+        }
+        PerlinNoise { permutation: std::array::from_fn(|i| table[i % 256]) }
+    }
+
+    fn sample(&self, x: f32, y: f32) -> f32 {
+        let (xf, yf) = (x.floor(), y.floor());
+        let (xi, yi) = ((xf as i32 & 255) as usize, (yf as i32 & 255) as usize);
+        let (dx, dy) = (x - xf, y - yf);
+        let fade = |t: f32| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+        let (u, v) = (fade(dx), fade(dy));
+
+        let p = &self.permutation;
+        let hash = |cx: usize, cy: usize| p[p[xi + cx] as usize + yi + cy];
+        let gradient = |h: u8, x: f32, y: f32| match h & 3 {
+            0 => x + y,
+            1 => -x + y,
+            2 => x - y,
+            _ => -x - y,
+        };
+
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        let top = lerp(gradient(hash(0, 0), dx, dy), gradient(hash(1, 0), dx - 1.0, dy), u);
+        let bottom = lerp(gradient(hash(0, 1), dx, dy - 1.0), gradient(hash(1, 1), dx - 1.0, dy - 1.0), u);
+        lerp(top, bottom, v)
+    }
+}
+
+// Outline around whatever is drawn with this filter: a dilated (outside),
+// eroded (inside) or half-and-half (center) alpha ring filled with `color`.
+// Being an image filter, it is recomputed every time the content is drawn.
+pub fn outline_filter(color: Color, size: f32, position: OutlinePosition) -> Option<ImageFilter> {
+    let colorize = |input: Option<ImageFilter>| {
+        image_filters::color_filter(color_filters::blend(color, BlendMode::SrcIn)?, input, None)
+    };
+    match position {
+        OutlinePosition::Outside => {
+            let ring = colorize(image_filters::dilate(size, size, None, None))?;
+            image_filters::merge([Some(ring), None], None)
+        }
+        OutlinePosition::Inside => {
+            let eroded = image_filters::erode(size, size, None, None);
+            let ring = image_filters::blend(BlendMode::DstOut, colorize(None), eroded, None)?;
+            image_filters::merge([None, Some(ring)], None)
+        }
+        OutlinePosition::Center => {
+            let half = size * 0.5;
+            let eroded = image_filters::erode(half, half, None, None);
+            let dilated = colorize(image_filters::dilate(half, half, None, None));
+            let ring = image_filters::blend(BlendMode::DstOut, dilated, eroded, None)?;
+            image_filters::merge([None, Some(ring)], None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A left-to-right ramp through every channel, opaque.
+    fn ramp(width: usize, height: usize) -> Pixels {
+        let mut data = Vec::with_capacity(width * height * 4);
+        for y in 0..height {
+            for x in 0..width {
+                let t = (x + y * width) as f32 / (width * height - 1) as f32;
+                data.extend_from_slice(&[(t * 255.0) as u8, (t * 180.0) as u8 + 40, ((1.0 - t) * 255.0) as u8, 255]);
+            }
+        }
+        Pixels { width, height, data }
+    }
+
+    fn gray(width: usize, value: u8) -> Pixels {
+        Pixels { width, height: 1, data: [value, value, value, 255].repeat(width) }
+    }
+
+    fn reds(pixels: &Pixels) -> Vec<u8> {
+        pixels.data.chunks_exact(4).map(|pixel| pixel[0]).collect()
+    }
+
+    #[test]
+    fn quantize_only_uses_palette_colors() {
+        let palette = Palette::preset("Game Boy").unwrap();
+        let allowed: Vec<[u8; 3]> = palette.colors.iter().map(|color| [color.r(), color.g(), color.b()]).collect();
+        for dither in [Dither::None, Dither::Ordered, Dither::ErrorDiffusion] {
+            let mut pixels = ramp(8, 8);
+            pixels.quantize(&palette, dither);
+            for pixel in pixels.data.chunks_exact(4) {
+                assert!(allowed.contains(&[pixel[0], pixel[1], pixel[2]]), "{:?} gave {:?}", dither, pixel);
+                assert_eq!(pixel[3], 255);
+            }
+        }
+    }
+
+    #[test]
+    fn posterize_only_uses_its_levels() {
+        let allowed = [0, 127, 255];
+        for dither in [Dither::None, Dither::Ordered, Dither::ErrorDiffusion] {
+            let mut pixels = ramp(8, 8);
+            pixels.posterize(3, dither);
+            for pixel in pixels.data.chunks_exact(4) {
+                assert!(pixel[..3].iter().all(|channel| allowed.contains(channel)), "{:?} gave {:?}", dither, pixel);
+            }
+        }
+    }
+
+    #[test]
+    fn dithering_is_deterministic() {
+        // Mid gray between two levels: the Bayer row 0, 8, 2, 10 and the
+        // Floyd-Steinberg carry each alternate it, in opposite phase.
+        let mut ordered = gray(4, 128);
+        ordered.posterize(2, Dither::Ordered);
+        assert_eq!(reds(&ordered), [0, 255, 0, 255]);
+        let mut diffused = gray(4, 128);
+        diffused.posterize(2, Dither::ErrorDiffusion);
+        assert_eq!(reds(&diffused), [255, 0, 255, 0]);
+
+        let palette = Palette::preset("PICO-8").unwrap();
+        for dither in [Dither::Ordered, Dither::ErrorDiffusion] {
+            let mut first = ramp(6, 5);
+            let mut second = ramp(6, 5);
+            first.quantize(&palette, dither);
+            second.quantize(&palette, dither);
+            assert_eq!(first.data, second.data);
+        }
+    }
+
+    #[test]
+    fn halftone_shader_compiles() {
+        if let Err(e) = halftone_effect() {
+            panic!("halftone shader: {}", e);
+        }
+    }
+
+    #[test]
+    fn seeded_noise_is_deterministic() {
+        for noise in [Noise::White, Noise::Perlin { scale: 4.0 }] {
+            let noisy = |seed| {
+                let mut pixels = gray(16, 128);
+                pixels.add_noise(&mut Rng::new(seed), 0.25, noise);
+                pixels.data
+            };
+            assert_eq!(noisy(7), noisy(7), "{:?}", noise);
+            assert_ne!(noisy(7), noisy(8), "{:?}", noise);
+            assert_ne!(noisy(7), gray(16, 128).data, "{:?}", noise);
+        }
+    }
+
+    #[test]
+    fn gradient_map_follows_luminance() {
+        let shadows = Color::from_rgb(20, 40, 200);
+        let middle = Color::from_rgb(200, 100, 0);
+        let highlights = Color::from_rgb(250, 250, 120);
+        let map = GradientMap::new(vec![(0.0, shadows), (0.5, middle), (1.0, highlights)]);
+        assert_eq!(map.sample(0.0), [20.0, 40.0, 200.0]);
+        assert_eq!(map.sample(0.5), [200.0, 100.0, 0.0]);
+        assert_eq!(map.sample(1.0), [250.0, 250.0, 120.0]);
+        assert_eq!(map.sample(0.25), [110.0, 70.0, 100.0]);
+
+        // Black, mid gray (a hair past 0.5) and white pick the same colors.
+        let mut pixels = Pixels { width: 3, height: 1, data: vec![0, 0, 0, 255, 128, 128, 128, 255, 255, 255, 255, 255] };
+        pixels.apply_gradient_map(&map);
+        let expected = [20, 40, 200, 255, 200, 101, 0, 255, 250, 250, 120, 255];
+        assert_eq!(pixels.data, expected);
+    }
+
+    #[test]
+    fn gradient_presets_round_trip() {
+        let user = GradientPreset { name: "Mine".to_string(), map: GradientMap::duotone(Color::from_rgb(1, 2, 3), Color::WHITE) };
+        let json = serde_json::to_string(&user).unwrap();
+        assert_eq!(serde_json::from_str::<GradientPreset>(&json).unwrap(), user);
+
+        let presets = gradient_presets(std::slice::from_ref(&user));
+        assert_eq!(presets.len(), DUOTONE_PRESETS.len() + 1);
+        assert_eq!(presets[0].map, GradientMap::preset("Sepia").unwrap());
+        assert_eq!(presets.last(), Some(&user));
+    }
+}
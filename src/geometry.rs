@@ -0,0 +1,128 @@
+use skia_safe::{Point, Rect};
+
+pub fn union(a: Rect, b: Rect) -> Rect {
+    Rect::new(a.left.min(b.left), a.top.min(b.top), a.right.max(b.right), a.bottom.max(b.bottom))
+}
+
+pub fn union_all(rects: impl IntoIterator<Item = Rect>) -> Option<Rect> {
+    rects.into_iter().reduce(union)
+}
+
+// None when the rects only touch or don't overlap at all.
+pub fn intersection(a: Rect, b: Rect) -> Option<Rect> {
+    let rect = Rect::new(a.left.max(b.left), a.top.max(b.top), a.right.min(b.right), a.bottom.min(b.bottom));
+    (rect.left < rect.right && rect.top < rect.bottom).then_some(rect)
+}
+
+// Grows each side outwards; negative amounts shrink.
+pub fn inflate(rect: Rect, dx: f32, dy: f32) -> Rect {
+    Rect::new(rect.left - dx, rect.top - dy, rect.right + dx, rect.bottom + dy)
+}
+
+// Rect::contains excludes the right and bottom edges; hit testing wants
+// them, so the pointer on a border still counts.
+pub fn contains_inclusive(rect: Rect, point: Point) -> bool {
+    point.x >= rect.left && point.x <= rect.right && point.y >= rect.top && point.y <= rect.bottom
+}
+
+// `radius` is clamped to half the shorter side, like skia does when it
+// draws the rect.
+pub fn round_rect_contains(rect: Rect, radius: f32, point: Point) -> bool {
+    if !contains_inclusive(rect, point) {
+        return false;
+    }
+    let radius = radius.min(rect.width() / 2.0).min(rect.height() / 2.0).max(0.0);
+    // Distance into the corner square, zero outside the corners.
+    let dx = (rect.left + radius - point.x).max(point.x - (rect.right - radius)).max(0.0);
+    let dy = (rect.top + radius - point.y).max(point.y - (rect.bottom - radius)).max(0.0);
+    dx * dx + dy * dy <= radius * radius
+}
+
+// The ellipse inscribed in `rect`.
+pub fn oval_contains(rect: Rect, point: Point) -> bool {
+    let rx = rect.width() / 2.0;
+    let ry = rect.height() / 2.0;
+    if rx <= 0.0 || ry <= 0.0 {
+        return false;
+    }
+    let dx = (point.x - rect.center_x()) / rx;
+    let dy = (point.y - rect.center_y()) / ry;
+    dx * dx + dy * dy <= 1.0
+}
+
+pub fn lerp_point(a: Point, b: Point, t: f32) -> Point {
+    Point::new(a.x + (b.x - a.x) * t, a.y + (b.y - a.y) * t)
+}
+
+// Even-odd rule, so self-intersecting outlines have holes.
+pub fn point_in_polygon(point: Point, polygon: &[Point]) -> bool {
+    let mut inside = false;
+    let mut previous = match polygon.last() {
+        Some(last) => *last,
+        None => return false,
+    };
+    for current in polygon {
+        if (current.y > point.y) != (previous.y > point.y) {
+            let x = current.x + (point.y - current.y) * (previous.x - current.x) / (previous.y - current.y);
+            if point.x < x {
+                inside = !inside;
+            }
+        }
+        previous = *current;
+    }
+    inside
+}
+
+fn cross(a: Point, b: Point) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+// Where segments a0-a1 and b0-b1 cross, endpoints included. Parallel
+// segments never intersect, even when they overlap.
+pub fn segment_intersection(a0: Point, a1: Point, b0: Point, b1: Point) -> Option<Point> {
+    let da = a1 - a0;
+    let db = b1 - b0;
+    let denominator = cross(da, db);
+    if denominator.abs() <= f32::EPSILON {
+        return None;
+    }
+    let offset = b0 - a0;
+    let t = cross(offset, db) / denominator;
+    let u = cross(offset, da) / denominator;
+    ((0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u)).then(|| lerp_point(a0, a1, t))
+}
+
+pub fn cubic_point(p0: Point, p1: Point, p2: Point, p3: Point, t: f32) -> Point {
+    let mt = 1.0 - t;
+    let a = mt * mt * mt;
+    let b = 3.0 * mt * mt * t;
+    let c = 3.0 * mt * t * t;
+    let d = t * t * t;
+    Point::new(
+        a * p0.x + b * p1.x + c * p2.x + d * p3.x,
+        a * p0.y + b * p1.y + c * p2.y + d * p3.y,
+    )
+}
+
+// Polyline within `tolerance` of the curve, endpoints included. The segment
+// count comes from the curve's second differences, which bound how far a
+// chord can stray.
+pub fn flatten_cubic(p0: Point, p1: Point, p2: Point, p3: Point, tolerance: f32) -> Vec<Point> {
+    let dd = ((p0 - p1 * 2.0) + p2).length().max(((p1 - p2 * 2.0) + p3).length());
+    let segments = (0.75 * dd / tolerance.max(f32::EPSILON)).sqrt().ceil().max(1.0) as usize;
+    (0..=segments).map(|i| cubic_point(p0, p1, p2, p3, i as f32 / segments as f32)).collect()
+}
+
+// Angles are in radians, clockwise from the positive x axis since y points
+// down.
+pub fn arc_point(center: Point, radius: f32, angle: f32) -> Point {
+    Point::new(center.x + radius * angle.cos(), center.y + radius * angle.sin())
+}
+
+pub fn flatten_arc(center: Point, radius: f32, start: f32, sweep: f32, tolerance: f32) -> Vec<Point> {
+    let step = if radius > tolerance { 2.0 * (1.0 - tolerance / radius).acos() } else { sweep.abs() };
+    let segments = (sweep.abs() / step.max(f32::EPSILON)).ceil().max(1.0) as usize;
+    (0..=segments)
+        .map(|i| arc_point(center, radius, start + sweep * i as f32 / segments as f32))
+        .collect()
+}
@@ -0,0 +1,80 @@
+use std::{collections::VecDeque, time::Duration};
+
+// Enough for both passes of a few frames still on the GPU.
+const QUERY_COUNT: usize = 8;
+
+// GL_TIME_ELAPSED queries around render passes. Results arrive a frame or
+// more later and are only collected once available, so timing never
+// stalls the pipeline. Needs a current GL context with timer queries
+// (GL 3.3 or ARB_timer_query).
+pub struct GpuTimer {
+    queries: Vec<u32>,
+    free: Vec<u32>,
+    in_flight: VecDeque<(&'static str, u32)>,
+    current: Option<(&'static str, u32)>,
+}
+
+impl GpuTimer {
+    pub fn new() -> Self {
+        let mut queries = vec![0; QUERY_COUNT];
+        unsafe {
+            gl::GenQueries(QUERY_COUNT as i32, queries.as_mut_ptr());
+        }
+        GpuTimer { free: queries.clone(), queries, in_flight: VecDeque::new(), current: None }
+    }
+
+    // False if every query is still in flight; that pass goes untimed.
+    pub fn begin(&mut self, pass: &'static str) -> bool {
+        if self.current.is_some() {
+            return false;
+        }
+        let Some(query) = self.free.pop() else { return false; };
+        unsafe {
+            gl::BeginQuery(gl::TIME_ELAPSED, query);
+        }
+        self.current = Some((pass, query));
+        true
+    }
+
+    pub fn end(&mut self) {
+        if let Some(current) = self.current.take() {
+            unsafe {
+                gl::EndQuery(gl::TIME_ELAPSED);
+            }
+            self.in_flight.push_back(current);
+        }
+    }
+
+    // Finished passes, oldest first.
+    pub fn poll(&mut self) -> Vec<(&'static str, Duration)> {
+        let mut results = Vec::new();
+        while let Some(&(pass, query)) = self.in_flight.front() {
+            let mut available = 0;
+            let mut nanos = 0;
+            unsafe {
+                gl::GetQueryObjectiv(query, gl::QUERY_RESULT_AVAILABLE, &mut available);
+                if available == 0 {
+                    break;
+                }
+                gl::GetQueryObjectui64v(query, gl::QUERY_RESULT, &mut nanos);
+            }
+            self.in_flight.pop_front();
+            self.free.push(query);
+            results.push((pass, Duration::from_nanos(nanos)));
+        }
+        results
+    }
+}
+
+impl Drop for GpuTimer {
+    fn drop(&mut self) {
+        if self.current.is_some() {
+            unsafe {
+                gl::EndQuery(gl::TIME_ELAPSED);
+            }
+        }
+        unsafe {
+            gl::DeleteQueries(self.queries.len() as i32, self.queries.as_ptr());
+        }
+    }
+}
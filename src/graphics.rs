@@ -1,4 +1,61 @@
-use skia_safe::{Point, Rect};
+use skia_safe::{Point, Rect, Vector};
 
-fn parallax(rect: &Rect, point: Point) {
+use crate::ecs::{Component, Entity, Parallax, World};
+
+// How far parallax_compute currently shifts an entity, in parent space.
+// Applied on top of its Transform when drawing (see DrawScope::for_entity),
+// so the Transform itself, and with it saves and undo steps, never moves.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParallaxOffset(pub Vector);
+
+impl Component for ParallaxOffset {}
+
+// Shifts every Parallax entity by the pointer's offset from the viewport
+// center times its strength, so layers with different strengths slide
+// against each other. Returns whether anything moved.
+pub fn parallax_compute(world: &mut World, pointer: Point, viewport: Rect) -> bool {
+    let offset = pointer - viewport.center();
+    let moves: Vec<(Entity, Vector)> = {
+        let applied = world.storage::<ParallaxOffset>();
+        world.query::<Parallax>().iter()
+            .map(|(entity, parallax)| (entity, offset * parallax.strength))
+            // Only write real moves so Changed<ParallaxOffset> stays quiet.
+            .filter(|(entity, shift)| applied.as_ref().and_then(|applied| applied.get(*entity)) != Some(&ParallaxOffset(*shift)))
+            .collect()
+    };
+    let moved = !moves.is_empty();
+    for (entity, shift) in moves {
+        world.insert(entity, ParallaxOffset(shift));
+    }
+    moved
+}
+
+#[cfg(test)]
+mod tests {
+    use skia_safe::{Matrix, Point, Rect};
+
+    use super::*;
+    use crate::ecs::Transform;
+    use crate::testing::WorldBuilder;
+
+    #[test]
+    fn parallax_follows_pointer_by_strength() {
+        let base = Transform { local_to_parent: Matrix::translate((5.0, 5.0)), z: 0.0 };
+        let (mut world, entities) = WorldBuilder::new()
+            .region(Rect::from_wh(10.0, 10.0))
+            .with(base)
+            .with(Parallax { strength: 0.5 })
+            .build();
+        let viewport = Rect::from_wh(100.0, 100.0);
+        let shift = |world: &World| world.storage::<ParallaxOffset>().and_then(|storage| storage.get(entities[0]).copied());
+
+        assert!(parallax_compute(&mut world, Point::new(70.0, 50.0), viewport));
+        assert_eq!(shift(&world), Some(ParallaxOffset(Vector::new(10.0, 0.0))));
+        assert!(!parallax_compute(&mut world, Point::new(70.0, 50.0), viewport));
+        // The new shift replaces the old one, and the Transform never moves.
+        assert!(parallax_compute(&mut world, Point::new(50.0, 30.0), viewport));
+        assert_eq!(shift(&world), Some(ParallaxOffset(Vector::new(0.0, -10.0))));
+        let transform = world.storage::<Transform>().unwrap().get(entities[0]).copied().unwrap();
+        assert_eq!(transform.local_to_parent, base.local_to_parent);
+    }
 }
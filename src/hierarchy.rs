@@ -0,0 +1,270 @@
+use std::io;
+
+use skia_safe::{Rect, Vector};
+
+use crate::align::translate;
+use crate::geometry::union_all;
+use crate::ecs::{Bounds, Children, Component, Entity, Hidden, Locked, Parent, World};
+use crate::scene::SceneData;
+
+pub fn parent_of(world: &World, entity: Entity) -> Option<Entity> {
+    world.storage::<Parent>()?.get(entity).map(|parent| parent.0)
+}
+
+pub fn children_of(world: &World, entity: Entity) -> Vec<Entity> {
+    world.storage::<Children>()
+        .and_then(|storage| storage.get(entity).map(|children| children.0.clone()))
+        .unwrap_or_default()
+}
+
+pub fn is_group(world: &World, entity: Entity) -> bool {
+    world.storage::<Children>().is_some_and(|storage| storage.contains(entity))
+}
+
+// Whether `entity` or any group containing it has a `T`.
+fn inherits<T: Component>(world: &World, entity: Entity) -> bool {
+    let Some(storage) = world.storage::<T>() else { return false; };
+    let mut current = Some(entity);
+    while let Some(entity) = current {
+        if storage.contains(entity) {
+            return true;
+        }
+        current = parent_of(world, entity);
+    }
+    false
+}
+
+pub fn is_hidden(world: &World, entity: Entity) -> bool {
+    inherits::<Hidden>(world, entity)
+}
+
+pub fn is_locked(world: &World, entity: Entity) -> bool {
+    inherits::<Locked>(world, entity)
+}
+
+// Sets `marker` on all of `entities`, or removes it if they all have it
+// already.
+pub fn toggle_marker<T: Component + Copy>(world: &mut World, entities: &[Entity], marker: T) {
+    let all_set = world.storage::<T>()
+        .is_some_and(|storage| entities.iter().all(|entity| storage.contains(*entity)));
+    for entity in entities {
+        if all_set {
+            world.remove::<T>(*entity);
+        } else {
+            world.insert(*entity, marker);
+        }
+    }
+}
+
+pub fn depth(world: &World, entity: Entity) -> usize {
+    let mut depth = 0;
+    let mut current = entity;
+    while let Some(parent) = parent_of(world, current) {
+        depth += 1;
+        current = parent;
+    }
+    depth
+}
+
+// The ancestor of `entity` (or `entity` itself) that sits directly inside
+// `scope`; `None` if `entity` isn't inside `scope` at all. A `None` scope is
+// the top level.
+pub fn ancestor_in_scope(world: &World, entity: Entity, scope: Option<Entity>) -> Option<Entity> {
+    let mut current = entity;
+    loop {
+        let parent = parent_of(world, current);
+        if parent == scope {
+            return Some(current);
+        }
+        current = parent?;
+    }
+}
+
+fn union_bounds(world: &World, entities: &[Entity]) -> Option<Rect> {
+    let bounds = world.storage::<Bounds>()?;
+    union_all(entities.iter().filter_map(|entity| bounds.get(*entity).map(|bounds| bounds.rect)))
+}
+
+fn attach(world: &mut World, entity: Entity, parent: Entity) {
+    world.insert(entity, Parent(parent));
+    if let Some(mut children) = world.storage_mut::<Children>().as_mut().and_then(|storage| storage.get_mut(parent)) {
+        children.0.push(entity);
+    }
+}
+
+fn detach(world: &mut World, entity: Entity) {
+    let Some(Parent(parent)) = world.remove::<Parent>(entity) else { return; };
+    if let Some(mut children) = world.storage_mut::<Children>().as_mut().and_then(|storage| storage.get_mut(parent)) {
+        children.0.retain(|child| *child != entity);
+    }
+}
+
+// Puts `entities` under a new group entity whose Bounds cover them all. The
+// group takes the place of the first entity in the hierarchy.
+pub fn group(world: &mut World, entities: &[Entity]) -> Option<Entity> {
+    let rect = union_bounds(world, entities)?;
+    let parent = parent_of(world, entities[0]);
+
+    let group = world.spawn();
+    world.insert(group, Bounds { rect });
+    world.insert(group, Children(Vec::new()));
+    for entity in entities {
+        detach(world, *entity);
+        attach(world, *entity, group);
+    }
+    if let Some(parent) = parent {
+        attach(world, group, parent);
+    }
+    Some(group)
+}
+
+// Loads a copied subtree (see World::copy_subtree) into `world` as a new
+// child of `parent`, or at the top level, moved by `offset`. Names are
+// left out, so a name keeps pointing at the original. Returns the new
+// root.
+pub fn paste(world: &mut World, mut scene: SceneData, parent: Option<Entity>, offset: Vector) -> io::Result<Entity> {
+    for entity in &mut scene.entities {
+        entity.components.remove("Name");
+    }
+    let root = *world.load(scene)?.first().ok_or_else(|| io::Error::other("nothing to paste"))?;
+    if let Some(parent) = parent {
+        attach(world, root, parent);
+    }
+    translate(world, root, offset);
+    Ok(root)
+}
+
+// Copies `entity` and everything inside it next to the original, under
+// the same parent, moved by `offset`.
+pub fn duplicate(world: &mut World, entity: Entity, offset: Vector) -> io::Result<Entity> {
+    let scene = world.copy_subtree(entity)?;
+    let parent = parent_of(world, entity);
+    paste(world, scene, parent, offset)
+}
+
+// Moves the group's children up to the group's own parent and despawns it.
+pub fn ungroup(world: &mut World, group: Entity) -> Vec<Entity> {
+    if !is_group(world, group) {
+        return Vec::new();
+    }
+    let children = children_of(world, group);
+    let parent = parent_of(world, group);
+    detach(world, group);
+    for child in &children {
+        detach(world, *child);
+        if let Some(parent) = parent {
+            attach(world, *child, parent);
+        }
+    }
+    world.despawn(group);
+    children
+}
+
+impl World {
+    // Despawns `entity` and everything inside it, and takes it out of its
+    // parent's Children. Returns how many entities were despawned.
+    pub fn despawn_recursive(self: &mut Self, entity: Entity) -> usize {
+        if !self.is_alive(entity) {
+            return 0;
+        }
+        detach(self, entity);
+        despawn_subtree(self, entity)
+    }
+}
+
+fn despawn_subtree(world: &mut World, entity: Entity) -> usize {
+    let mut count = 0;
+    for child in children_of(world, entity) {
+        count += despawn_subtree(world, child);
+    }
+    if world.despawn(entity) { count + 1 } else { count }
+}
+
+// Refits every group to its children, innermost groups first so nested
+// groups see up to date child bounds. Unchanged bounds aren't written, so
+// they don't show up as Changed<Bounds>.
+pub fn update_group_bounds(world: &World) {
+    let mut groups: Vec<(usize, Entity)> = world.query::<Children>().iter()
+        .map(|(entity, _)| (depth(world, entity), entity))
+        .collect();
+    groups.sort_by(|a, b| b.0.cmp(&a.0));
+
+    for (_, group) in groups {
+        let Some(rect) = union_bounds(world, &children_of(world, group)) else { continue; };
+        let Some(mut storage) = world.storage_mut::<Bounds>() else { return; };
+        if let Some(mut bounds) = storage.get_mut(group) {
+            if bounds.rect != rect {
+                bounds.rect = rect;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use skia_safe::{Color4f, Matrix, Point};
+
+    use super::*;
+    use crate::dynamic::{DynamicLayout, FieldKind};
+    use crate::ecs::{Adjustment, Name, Opacity, Quad, Slice, Transform};
+
+    fn subtree(world: &World, root: Entity) -> Vec<Entity> {
+        let mut entities = vec![root];
+        let mut next = 0;
+        while next < entities.len() {
+            entities.extend(children_of(world, entities[next]));
+            next += 1;
+        }
+        entities
+    }
+
+    fn layer(world: &mut World, name: &str, rect: Rect) -> Entity {
+        let entity = world.spawn();
+        world.insert(entity, Name(name.to_string()));
+        world.insert(entity, Bounds { rect });
+        world.insert(entity, Quad { rect, color: Color4f::new(0.2, 0.4, 0.6, 1.0) });
+        entity
+    }
+
+    #[test]
+    fn duplicating_a_group_keeps_every_registered_component() {
+        let mut world = World::new();
+        world.register_dynamic(DynamicLayout::new("Health").field("current", FieldKind::Number)).unwrap();
+        let a = layer(&mut world, "a", Rect::from_xywh(0.0, 0.0, 10.0, 10.0));
+        world.insert(a, Opacity(0.5));
+        world.insert(a, Adjustment::Posterize { levels: 3 });
+        world.insert(a, Locked);
+        world.insert_dynamic(a, "Health", json!({ "current": 3.0 })).unwrap();
+        let b = layer(&mut world, "b", Rect::from_xywh(20.0, 0.0, 10.0, 10.0));
+        world.insert(b, Slice { name: "icon".to_string(), rect: Rect::from_xywh(20.0, 0.0, 10.0, 10.0), pivot: Point::new(0.5, 1.0) });
+        world.insert(b, Transform { local_to_parent: Matrix::scale((2.0, 2.0)), z: 1.0 });
+        let c = layer(&mut world, "c", Rect::from_xywh(0.0, 20.0, 10.0, 10.0));
+        world.insert(c, Hidden);
+        let inner = group(&mut world, &[b, c]).unwrap();
+        let outer = group(&mut world, &[a, inner]).unwrap();
+
+        let copy = duplicate(&mut world, outer, Vector::new(0.0, 0.0)).unwrap();
+
+        let originals = subtree(&world, outer);
+        let copies = subtree(&world, copy);
+        assert_eq!(copies.len(), originals.len());
+        assert!(copies.iter().all(|entity| !originals.contains(entity)));
+        assert_eq!(parent_of(&world, copy), None);
+        for (original, copy) in originals.iter().zip(&copies) {
+            let mut expected = world.serialize_components(*original).unwrap();
+            let mut copied = world.serialize_components(*copy).unwrap();
+            for name in ["Name", "Parent", "Children"] {
+                expected.remove(name);
+                copied.remove(name);
+            }
+            assert_eq!(copied, expected);
+            assert!(world.storage::<Name>().unwrap().get(*copy).is_none());
+            // Parent and Children point into the copy, not the original.
+            if let Some(parent) = parent_of(&world, *copy) {
+                assert!(copies.contains(&parent));
+            }
+            assert!(children_of(&world, *copy).iter().all(|child| copies.contains(child)));
+        }
+    }
+}
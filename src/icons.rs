@@ -0,0 +1,61 @@
+use skia_safe::{BlendMode, Canvas, Color4f, Matrix, Paint, PaintCap, PaintJoin, PaintStyle, Path, Rect, color_filters};
+
+use crate::draw::DrawScope;
+use crate::atlas::{AtlasIcon, draw_region};
+use crate::ecs::Icon;
+
+// Bundled icons are stroked outlines on a 24x24 grid, so they scale cleanly
+// and take the tint like text would.
+const ICON_GRID: f32 = 24.0;
+const ICON_STROKE: f32 = 2.0;
+
+const ICONS: &[(&str, &str)] = &[
+    ("close", "M6 6 L18 18 M18 6 L6 18"),
+    ("check", "M5 12 L10 17 L19 7"),
+    ("plus", "M12 5 L12 19 M5 12 L19 12"),
+    ("minus", "M5 12 L19 12"),
+    ("menu", "M4 6 L20 6 M4 12 L20 12 M4 18 L20 18"),
+    ("chevron-left", "M15 6 L9 12 L15 18"),
+    ("chevron-right", "M9 6 L15 12 L9 18"),
+    ("chevron-up", "M6 15 L12 9 L18 15"),
+    ("chevron-down", "M6 9 L12 15 L18 9"),
+    ("info", "M12 3 A9 9 0 1 0 12 21 A9 9 0 1 0 12 3 M12 11 L12 16 M12 8 L12 8"),
+    ("warning", "M12 3 L22 20 L2 20 Z M12 10 L12 14 M12 17 L12 17"),
+    ("search", "M10 4 A6 6 0 1 0 10 16 A6 6 0 1 0 10 4 M14.5 14.5 L20 20"),
+];
+
+pub fn icon_names() -> impl Iterator<Item = &'static str> {
+    ICONS.iter().map(|(name, _)| *name)
+}
+
+// The icon's outline on the 24x24 grid; None for unknown names.
+pub fn icon_path(name: &str) -> Option<Path> {
+    let (_, svg) = ICONS.iter().find(|(icon, _)| *icon == name)?;
+    Path::from_svg(svg)
+}
+
+// Draws `icon` centered in `rect`: its atlas image, tinted, when it has
+// one, otherwise its outline. Unknown names draw nothing.
+pub fn draw_icon(canvas: &Canvas, icon: &Icon, atlas: Option<&AtlasIcon>, rect: Rect) {
+    if let Some(atlas) = atlas {
+        let dst = Rect::from_xywh(rect.center_x() - icon.size * 0.5, rect.center_y() - icon.size * 0.5, icon.size, icon.size);
+        let mut paint = Paint::default();
+        paint.set_color_filter(color_filters::blend(icon.tint, BlendMode::SrcIn));
+        draw_region(canvas, &atlas.page, atlas.src, dst, &paint);
+        return;
+    }
+    let Some(path) = icon_path(&icon.name) else { return; };
+    let scale = icon.size / ICON_GRID;
+    let mut paint = Paint::new(Color4f::from(icon.tint), None);
+    paint.set_anti_alias(true);
+    paint.set_style(PaintStyle::Stroke);
+    paint.set_stroke_width(ICON_STROKE);
+    paint.set_stroke_cap(PaintCap::Round);
+    paint.set_stroke_join(PaintJoin::Round);
+
+    let mut matrix = Matrix::scale((scale, scale));
+    matrix.post_translate((rect.center_x() - icon.size * 0.5, rect.center_y() - icon.size * 0.5));
+    let scope = DrawScope::new(canvas);
+    scope.transform(&matrix);
+    scope.canvas().draw_path(&path, &paint);
+}
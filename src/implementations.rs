@@ -1,8 +1,8 @@
 use glutin::{config::GlConfig, prelude::PossiblyCurrentGlContext};
 use skia_safe::{ColorType, gpu::{backend_render_targets::make_gl, gl::{Format, FramebufferInfo}, surfaces::wrap_backend_render_target}};
-use winit::dpi::PhysicalSize;
+use winit::{dpi::PhysicalSize, event::MouseButton};
 
-use crate::ecs::GpuState;
+use crate::ecs::{GpuState, PointerButton};
 
 impl GpuState {
     pub fn create_skia_surface(&mut self, size: PhysicalSize<u32>) {
@@ -36,3 +36,16 @@ impl GpuState {
         ).expect("failed to create skia surface"));
     }
 }
+
+impl From<MouseButton> for PointerButton {
+    fn from(button: MouseButton) -> Self {
+        match button {
+            MouseButton::Left => PointerButton::Left,
+            MouseButton::Right => PointerButton::Right,
+            MouseButton::Middle => PointerButton::Middle,
+            MouseButton::Back => PointerButton::Other(3),
+            MouseButton::Forward => PointerButton::Other(4),
+            MouseButton::Other(id) => PointerButton::Other(id),
+        }
+    }
+}
@@ -1,6 +1,44 @@
 pub mod implementations;
+pub mod adjustments;
+pub mod align;
+pub mod assets;
+pub mod atlas;
 pub mod canvas;
+pub mod capture;
+pub mod clipboard;
+pub mod color;
+pub mod constraints;
+pub mod draw;
+pub mod dynamic;
 pub mod ecs;
+pub mod export;
+pub mod filter_menu;
+pub mod filters;
+pub mod geometry;
+pub mod gpu_timer;
+pub mod graphics;
+pub mod hierarchy;
+pub mod icons;
+pub mod packer;
+pub mod panel;
+pub mod prefab;
+pub mod quality;
+pub mod registry;
+pub mod rng;
+pub mod scene;
+pub mod settings;
+pub mod snapshot;
+pub mod start;
+pub mod status;
+pub mod systems;
+pub mod templates;
+#[cfg(test)]
+mod testing;
+pub mod text;
+pub mod timers;
+pub mod units;
+pub mod wakeup;
+pub mod workspace;
 
 use glutin::config::{ConfigTemplateBuilder, GlConfig};
 use glutin::context::{ContextAttributesBuilder, PossiblyCurrentContext};
@@ -13,43 +51,95 @@ use skia_safe::gpu::backend_render_targets::make_gl;
 use skia_safe::gpu::surfaces::wrap_backend_render_target;
 use skia_safe::gpu::{direct_contexts, BackendRenderTarget, Budgeted, DirectContext, Protected, SurfaceOrigin};
 use skia_safe::gpu::gl::{Format, FramebufferInfo, Interface};
-use skia_safe::{Canvas, Color, Color4f, ColorType, Image, Matrix, Paint, Point, Rect};
+use skia_safe::{Canvas, Color, Color4f, ColorType, Image, Matrix, Paint, Point, Rect, Vector};
 use winit::dpi::{LogicalPosition, PhysicalPosition, PhysicalSize};
 use winit::error::EventLoopError;
 use winit::event::{ElementState, Ime, KeyEvent, Modifiers, MouseButton, WindowEvent};
-use winit::event_loop::{ActiveEventLoop, EventLoop};
+use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
 use winit::keyboard::{KeyCode, ModifiersKeyState, PhysicalKey};
 use winit::platform::modifier_supplement::KeyEventExtModifierSupplement;
 use winit::platform::scancode::PhysicalKeyExtScancode;
 use winit::raw_window_handle::{self, HasRawWindowHandle, HasWindowHandle};
-use winit::window::{Window, WindowAttributes, WindowId};
+use winit::window::{CursorIcon, Window, WindowAttributes, WindowId};
 
 use std::ffi::CString;
+use std::io;
 use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 
-use crate::ecs::{Bounds, Entity, GpuState, Interactable, Quad, Resources, Transform, World, render_quads};
+use crate::ecs::{Bounds, EventReader, GpuState, Hidden, Interactable, Locked, InteractionEvent, PointerButton, Resources, RubberBand, Selection, SelectionMode, Sprite, Transform, World, render_quads};
+use crate::align::{Align, Axis};
+use crate::assets::import_image;
+use crate::atlas::{ATLAS_PAGE_SIZE, TextureAtlas, atlas_icon_system};
+use crate::constraints::constraint_system;
+use crate::draw::DrawScope;
+use crate::filter_menu::{FilterMenu, MenuAction};
+use crate::filters::{GradientPreset, gradient_presets};
+use crate::gpu_timer::GpuTimer;
+use crate::hierarchy::update_group_bounds;
+use crate::panel::{TextPanel, inspector_lines};
+use crate::settings::{SETTINGS_PATH, Settings};
+use crate::snapshot::{SavePoint, UndoHistory, WorldSnapshot};
+use crate::start::{RECENT_PATH, RecentFiles, StartAction, StartScreen};
+use crate::status::StatusBar;
+use crate::systems::{button_prefab, changed_visuals, click_select, entity_at, hover_detect, hover_update, render_rubber_band, render_selection, render_system, slice_at, update_document};
+use crate::templates::{DialogAction, NewDocumentDialog, TEMPLATES_PATH, Template};
+use crate::text::text_layout_system;
+use crate::wakeup::{Waker, Wakeup};
+use crate::workspace::{Dock, Workspace, dock_system};
 
-#[derive(PartialEq, Eq, Clone)]
-enum InteractableState {
-    DEFAULT,
-    HOVERED,
-    PRESSED,
-}
-
-impl InteractableState {
-    fn color(&self) -> Color4f {
-        match self {
-            InteractableState::DEFAULT => Color4f::new(0.5, 0.5, 0.5, 1.0),
-            InteractableState::HOVERED => Color4f::new(0.6, 0.6, 0.6, 1.0),
-            InteractableState::PRESSED => Color4f::new(0.3, 0.3, 0.3, 1.0),
-        }
-    }
-}
+const DUPLICATE_OFFSET: f32 = 10.0;
+const NUDGE_SMALL: f32 = 1.0;
+const NUDGE_LARGE: f32 = 10.0;
+const SCENE_PATH: &str = "scene.json";
+const CAPTURE_PATH: &str = "capture/frame.skp";
+const ICONS_DIR: &str = "assets/icons";
+// Diagnostic panels' distance from the window edge.
+const PANEL_MARGIN: f32 = 12.0;
+const INSPECTOR_WIDTH: f32 = 280.0;
+const DRAW_OPS_WIDTH: f32 = 320.0;
+const GPU_TIMINGS_WIDTH: f32 = 240.0;
+// Room for the frame line and one per timed pass.
+const GPU_TIMINGS_HEIGHT: f32 = 80.0;
+// Timings change every frame; faster than this they can't be read anyway.
+const GPU_TIMINGS_INTERVAL: Duration = Duration::from_millis(500);
 
 struct App {
+    // The document; everything in it is saved, selectable and undoable.
     world: World,
+    // Editor chrome (toolbars, dialogs), drawn over the document in window
+    // space and never saved. World::move_to moves entities between the two.
+    ui: World,
+    // The open new-document dialog, whose entities live in `ui`.
+    dialog: Option<NewDocumentDialog>,
+    // The open filter menu, for the selection; also in `ui`.
+    filter_menu: Option<FilterMenu>,
+    // Shown instead of a document, e.g. at startup without a saved scene.
+    start: Option<StartScreen>,
+    status: StatusBar,
+    // Ctrl+I: the selection's components, kept current while open.
+    inspector: Option<TextPanel>,
+    // F12: what each entity drew in the last captured frame.
+    draw_ops: Option<TextPanel>,
+    // F11: the latest GPU pass timings while profiling, and when they were
+    // last shown.
+    gpu_timings: Option<TextPanel>,
+    gpu_timings_shown: Instant,
+    recent: RecentFiles,
+    // The file the document was opened from or last saved to. New documents
+    // and samples have none until Ctrl+S picks an untitled one.
+    document_path: Option<PathBuf>,
+    // The document as last opened or saved; None if it couldn't be
+    // captured, which counts as unsaved changes.
+    saved: Option<SavePoint>,
+    // A key that would discard unsaved changes and was pressed once; the
+    // next key press runs it if it's the same key.
+    pending_discard: Option<KeyCode>,
+    settings: Settings,
     resources: Resources,
+    interaction_reader: EventReader<InteractionEvent>,
 }
 
 fn create_canvas_skia_surface(gr_context: &mut DirectContext, rect: Rect) -> skia_safe::Surface {
@@ -58,7 +148,7 @@ fn create_canvas_skia_surface(gr_context: &mut DirectContext, rect: Rect) -> ski
     skia_safe::gpu::surfaces::render_target(gr_context, Budgeted::Yes, &image_info, None, SurfaceOrigin::TopLeft, None, None, false).unwrap()
 }
 
-impl winit::application::ApplicationHandler<()> for App {
+impl winit::application::ApplicationHandler<Wakeup> for App {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         if self.resources.gpu_state.is_none() {
             let attrs = WindowAttributes::default().with_title("gamer");
@@ -92,35 +182,820 @@ impl winit::application::ApplicationHandler<()> for App {
                 gpu_state.gl_surface.resize(&gpu_state.gl_context, width, height);
 
                 gpu_state.create_skia_surface(size);
-                gpu_state.window.request_redraw();
+                self.resources.damage.add_full();
+                self.resources.frame_scheduler.request_redraw();
+            }
+            WindowEvent::Moved(_) | WindowEvent::ScaleFactorChanged { .. } => {
+                self.resources.time.update_refresh_rate(&gpu_state.window);
             }
             WindowEvent::CursorMoved { device_id, position } => {
                 let x = position.x as f32;
                 let y = position.y as f32;
-                let should_update = hover_system(&mut self.world, x, y);
-                if should_update { gpu_state.window.request_redraw(); }
+                self.resources.pointer_state.push_sample(Point::new(x, y));
             }
             WindowEvent::MouseInput { device_id, state, button } => {
+                let pressed = state == ElementState::Pressed;
+                self.resources.pointer_state.set_button(PointerButton::from(button), pressed);
+                if pressed && button == MouseButton::Left && self.dialog.is_some() {
+                    let position = self.resources.pointer_state.position;
+                    let action = match &mut self.dialog {
+                        Some(dialog) => dialog.click(&mut self.ui, position),
+                        None => DialogAction::Pending,
+                    };
+                    if !matches!(action, DialogAction::Pending) {
+                        if let Some(dialog) = self.dialog.take() {
+                            dialog.close(&mut self.ui);
+                        }
+                    }
+                    if let DialogAction::Create(document) = action {
+                        templates::new_document(&mut self.world, document);
+                        self.document_path = None;
+                        self.saved = save_point(&self.world);
+                        reset_editor_state(&mut self.resources);
+                    }
+                    self.resources.damage.add_full();
+                    self.resources.frame_scheduler.request_redraw();
+                } else if pressed && button == MouseButton::Left && self.filter_menu.is_some() {
+                    let position = self.resources.pointer_state.position;
+                    let action = match &self.filter_menu {
+                        Some(menu) => menu.click(&self.ui, position),
+                        None => MenuAction::Pending,
+                    };
+                    if !matches!(action, MenuAction::Pending) {
+                        if let Some(menu) = self.filter_menu.take() {
+                            menu.close(&mut self.ui);
+                        }
+                    }
+                    if let MenuAction::Run(command) = action {
+                        let before = WorldSnapshot::capture(&self.world);
+                        self.resources.assets.set_root(document_dir(self.document_path.as_deref()));
+                        let root = self.resources.assets.root().to_path_buf();
+                        let selection = self.resources.selection.entities();
+                        match adjustments::run_filter_command(&mut self.world, selection, &command, &mut self.resources.rng, &root) {
+                            Ok(count) => self.status.show(&mut self.ui, &format!("Filtered {} layers", count)),
+                            Err(e) => self.status.show(&mut self.ui, &format!("Failed to apply filter: {}", e)),
+                        }
+                        match before {
+                            Ok(before) => {
+                                if let Err(e) = self.resources.undo.commit(before, &self.world) {
+                                    eprintln!("Failed to record undo step: {:?}", e);
+                                }
+                            }
+                            Err(e) => eprintln!("Failed to capture undo step: {:?}", e),
+                        }
+                    }
+                    self.resources.damage.add_full();
+                    self.resources.frame_scheduler.request_redraw();
+                } else if pressed && button == MouseButton::Left && self.start.is_some() {
+                    let position = self.resources.pointer_state.position;
+                    let action = match &self.start {
+                        Some(start) => start.click(&self.ui, position),
+                        None => StartAction::Pending,
+                    };
+                    let opened = match action {
+                        StartAction::Pending => None,
+                        StartAction::New => {
+                            let size = gpu_state.window.inner_size();
+                            let viewport = Rect::from_wh(size.width as f32, size.height as f32);
+                            self.dialog = Some(NewDocumentDialog::open(&mut self.ui, new_document_templates(), viewport));
+                            Some(Ok(None))
+                        }
+                        StartAction::Open(path) => Some(scene::switch_scene(&mut self.world, &path, &[]).map(|_| Some(path))),
+                        // Samples are saved as a new file, never over the bundled one.
+                        StartAction::Sample(sample) => {
+                            Some(scene::switch_scene_str(&mut self.world, start::SAMPLES[sample].1, &[]).map(|_| None))
+                        }
+                    };
+                    match opened {
+                        Some(Ok(path)) => {
+                            if let Some(start) = self.start.take() {
+                                start.close(&mut self.ui);
+                            }
+                            if let Some(path) = &path {
+                                remember_recent(&mut self.recent, path);
+                            }
+                            self.document_path = path;
+                            self.saved = save_point(&self.world);
+                            reset_editor_state(&mut self.resources);
+                        }
+                        Some(Err(e)) => eprintln!("Failed to open scene: {:?}", e),
+                        None => {}
+                    }
+                    self.resources.damage.add_full();
+                    self.resources.frame_scheduler.request_redraw();
+                } else if pressed && button == MouseButton::Left && entity_at(&self.ui, self.resources.pointer_state.position).is_some() {
+                    // Docked panels cover the document; clicks on them
+                    // don't reach it.
+                } else if pressed && button == MouseButton::Left {
+                    let modifiers = self.resources.keyboard_state.modifiers.state();
+                    let cursor = self.resources.pointer_state.document_position();
+                    let target = entity_at(&self.world, cursor);
+                    if let Some(entity) = target {
+                        if self.world.storage::<Interactable>().is_some_and(|storage| storage.contains(entity)) {
+                            self.resources.interactions.send(InteractionEvent::Clicked(entity));
+                        }
+                    }
+                    let double_click = self.resources.pointer_state.register_click(cursor, &mut self.resources.timers);
+                    let selection = &mut self.resources.selection;
+                    self.resources.damage.add_entities(&self.world, selection.entities());
+                    // Pressing on empty space starts a rubber band.
+                    if click_select(&self.world, selection, cursor, modifiers.shift_key(), double_click).is_none() {
+                        let mode = if modifiers.shift_key() {
+                            SelectionMode::Add
+                        } else if modifiers.alt_key() {
+                            SelectionMode::Subtract
+                        } else {
+                            SelectionMode::Replace
+                        };
+                        let band = RubberBand::new(cursor, mode, selection);
+                        selection.replace(band.apply(&[]));
+                        self.resources.rubber_band = Some(band);
+                    }
+                    self.resources.damage.add_entities(&self.world, selection.entities());
+                    self.resources.frame_scheduler.request_redraw();
+                } else if !pressed && button == MouseButton::Left {
+                    if let Some(band) = self.resources.rubber_band.take() {
+                        self.resources.damage.add(band.rect());
+                        self.resources.frame_scheduler.request_redraw();
+                    }
+                }
             }
             WindowEvent::RedrawRequested => {
                 if gpu_state.skia_surface.is_none() {
                     gpu_state.create_skia_surface(gpu_state.window.inner_size());
                 }
+                self.resources.time.tick();
                 if let Some(surface) = &mut gpu_state.skia_surface {
+                    let damage = self.resources.damage.begin_frame(gpu_state.gl_surface.buffer_age());
                     let canvas = surface.canvas();
-                    render_system(&self.world, &canvas);
+                    let record_start = Instant::now();
+                    {
+                        let scope = DrawScope::new(canvas);
+                        if let Some(rect) = damage {
+                            scope.clip(rect);
+                        }
+                        // Skia defers GPU work to the flush, so each timed
+                        // pass is flushed on its own while profiling.
+                        if let Some(timer) = &mut gpu_state.timer { timer.begin("scene"); }
+                        render_system(&self.world, &canvas, self.resources.quality.level());
+                        if let Some(timer) = &mut gpu_state.timer {
+                            gpu_state.gr_context.flush_and_submit();
+                            timer.end();
+                            timer.begin("overlay");
+                        }
+                        render_selection(&self.world, &self.resources.selection, &canvas);
+                        if let Some(band) = &self.resources.rubber_band {
+                            render_rubber_band(band, &canvas);
+                        }
+                        render_quads(&self.ui, &canvas, self.resources.quality.level());
+                        if let Some(start) = &self.start {
+                            start.draw_thumbnails(&canvas);
+                        }
+                    }
+                    let record_time = record_start.elapsed();
                     gpu_state.gr_context.flush_and_submit();
-                    gpu_state.gl_surface.swap_buffers(&gpu_state.gl_context).unwrap();
+                    // Recording plus the flush stands in for the frame's
+                    // cost; it's all CPU side but tracks the load well enough.
+                    if self.resources.quality.record(record_start.elapsed(), self.resources.time.frame_interval()) {
+                        self.status.show(&mut self.ui, &self.resources.quality.describe());
+                        self.resources.damage.add_full();
+                        self.resources.frame_scheduler.request_redraw();
+                    }
+                    if let Some(timer) = &mut gpu_state.timer {
+                        timer.end();
+                        // Oldest first, so later frames overwrite earlier ones.
+                        let mut latest: Vec<(&'static str, Duration)> = Vec::new();
+                        for (pass, time) in timer.poll() {
+                            match latest.iter_mut().find(|(name, _)| *name == pass) {
+                                Some(entry) => entry.1 = time,
+                                None => latest.push((pass, time)),
+                            }
+                        }
+                        let due = self.gpu_timings_shown.elapsed() >= GPU_TIMINGS_INTERVAL;
+                        if let Some(panel) = self.gpu_timings.as_mut().filter(|_| due && !latest.is_empty()) {
+                            let mut lines = vec![format!("frame {}: recorded in {:.2?}", self.resources.frame_scheduler.frames_presented, record_time)];
+                            lines.extend(latest.iter().map(|(pass, time)| format!("gpu {}: {:.2?}", pass, time)));
+                            if let Some(rect) = panel.set_lines(&mut self.ui, lines) {
+                                self.resources.damage.add(rect);
+                            }
+                            self.gpu_timings_shown = Instant::now();
+                        }
+                    }
+                    present(gpu_state, damage);
+                    self.resources.frame_scheduler.frames_presented += 1;
                 }
             }
             WindowEvent::KeyboardInput { device_id, event, is_synthetic } => {
+                if event.state == ElementState::Released { return; }
+                let ctrl_pressed = self.resources.keyboard_state.modifiers.state().control_key();
+                let shift_pressed = self.resources.keyboard_state.modifiers.state().shift_key();
+                let alt_pressed = self.resources.keyboard_state.modifiers.state().alt_key();
+                // The dialog and the filter menu are modal: Escape closes them,
+                // other keys are ignored.
+                if let Some(dialog) = self.dialog.take() {
+                    if event.physical_key == PhysicalKey::Code(KeyCode::Escape) {
+                        dialog.close(&mut self.ui);
+                        self.resources.damage.add_full();
+                        self.resources.frame_scheduler.request_redraw();
+                    } else {
+                        self.dialog = Some(dialog);
+                    }
+                    return;
+                }
+                if let Some(menu) = self.filter_menu.take() {
+                    if event.physical_key == PhysicalKey::Code(KeyCode::Escape) {
+                        menu.close(&mut self.ui);
+                        self.resources.damage.add_full();
+                        self.resources.frame_scheduler.request_redraw();
+                    } else {
+                        self.filter_menu = Some(menu);
+                    }
+                    return;
+                }
+                // So is the start screen, with nothing to go back to.
+                if self.start.is_some() {
+                    return;
+                }
+                let PhysicalKey::Code(code) = event.physical_key else { return; };
+                let confirmed = !is_modifier(code) && self.pending_discard.take() == Some(code);
+                // Ctrl+Z undoes and Ctrl+Shift+Z redoes; any other key that
+                // changes the World becomes an undo step.
+                if ctrl_pressed && event.physical_key == PhysicalKey::Code(KeyCode::KeyZ) {
+                    let result = if shift_pressed {
+                        self.resources.undo.redo(&mut self.world)
+                    } else {
+                        self.resources.undo.undo(&mut self.world)
+                    };
+                    match result {
+                        Ok(true) => {
+                            self.resources.damage.add_full();
+                            self.resources.frame_scheduler.request_redraw();
+                        }
+                        Ok(false) => {}
+                        Err(e) => eprintln!("Failed to undo: {:?}", e),
+                    }
+                    return;
+                }
+                let before = edits_document(event.physical_key, ctrl_pressed, shift_pressed, alt_pressed)
+                    .then(|| WorldSnapshot::capture(&self.world));
+                match event.physical_key {
+                    // Ctrl+C copies the selection; with nothing selected, the
+                    // slice under the pointer as an image.
+                    PhysicalKey::Code(KeyCode::KeyC) if ctrl_pressed => {
+                        let selection = self.resources.selection.entities();
+                        if !selection.is_empty() {
+                            match clipboard::copy_entities(&self.world, selection) {
+                                Ok(()) => self.status.show(&mut self.ui, &format!("Copied {} layers", selection.len())),
+                                Err(e) => self.status.show(&mut self.ui, &format!("Failed to copy layers: {}", e)),
+                            }
+                        } else {
+                            let cursor = self.resources.pointer_state.document_position();
+                            if let Some(rect) = slice_at(&self.world, cursor) {
+                                if let Err(e) = clipboard::copy_region_image(&self.world, rect) {
+                                    eprintln!("Failed to copy image: {:?}", e);
+                                }
+                            }
+                        }
+                    }
+                    // Ctrl+V pastes copied layers into the group being edited,
+                    // offset like a duplicate, and selects them.
+                    PhysicalKey::Code(KeyCode::KeyV) if ctrl_pressed => {
+                        let offset = Vector::new(DUPLICATE_OFFSET, DUPLICATE_OFFSET);
+                        match clipboard::paste_entities(&mut self.world, self.resources.selection.scope, offset) {
+                            Ok(pasted) => {
+                                let selection = &mut self.resources.selection;
+                                self.resources.damage.add_entities(&self.world, selection.entities());
+                                selection.replace(pasted);
+                                self.resources.frame_scheduler.request_redraw();
+                            }
+                            Err(e) => self.status.show(&mut self.ui, &format!("Failed to paste: {}", e)),
+                        }
+                    }
+                    PhysicalKey::Code(KeyCode::KeyE) if ctrl_pressed && alt_pressed => {
+                        if let Err(e) = export::export_slices_svg(&self.world, Path::new("export")) {
+                            eprintln!("Failed to export svg: {:?}", e);
+                        }
+                    }
+                    PhysicalKey::Code(KeyCode::KeyE) if ctrl_pressed && shift_pressed => {
+                        if let Err(e) = export::export_slices_pdf(&self.world, Path::new("export/slices.pdf")) {
+                            eprintln!("Failed to export pdf: {:?}", e);
+                        }
+                    }
+                    PhysicalKey::Code(KeyCode::KeyE) if ctrl_pressed => {
+                        match export::export_slices(&self.world, &export::SLICE_SCALES, Path::new("export")) {
+                            Ok(paths) => self.status.show(&mut self.ui, &format!("Exported {} slice images", paths.len())),
+                            Err(e) => self.status.show(&mut self.ui, &format!("Failed to export slices: {}", e)),
+                        }
+                    }
+                    PhysicalKey::Code(KeyCode::KeyP) if ctrl_pressed => {
+                        if let Err(e) = export::export_sprite_sheet(&self.world, 1.0, Path::new("export"), "sheet") {
+                            eprintln!("Failed to export sprite sheet: {:?}", e);
+                        }
+                    }
+                    PhysicalKey::Code(KeyCode::KeyS) if ctrl_pressed && !alt_pressed => {
+                        let path = self.document_path.clone().unwrap_or_else(|| scene::untitled_path(Path::new(".")));
+                        if let Err(e) = scene::save_scene(&self.world, &path) {
+                            eprintln!("Failed to save scene: {:?}", e);
+                        } else {
+                            if let Err(e) = start::write_thumbnail(&self.world, &path) {
+                                eprintln!("Failed to write thumbnail: {:?}", e);
+                            }
+                            self.status.show(&mut self.ui, &format!("Saved {}", path.display()));
+                            remember_recent(&mut self.recent, &path);
+                            self.document_path = Some(path);
+                            self.saved = save_point(&self.world);
+                        }
+                    }
+                    // Ctrl+W closes the document and goes back to the start
+                    // screen; with unsaved changes, only when pressed twice.
+                    PhysicalKey::Code(KeyCode::KeyW) if ctrl_pressed => {
+                        if !confirmed && has_unsaved_changes(self.saved.as_ref(), &self.world) {
+                            self.pending_discard = Some(KeyCode::KeyW);
+                            self.status.show(&mut self.ui, "Unsaved changes: Ctrl+W again to discard them, Ctrl+S to save");
+                            return;
+                        }
+                        let size = gpu_state.window.inner_size();
+                        let viewport = Rect::from_wh(size.width as f32, size.height as f32);
+                        templates::new_document(&mut self.world, Default::default());
+                        self.document_path = None;
+                        self.saved = save_point(&self.world);
+                        reset_editor_state(&mut self.resources);
+                        self.start = Some(StartScreen::open(&mut self.ui, &self.recent, new_world, &mut self.resources.fonts, viewport));
+                        self.resources.damage.add_full();
+                        self.resources.frame_scheduler.request_redraw();
+                    }
+                    // Ctrl+N opens the new-document dialog; Ctrl+Shift+N
+                    // saves the current page settings as a template for it.
+                    PhysicalKey::Code(KeyCode::KeyN) if ctrl_pressed && shift_pressed => {
+                        let document = *self.world.document();
+                        let name = match document.page() {
+                            Some(page) => format!("{} x {}", document.format(page.width()), document.format(page.height())),
+                            None => format!("Unbounded {} dpi", document.dpi),
+                        };
+                        match templates::save_user_template(Path::new(TEMPLATES_PATH), Template { name: name.clone(), document }) {
+                            Ok(()) => self.status.show(&mut self.ui, &format!("Saved template {}", name)),
+                            Err(e) => self.status.show(&mut self.ui, &format!("Failed to save template: {}", e)),
+                        }
+                    }
+                    // Picking a template replaces the document, so with unsaved
+                    // changes the dialog only opens when pressed twice.
+                    PhysicalKey::Code(KeyCode::KeyN) if ctrl_pressed => {
+                        if !confirmed && has_unsaved_changes(self.saved.as_ref(), &self.world) {
+                            self.pending_discard = Some(KeyCode::KeyN);
+                            self.status.show(&mut self.ui, "Unsaved changes: Ctrl+N again to discard them, Ctrl+S to save");
+                            return;
+                        }
+                        let size = gpu_state.window.inner_size();
+                        let viewport = Rect::from_wh(size.width as f32, size.height as f32);
+                        self.dialog = Some(NewDocumentDialog::open(&mut self.ui, new_document_templates(), viewport));
+                        self.resources.damage.add_full();
+                        self.resources.frame_scheduler.request_redraw();
+                    }
+                    // Ctrl+O reloads the document from its file, in the same
+                    // World and window. Unsaved documents have none yet. A
+                    // file that fails to load leaves the open document as it
+                    // was, unsaved changes included.
+                    PhysicalKey::Code(KeyCode::KeyO) if ctrl_pressed => {
+                        if let Some(path) = &self.document_path {
+                            match scene::switch_scene(&mut self.world, path, &[]) {
+                                Ok(entities) => {
+                                    self.status.show(&mut self.ui, &format!("Reloaded {} ({} entities)", path.display(), entities.len()));
+                                    self.saved = save_point(&self.world);
+                                    reset_editor_state(&mut self.resources);
+                                    self.resources.damage.add_full();
+                                    self.resources.frame_scheduler.request_redraw();
+                                }
+                                Err(e) => self.status.show(&mut self.ui, &format!("Failed to reload {}: {}", path.display(), e)),
+                            }
+                        }
+                    }
+                    // F11 toggles GPU profiling and the panel its timings
+                    // show in, above the status bar.
+                    PhysicalKey::Code(KeyCode::F11) => {
+                        gpu_state.timer = match gpu_state.timer.take() {
+                            Some(_) => None,
+                            None => Some(GpuTimer::new()),
+                        };
+                        match self.gpu_timings.take() {
+                            Some(panel) => self.resources.damage.add(panel.close(&mut self.ui)),
+                            None => {
+                                let size = gpu_state.window.inner_size();
+                                let origin = Point::new(PANEL_MARGIN, size.height as f32 - status::HEIGHT - GPU_TIMINGS_HEIGHT - PANEL_MARGIN);
+                                self.gpu_timings = Some(TextPanel::open(&mut self.ui, origin, GPU_TIMINGS_WIDTH));
+                            }
+                        }
+                        self.resources.frame_scheduler.request_redraw();
+                    }
+                    // F10 cycles the quality override: automatic, full,
+                    // reduced.
+                    PhysicalKey::Code(KeyCode::F10) => {
+                        self.resources.quality.cycle_override();
+                        self.status.show(&mut self.ui, &self.resources.quality.describe());
+                        self.resources.damage.add_full();
+                        self.resources.frame_scheduler.request_redraw();
+                    }
+                    // F12 dumps the frame as an .skp and opens a panel with
+                    // what each entity draws; pressed again, it closes it.
+                    PhysicalKey::Code(KeyCode::F12) => {
+                        if let Some(panel) = self.draw_ops.take() {
+                            self.resources.damage.add(panel.close(&mut self.ui));
+                            self.resources.frame_scheduler.request_redraw();
+                            return;
+                        }
+                        let size = gpu_state.window.inner_size();
+                        let viewport = Rect::from_wh(size.width as f32, size.height as f32);
+                        match capture::capture_frame(&self.world, &self.resources.selection, viewport, self.resources.quality.level(), Path::new(CAPTURE_PATH)) {
+                            Ok(()) => self.status.show(&mut self.ui, &format!("Captured frame to {}", CAPTURE_PATH)),
+                            Err(e) => eprintln!("Failed to capture frame: {:?}", e),
+                        }
+                        let mut lines: Vec<String> = capture::entity_ops(&self.world, viewport).into_iter()
+                            .map(|ops| {
+                                let cull = ops.cull.round_out();
+                                format!("{}: {} ops, {} bytes, cull {}x{} at {},{}", ops.entity, ops.ops, ops.bytes, cull.width(), cull.height(), cull.left, cull.top)
+                            })
+                            .collect();
+                        if lines.is_empty() {
+                            lines.push("Nothing drawn".to_string());
+                        }
+                        let origin = Point::new(viewport.right - DRAW_OPS_WIDTH - PANEL_MARGIN, PANEL_MARGIN);
+                        let mut panel = TextPanel::open(&mut self.ui, origin, DRAW_OPS_WIDTH);
+                        if let Some(rect) = panel.set_lines(&mut self.ui, lines) {
+                            self.resources.damage.add(rect);
+                        }
+                        self.draw_ops = Some(panel);
+                        self.resources.frame_scheduler.request_redraw();
+                    }
+                    // Ctrl+Shift+I exports the slices reduced to the indexed
+                    // palette, which Ctrl+Alt+I cycles (with Shift, the dithering).
+                    PhysicalKey::Code(KeyCode::KeyI) if ctrl_pressed && shift_pressed && !alt_pressed => {
+                        match export::export_slices_indexed(&self.world, &self.settings.indexed_export, Path::new("export/indexed")) {
+                            Ok(paths) => self.status.show(&mut self.ui, &format!("Exported {} indexed slice images", paths.len())),
+                            Err(e) => self.status.show(&mut self.ui, &format!("Failed to export indexed slices: {}", e)),
+                        }
+                    }
+                    PhysicalKey::Code(KeyCode::KeyI) if ctrl_pressed && alt_pressed => {
+                        let indexed = &mut self.settings.indexed_export;
+                        if shift_pressed {
+                            indexed.dither = indexed.dither.next();
+                        } else {
+                            indexed.next_palette();
+                        }
+                        let message = format!("Indexed export: {}, {}", indexed.palette, indexed.dither.label());
+                        self.status.show(&mut self.ui, &message);
+                        if let Err(e) = self.settings.save(Path::new(SETTINGS_PATH)) {
+                            eprintln!("Failed to save settings: {:?}", e);
+                        }
+                    }
+                    // Ctrl+F opens the filter menu for the selection.
+                    PhysicalKey::Code(KeyCode::KeyF) if ctrl_pressed => {
+                        if self.resources.selection.entities().is_empty() {
+                            self.status.show(&mut self.ui, "Select layers to filter");
+                        } else {
+                            let size = gpu_state.window.inner_size();
+                            let viewport = Rect::from_wh(size.width as f32, size.height as f32);
+                            self.filter_menu = Some(FilterMenu::open(&mut self.ui, viewport));
+                            self.resources.damage.add_full();
+                            self.resources.frame_scheduler.request_redraw();
+                        }
+                    }
+                    // Ctrl+M steps the selection's gradient map through the
+                    // presets and then off; Ctrl+Shift+M saves a preset running
+                    // through the selection's fill colors.
+                    PhysicalKey::Code(KeyCode::KeyM) if ctrl_pressed && shift_pressed => {
+                        match adjustments::gradient_from_fills(&self.world, self.resources.selection.entities()) {
+                            Some(map) => {
+                                let name = format!("Gradient {}", self.settings.gradient_presets.len() + 1);
+                                self.settings.gradient_presets.push(GradientPreset { name: name.clone(), map });
+                                match self.settings.save(Path::new(SETTINGS_PATH)) {
+                                    Ok(()) => self.status.show(&mut self.ui, &format!("Saved gradient preset {}", name)),
+                                    Err(e) => self.status.show(&mut self.ui, &format!("Failed to save gradient preset: {}", e)),
+                                }
+                            }
+                            None => self.status.show(&mut self.ui, "Select filled shapes to make a gradient from"),
+                        }
+                    }
+                    PhysicalKey::Code(KeyCode::KeyM) if ctrl_pressed => {
+                        let selection = self.resources.selection.entities();
+                        let presets = gradient_presets(&self.settings.gradient_presets);
+                        let applied = adjustments::cycle_gradient_map(&mut self.world, selection, &presets);
+                        self.resources.damage.add_entities(&self.world, selection);
+                        if !selection.is_empty() {
+                            self.status.show(&mut self.ui, &format!("Gradient map: {}", applied.as_deref().unwrap_or("off")));
+                        }
+                        self.resources.frame_scheduler.request_redraw();
+                    }
+                    // Ctrl+I shows or hides the inspector; about_to_wait fills it.
+                    PhysicalKey::Code(KeyCode::KeyI) if ctrl_pressed => {
+                        match self.inspector.take() {
+                            Some(panel) => self.resources.damage.add(panel.close(&mut self.ui)),
+                            None => self.inspector = Some(TextPanel::open(&mut self.ui, Point::new(PANEL_MARGIN, PANEL_MARGIN), INSPECTOR_WIDTH)),
+                        }
+                        self.resources.frame_scheduler.request_redraw();
+                    }
+                    // Ctrl+U cycles the unit dimensions are shown in.
+                    PhysicalKey::Code(KeyCode::KeyU) if ctrl_pressed => {
+                        let document = self.world.document_mut();
+                        document.unit = document.unit.next();
+                        let message = format!("Units: {} at {} dpi", document.unit.suffix(), document.dpi);
+                        self.status.show(&mut self.ui, &message);
+                    }
+                    PhysicalKey::Code(KeyCode::KeyD) if ctrl_pressed => {
+                        let selection = &mut self.resources.selection;
+                        let copies: io::Result<Vec<_>> = selection.entities().to_vec().into_iter()
+                            .map(|entity| hierarchy::duplicate(&mut self.world, entity, Vector::new(DUPLICATE_OFFSET, DUPLICATE_OFFSET)))
+                            .collect();
+                        match copies {
+                            Ok(copies) if !copies.is_empty() => {
+                                self.resources.damage.add_entities(&self.world, selection.entities());
+                                selection.replace(copies);
+                                self.resources.frame_scheduler.request_redraw();
+                            }
+                            Ok(_) => {}
+                            Err(e) => self.status.show(&mut self.ui, &format!("Failed to duplicate: {}", e)),
+                        }
+                    }
+                    // Alt+arrows dock the panel under the pointer on that side.
+                    PhysicalKey::Code(code @ (KeyCode::ArrowLeft | KeyCode::ArrowRight | KeyCode::ArrowUp | KeyCode::ArrowDown)) if alt_pressed && !ctrl_pressed => {
+                        let dock = match code {
+                            KeyCode::ArrowLeft => Dock::Left,
+                            KeyCode::ArrowRight => Dock::Right,
+                            KeyCode::ArrowUp => Dock::Top,
+                            _ => Dock::Bottom,
+                        };
+                        if let Some(panel) = entity_at(&self.ui, self.resources.pointer_state.position) {
+                            if workspace::redock(&self.ui, panel, dock) {
+                                self.resources.frame_scheduler.request_redraw();
+                            }
+                        }
+                    }
+                    // Ctrl+Alt+S saves the panel layout under the active
+                    // workspace's name.
+                    PhysicalKey::Code(KeyCode::KeyS) if ctrl_pressed && alt_pressed => {
+                        let saved = Workspace { name: self.settings.workspace.clone(), panels: workspace::current_panels(&self.ui) };
+                        self.settings.workspaces.retain(|workspace| workspace.name != saved.name);
+                        self.settings.workspaces.push(saved);
+                        match self.settings.save(Path::new(SETTINGS_PATH)) {
+                            Ok(()) => self.status.show(&mut self.ui, &format!("Saved workspace {}", self.settings.workspace)),
+                            Err(e) => eprintln!("Failed to save settings: {:?}", e),
+                        }
+                    }
+                    // F1 to F9 switch workspaces: the presets, then saved ones.
+                    PhysicalKey::Code(code @ (KeyCode::F1 | KeyCode::F2 | KeyCode::F3 | KeyCode::F4 | KeyCode::F5 | KeyCode::F6 | KeyCode::F7 | KeyCode::F8 | KeyCode::F9)) => {
+                        let index = match code {
+                            KeyCode::F1 => 0,
+                            KeyCode::F2 => 1,
+                            KeyCode::F3 => 2,
+                            KeyCode::F4 => 3,
+                            KeyCode::F5 => 4,
+                            KeyCode::F6 => 5,
+                            KeyCode::F7 => 6,
+                            KeyCode::F8 => 7,
+                            _ => 8,
+                        };
+                        if let Some(chosen) = workspace::workspaces(&self.settings).into_iter().nth(index) {
+                            let size = gpu_state.window.inner_size();
+                            let viewport = Rect::from_wh(size.width as f32, size.height as f32);
+                            workspace::apply_workspace(&mut self.ui, &chosen, viewport);
+                            self.status.show(&mut self.ui, &format!("Workspace: {}", chosen.name));
+                            self.settings.workspace = chosen.name;
+                            if let Err(e) = self.settings.save(Path::new(SETTINGS_PATH)) {
+                                eprintln!("Failed to save settings: {:?}", e);
+                            }
+                            self.resources.damage.add_full();
+                            self.resources.frame_scheduler.request_redraw();
+                        }
+                    }
+                    PhysicalKey::Code(code @ (KeyCode::ArrowLeft | KeyCode::ArrowRight | KeyCode::ArrowUp | KeyCode::ArrowDown)) if !ctrl_pressed && !alt_pressed => {
+                        let step = if shift_pressed { NUDGE_LARGE } else { NUDGE_SMALL };
+                        let delta = match code {
+                            KeyCode::ArrowLeft => Vector::new(-step, 0.0),
+                            KeyCode::ArrowRight => Vector::new(step, 0.0),
+                            KeyCode::ArrowUp => Vector::new(0.0, -step),
+                            _ => Vector::new(0.0, step),
+                        };
+                        let selection = self.resources.selection.entities();
+                        self.resources.damage.add_entities(&self.world, selection);
+                        for entity in selection {
+                            align::translate(&self.world, *entity, delta);
+                        }
+                    }
+                    // Ctrl+Shift+H/L hide or lock the selection; with nothing
+                    // selected they show or unlock everything instead.
+                    PhysicalKey::Code(KeyCode::KeyH) if ctrl_pressed && shift_pressed => {
+                        let selection = &mut self.resources.selection;
+                        let entities = selection.entities().to_vec();
+                        if entities.is_empty() {
+                            self.world.clear_markers::<Hidden>();
+                            self.resources.damage.add_full();
+                        } else {
+                            self.resources.damage.add_entities(&self.world, &entities);
+                            hierarchy::toggle_marker(&mut self.world, &entities, Hidden);
+                            selection.clear();
+                        }
+                        self.resources.frame_scheduler.request_redraw();
+                    }
+                    PhysicalKey::Code(KeyCode::KeyL) if ctrl_pressed && shift_pressed => {
+                        let selection = &mut self.resources.selection;
+                        let entities = selection.entities().to_vec();
+                        if entities.is_empty() {
+                            self.world.clear_markers::<Locked>();
+                        } else {
+                            self.resources.damage.add_entities(&self.world, &entities);
+                            hierarchy::toggle_marker(&mut self.world, &entities, Locked);
+                            selection.clear();
+                            self.resources.frame_scheduler.request_redraw();
+                        }
+                    }
+                    PhysicalKey::Code(KeyCode::KeyG) if ctrl_pressed && shift_pressed => {
+                        let selection = &mut self.resources.selection;
+                        self.resources.damage.add_entities(&self.world, selection.entities());
+                        let mut children = Vec::new();
+                        for entity in selection.entities().to_vec() {
+                            children.extend(hierarchy::ungroup(&mut self.world, entity));
+                        }
+                        if !children.is_empty() {
+                            selection.replace(children);
+                        }
+                        self.resources.frame_scheduler.request_redraw();
+                    }
+                    PhysicalKey::Code(KeyCode::KeyG) if ctrl_pressed => {
+                        let selection = &mut self.resources.selection;
+                        self.resources.damage.add_entities(&self.world, selection.entities());
+                        if let Some(group) = hierarchy::group(&mut self.world, selection.entities()) {
+                            selection.set(group);
+                            self.resources.damage.add_entity(&self.world, group);
+                            self.resources.frame_scheduler.request_redraw();
+                        }
+                    }
+                    PhysicalKey::Code(KeyCode::Delete | KeyCode::Backspace) if !ctrl_pressed => {
+                        let selection = &mut self.resources.selection;
+                        self.resources.damage.add_entities(&self.world, selection.entities());
+                        for entity in selection.entities().to_vec() {
+                            self.world.despawn_recursive(entity);
+                        }
+                        selection.clear();
+                        self.resources.frame_scheduler.request_redraw();
+                    }
+                    PhysicalKey::Code(KeyCode::Escape) => {
+                        let selection = &mut self.resources.selection;
+                        self.resources.damage.add_entities(&self.world, selection.entities());
+                        match selection.scope.take() {
+                            Some(group) => selection.set(group),
+                            None => selection.clear(),
+                        }
+                        self.resources.frame_scheduler.request_redraw();
+                    }
+                    // Alt+L/H/R and Alt+T/V/B align the selection, Alt+Shift+H/V
+                    // distributes it. Old positions are damaged here, new ones
+                    // show up as changed Quads.
+                    PhysicalKey::Code(code) if alt_pressed && !ctrl_pressed => {
+                        let selection = self.resources.selection.entities();
+                        self.resources.damage.add_entities(&self.world, selection);
+                        match (code, shift_pressed) {
+                            (KeyCode::KeyH, true) => align::distribute(&self.world, selection, Axis::Horizontal),
+                            (KeyCode::KeyV, true) => align::distribute(&self.world, selection, Axis::Vertical),
+                            (KeyCode::KeyL, false) => align::align(&self.world, selection, Align::Left),
+                            (KeyCode::KeyH, false) => align::align(&self.world, selection, Align::Center),
+                            (KeyCode::KeyR, false) => align::align(&self.world, selection, Align::Right),
+                            (KeyCode::KeyT, false) => align::align(&self.world, selection, Align::Top),
+                            (KeyCode::KeyV, false) => align::align(&self.world, selection, Align::Middle),
+                            (KeyCode::KeyB, false) => align::align(&self.world, selection, Align::Bottom),
+                            _ => {}
+                        }
+                    }
+                    _ => {}
+                }
+                match before {
+                    Some(Ok(before)) => {
+                        if let Err(e) = self.resources.undo.commit(before, &self.world) {
+                            eprintln!("Failed to record undo step: {:?}", e);
+                        }
+                    }
+                    Some(Err(e)) => eprintln!("Failed to capture undo step: {:?}", e),
+                    None => {}
+                }
+            }
+            // An image file dropped on the window becomes a sprite at its
+            // own size, with its corner under the pointer.
+            WindowEvent::DroppedFile(path) => {
+                if self.dialog.is_some() || self.filter_menu.is_some() || self.start.is_some() {
+                    return;
+                }
+                let before = WorldSnapshot::capture(&self.world);
+                // Copied next to the document, so the saved scene doesn't
+                // point at wherever the file was dropped from.
+                self.resources.assets.set_root(document_dir(self.document_path.as_deref()));
+                let path = match import_image(self.resources.assets.root(), &path) {
+                    Ok(path) => path,
+                    Err(e) => {
+                        eprintln!("Failed to import image {}: {:?}", path.display(), e);
+                        return;
+                    }
+                };
+                let image = match self.resources.assets.load_image(&path, Some(&mut gpu_state.gr_context)) {
+                    Ok(image) => image,
+                    Err(e) => {
+                        eprintln!("Failed to load image {}: {:?}", path.display(), e);
+                        return;
+                    }
+                };
+                let Some((width, height)) = self.resources.assets.image(&image).map(|loaded| (loaded.image.width() as f32, loaded.image.height() as f32)) else { return; };
+                let rect = Rect::from_point_and_size(self.resources.pointer_state.document_position(), (width, height));
+                let entity = self.world.spawn();
+                self.world.insert(entity, Bounds { rect });
+                self.world.insert(entity, Transform { local_to_parent: Matrix::new_identity(), z: 0.0 });
+                self.world.insert(entity, Sprite { image, src_rect: None, dst_rect: rect });
+                self.resources.damage.add_entities(&self.world, self.resources.selection.entities());
+                self.resources.selection.set(entity);
+                match before {
+                    Ok(before) => {
+                        if let Err(e) = self.resources.undo.commit(before, &self.world) {
+                            eprintln!("Failed to record undo step: {:?}", e);
+                        }
+                    }
+                    Err(e) => eprintln!("Failed to capture undo step: {:?}", e),
+                }
             }
             WindowEvent::ModifiersChanged(modifiers) => {
+                self.resources.keyboard_state.modifiers = modifiers;
             }
             _ => {}
         }
     }
     
+    fn user_event(&mut self, _event_loop: &ActiveEventLoop, event: Wakeup) {
+        match event {
+            // about_to_wait runs right after, which is all a tick needs.
+            Wakeup::Tick => {}
+            Wakeup::Redraw => {
+                self.resources.damage.add_full();
+                self.resources.frame_scheduler.request_redraw();
+            }
+        }
+    }
+
+    // Pointer events can arrive far faster than frames, so per-frame systems
+    // run here once after the event batch instead of on every CursorMoved.
+    // A redraw is only requested when some system actually changed something,
+    // so a static scene sleeps in ControlFlow::Wait until input, a Waker or
+    // the next WakeSchedule deadline.
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        let Some(gpu_state) = &self.resources.gpu_state else { return; };
+        let size = gpu_state.window.inner_size();
+        let viewport = Rect::from_wh(size.width as f32, size.height as f32);
+
+        self.resources.assets.set_root(document_dir(self.document_path.as_deref()));
+        update_document(&mut self.world, &mut self.resources, viewport);
+        if self.resources.pointer_state.moved() {
+            // The UI world is already in window space.
+            let hovered = hover_detect(&self.ui, self.resources.pointer_state.position);
+            hover_update(&mut self.ui, &hovered, &mut self.resources.interactions);
+        }
+        self.resources.pointer_state.end_frame();
+        let Some(gpu_state) = &self.resources.gpu_state else { return; };
+        for event in self.interaction_reader.read(&self.resources.interactions) {
+            match event {
+                InteractionEvent::HoverEntered(_) => gpu_state.window.set_cursor(CursorIcon::Pointer),
+                InteractionEvent::HoverExited(_) => gpu_state.window.set_cursor(CursorIcon::Default),
+                InteractionEvent::Clicked(_) => {}
+            }
+        }
+        self.resources.interactions.update();
+
+        if let Some(panel) = &mut self.inspector {
+            if let Some(rect) = panel.set_lines(&mut self.ui, inspector_lines(&self.world, &self.resources.selection)) {
+                self.resources.damage.add(rect);
+                self.resources.frame_scheduler.request_redraw();
+            }
+        }
+        self.ui.flush();
+        for rect in dock_system(&self.ui, viewport) {
+            self.resources.damage.add(rect);
+        }
+        for rect in constraint_system(&self.ui, viewport) {
+            self.resources.damage.add(rect);
+        }
+        update_group_bounds(&self.ui);
+        text_layout_system(&mut self.ui, &mut self.resources.fonts);
+        if let Some(atlas) = &self.resources.atlas {
+            atlas_icon_system(&mut self.ui, atlas);
+        }
+        let changed = changed_visuals(&self.ui);
+        self.resources.damage.add_entities(&self.ui, &changed);
+        if !changed.is_empty() { self.resources.frame_scheduler.request_redraw(); }
+        self.ui.clear_trackers();
+
+        if self.resources.frame_scheduler.take_redraw() {
+            gpu_state.window.request_redraw();
+        }
+        self.resources.wake_schedule.take_due(Instant::now());
+        let next = [self.resources.wake_schedule.next(), self.resources.timers.next()].into_iter().flatten().min();
+        match next {
+            Some(deadline) => event_loop.set_control_flow(ControlFlow::WaitUntil(deadline)),
+            None => event_loop.set_control_flow(ControlFlow::Wait),
+        }
+    }
+
     // Handle window destruction for cleanup (though not strictly necessary 
     // for this simple example as the fields are Option)
     fn exiting(&mut self, _event_loop: &ActiveEventLoop) {
@@ -128,48 +1003,99 @@ impl winit::application::ApplicationHandler<()> for App {
     }
 }
 
-fn render_system(world: &World, canvas: &Canvas) {
-    canvas.clear(Color::from_rgb(200, 200, 200));
-    render_quads(world, canvas);
+// Presents only the damaged region where the platform supports it. GL damage
+// rects have a bottom-left origin, unlike skia's canvas coordinates.
+fn present(gpu_state: &GpuState, damage: Option<Rect>) {
+    if let Some(rect) = damage {
+        let height = gpu_state.window.inner_size().height as i32;
+        let rect = rect.round_out();
+        let gl_rect = glutin::surface::Rect::new(rect.left, height - rect.bottom, rect.width(), rect.height());
+        if gpu_state.gl_surface.swap_buffers_with_damage(&gpu_state.gl_context, &[gl_rect]).is_ok() {
+            return;
+        }
+    }
+    gpu_state.gl_surface.swap_buffers(&gpu_state.gl_context).unwrap();
 }
 
-fn hover_system(world: &mut World, x: f32, y: f32) -> bool {
-    let hovered = hover_detect(world, x, y);
-    hover_update(world, &hovered);
-    hovered.len() > 0
+// Takes the fields rather than the App, which window_event has partly
+// borrowed through gpu_state.
+fn has_unsaved_changes(saved: Option<&SavePoint>, world: &World) -> bool {
+    !saved.is_some_and(|saved| saved.matches(world))
 }
 
-fn hover_detect(world: &World, x: f32, y: f32) -> Vec<Entity> {
-    let mut results = Vec::new();
-    world.query2::<Bounds, Interactable, _>(|entity, bounds, _| {
-            let rect = bounds.rect;
+fn save_point(world: &World) -> Option<SavePoint> {
+    SavePoint::capture(world).map_err(|e| eprintln!("Failed to capture save point: {:?}", e)).ok()
+}
 
-            let hovered =
-                x >= rect.left() && x <= rect.right() &&
-                y >= rect.top()  && y <= rect.bottom();
+// Where the document's images live; untitled ones are saved to the working
+// directory.
+fn document_dir(path: Option<&Path>) -> &Path {
+    path.and_then(Path::parent).filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."))
+}
 
-            if hovered { results.push(entity) }
-    });
-    results
+fn remember_recent(recent: &mut RecentFiles, path: &Path) {
+    recent.push(path);
+    if let Err(e) = recent.save(Path::new(RECENT_PATH)) {
+        eprintln!("Failed to save recent files: {:?}", e);
+    }
+}
+
+fn is_modifier(code: KeyCode) -> bool {
+    matches!(code,
+        KeyCode::ControlLeft | KeyCode::ControlRight | KeyCode::ShiftLeft | KeyCode::ShiftRight
+        | KeyCode::AltLeft | KeyCode::AltRight | KeyCode::SuperLeft | KeyCode::SuperRight)
 }
 
-fn hover_update(world: &mut World, hovered: &[Entity]) {
-    let mut interactable_storage = world.storage_mut::<Interactable>().unwrap();
-    for entity in hovered {
-        println!("{}", entity.0);
-        let interactable = Some(interactable_storage.data.get_mut(entity).unwrap());
-        interactable.unwrap().state = InteractableState::HOVERED;
+// After switching documents; nothing from the old one carries over.
+fn reset_editor_state(resources: &mut Resources) {
+    resources.selection = Selection::new();
+    resources.rubber_band = None;
+    resources.undo = UndoHistory::new();
+}
+
+// Keys the keyboard handler treats as document edits, which get an undo
+// step. Everything else (exports, view and panel changes, bare modifiers,
+// Escape) skips the snapshot.
+fn edits_document(key: PhysicalKey, ctrl: bool, shift: bool, alt: bool) -> bool {
+    let PhysicalKey::Code(code) = key else { return false; };
+    match code {
+        // Gradient map; with Shift it only saves a preset.
+        KeyCode::KeyM => ctrl && !shift,
+        // Duplicate, paste, group and ungroup.
+        KeyCode::KeyD | KeyCode::KeyG => ctrl,
+        KeyCode::KeyV if ctrl => true,
+        // Hide and lock.
+        KeyCode::KeyH | KeyCode::KeyL if ctrl => shift,
+        // Align and distribute.
+        KeyCode::KeyL | KeyCode::KeyH | KeyCode::KeyR | KeyCode::KeyT | KeyCode::KeyV | KeyCode::KeyB => alt && !ctrl,
+        // Nudge.
+        KeyCode::ArrowLeft | KeyCode::ArrowRight | KeyCode::ArrowUp | KeyCode::ArrowDown => !ctrl && !alt,
+        KeyCode::Delete | KeyCode::Backspace => !ctrl,
+        _ => false,
     }
-    let mut quad_storage = world.storage_mut::<Quad>().unwrap();
-    for entity in hovered {
-        let quad = quad_storage.data.get_mut(entity).unwrap();
-        quad.color = InteractableState::HOVERED.color();
+}
+
+// Presets first, then the user's own.
+fn new_document_templates() -> Vec<Template> {
+    let mut templates = templates::presets();
+    match templates::load_user_templates(Path::new(TEMPLATES_PATH)) {
+        Ok(user) => templates.extend(user),
+        Err(e) => eprintln!("Failed to load templates: {:?}", e),
     }
+    templates
+}
+
+fn new_world() -> World {
+    let mut world = World::new();
+    match button_prefab() {
+        Ok(prefab) => world.register_prefab("button", prefab),
+        Err(e) => eprintln!("Failed to build button prefab: {:?}", e),
+    }
+    world
 }
 
 fn main() -> Result<(), EventLoopError> {
-    let initial_button_rect = Rect::from_xywh(30.0, 30.0, 30.0, 30.0);
-    let event_loop = EventLoop::new().unwrap();
+    let event_loop = EventLoop::<Wakeup>::with_user_event().build().unwrap();
     
     let initial_attrs = WindowAttributes::default()
         .with_title("gamer")
@@ -233,22 +1159,76 @@ fn main() -> Result<(), EventLoopError> {
         gr_context,
         skia_surface: None,
         window: window.clone(),
+        timer: None,
     };
 
-    let mut world = World::new();
-    let button_entity = world.spawn();
-    world.insert(button_entity, Bounds { rect: initial_button_rect });
-    world.insert(button_entity, Quad { color: InteractableState::DEFAULT.color(), rect: initial_button_rect } );
-    world.insert(button_entity, Interactable { state: InteractableState::DEFAULT } );
-    world.insert(button_entity, Transform { local_to_parent: Matrix::new_identity(), z: 0.0 } );
-    println!("{}", button_entity.0);
+    let settings = Settings::load(Path::new(SETTINGS_PATH)).unwrap_or_else(|e| {
+        eprintln!("Failed to load settings: {:?}", e);
+        Settings::default()
+    });
+    let mut recent = RecentFiles::load(Path::new(RECENT_PATH)).unwrap_or_else(|e| {
+        eprintln!("Failed to load recent files: {:?}", e);
+        RecentFiles::default()
+    });
+    // Reopen the last document; without one, show the start screen.
+    let scene_path = recent.existing().next().map_or_else(|| PathBuf::from(SCENE_PATH), Path::to_path_buf);
+    let mut world = new_world();
+    let loaded = scene_path.exists() && match scene::load_scene(&mut world, &scene_path) {
+        Ok(_) => true,
+        Err(e) => {
+            eprintln!("Failed to load scene: {:?}", e);
+            world = new_world();
+            false
+        }
+    };
 
-    let resources = Resources::new(gpu_state);
+    let mut resources = Resources::new(gpu_state, Waker::new(event_loop.create_proxy()));
+    let icons_dir = Path::new(ICONS_DIR);
+    if icons_dir.exists() {
+        match TextureAtlas::load_dir(icons_dir, ATLAS_PAGE_SIZE) {
+            Ok(atlas) => resources.atlas = Some(atlas),
+            Err(e) => eprintln!("Failed to load icons: {:?}", e),
+        }
+    }
 
+    let size = window.inner_size();
+    let viewport = Rect::from_wh(size.width as f32, size.height as f32);
+    let mut ui = World::new();
+    let status = StatusBar::open(&mut ui, viewport);
+    let interaction_reader = resources.interactions.reader();
+    if loaded {
+        remember_recent(&mut recent, &scene_path);
+    }
+    let saved = save_point(&world);
     let mut app = App {
         world,
+        ui,
+        dialog: None,
+        filter_menu: None,
+        start: None,
+        status,
+        inspector: None,
+        draw_ops: None,
+        gpu_timings: None,
+        gpu_timings_shown: Instant::now(),
+        recent,
+        document_path: loaded.then_some(scene_path),
+        saved,
+        pending_discard: None,
+        settings,
         resources,
+        interaction_reader,
     };
+    // The saved workspace, or the first one.
+    let workspaces = workspace::workspaces(&app.settings);
+    let active = workspaces.iter().find(|workspace| workspace.name == app.settings.workspace).or(workspaces.first());
+    if let Some(active) = active {
+        workspace::apply_workspace(&mut app.ui, active, viewport);
+        app.settings.workspace = active.name.clone();
+    }
+    if !loaded {
+        app.start = Some(StartScreen::open(&mut app.ui, &app.recent, new_world, &mut app.resources.fonts, viewport));
+    }
 
     let mut canvas_history = Vec::new();
     canvas_history.push(canvas_skia_surface.image_snapshot());
@@ -0,0 +1,43 @@
+#[derive(Clone, Copy)]
+pub struct PackedRect {
+    pub index: usize,
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+pub struct Packing {
+    pub width: i32,
+    pub height: i32,
+    pub rects: Vec<PackedRect>,
+}
+
+// Shelf packing, tallest first: good enough for sprite sheets where most
+// entries have similar heights. `rects` come back in input order.
+pub fn pack(sizes: &[(i32, i32)], max_width: i32, padding: i32) -> Packing {
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by(|a, b| sizes[*b].1.cmp(&sizes[*a].1));
+
+    let mut rects = Vec::with_capacity(sizes.len());
+    let mut shelf_x = padding;
+    let mut shelf_y = padding;
+    let mut shelf_height = 0;
+    let mut width = 0;
+
+    for index in order {
+        let (w, h) = sizes[index];
+        if shelf_x > padding && shelf_x + w + padding > max_width {
+            shelf_y += shelf_height + padding;
+            shelf_x = padding;
+            shelf_height = 0;
+        }
+        rects.push(PackedRect { index, x: shelf_x, y: shelf_y, width: w, height: h });
+        shelf_x += w + padding;
+        shelf_height = shelf_height.max(h);
+        width = width.max(shelf_x);
+    }
+
+    rects.sort_by_key(|rect| rect.index);
+    Packing { width, height: shelf_y + shelf_height + padding, rects }
+}
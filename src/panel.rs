@@ -0,0 +1,130 @@
+use skia_safe::{Color4f, Matrix, Point, Rect};
+
+use crate::ecs::{BlocksInput, Bounds, Entity, InteractionPriority, Quad, Selection, TextAlign, Transform, World, ZIndex};
+use crate::text::spawn_label;
+
+// Above the status bar, under dialogs and the start screen.
+const PANEL_PRIORITY: i32 = 700;
+const PANEL_Z: i32 = 700;
+const LINE_HEIGHT: f32 = 16.0;
+const TEXT_SIZE: f32 = 12.0;
+const PADDING: f32 = 8.0;
+// Longer lists end in a line saying how many were left out.
+const MAX_LINES: usize = 40;
+
+// A read-only box of text lines in the UI world, for diagnostics such as
+// the inspector. Each line is a left-aligned label over a backdrop that
+// grows down from `origin` to fit them.
+pub struct TextPanel {
+    origin: Point,
+    width: f32,
+    backdrop: Entity,
+    labels: Vec<Entity>,
+    lines: Vec<String>,
+}
+
+impl TextPanel {
+    pub fn open(ui: &mut World, origin: Point, width: f32) -> Self {
+        let rect = Rect::from_xywh(origin.x, origin.y, width, PADDING * 2.0);
+        let backdrop = ui.spawn();
+        ui.insert(backdrop, Bounds { rect });
+        ui.insert(backdrop, Quad { rect, color: Color4f::new(0.97, 0.97, 0.97, 0.95) });
+        ui.insert(backdrop, Transform { local_to_parent: Matrix::new_identity(), z: 0.0 });
+        ui.insert(backdrop, ZIndex(PANEL_Z));
+        ui.insert(backdrop, BlocksInput);
+        ui.insert(backdrop, InteractionPriority(PANEL_PRIORITY));
+        TextPanel { origin, width, backdrop, labels: Vec::new(), lines: Vec::new() }
+    }
+
+    fn rect(&self) -> Rect {
+        let height = self.labels.len() as f32 * LINE_HEIGHT + PADDING * 2.0;
+        Rect::from_xywh(self.origin.x, self.origin.y, self.width, height)
+    }
+
+    // Shows `lines` in place of the current ones. Returns the area that
+    // needs repainting, or None if they were already shown.
+    pub fn set_lines(&mut self, ui: &mut World, mut lines: Vec<String>) -> Option<Rect> {
+        if lines == self.lines {
+            return None;
+        }
+        let before = self.rect();
+        for label in self.labels.drain(..) {
+            ui.despawn(label);
+        }
+        self.lines = lines.clone();
+        if lines.len() > MAX_LINES {
+            let hidden = lines.len() - (MAX_LINES - 1);
+            lines.truncate(MAX_LINES - 1);
+            lines.push(format!("... {} more", hidden));
+        }
+        for (index, line) in lines.iter().enumerate() {
+            let top = self.origin.y + PADDING + index as f32 * LINE_HEIGHT;
+            let rect = Rect::from_xywh(self.origin.x + PADDING, top, self.width - PADDING * 2.0, LINE_HEIGHT);
+            let label = spawn_label(ui, rect, line, TEXT_SIZE, PANEL_Z + 1);
+            ui.insert(label, TextAlign::Left);
+            self.labels.push(label);
+        }
+        let rect = self.rect();
+        ui.insert(self.backdrop, Bounds { rect });
+        ui.insert(self.backdrop, Quad { rect, color: Color4f::new(0.97, 0.97, 0.97, 0.95) });
+        let mut damage = before;
+        damage.join(rect);
+        Some(damage)
+    }
+
+    // Returns the area the panel covered.
+    pub fn close(self, ui: &mut World) -> Rect {
+        let rect = self.rect();
+        for entity in self.labels.into_iter().chain([self.backdrop]) {
+            ui.despawn(entity);
+        }
+        rect
+    }
+}
+
+// What Ctrl+I shows: each selected entity with its size in document units
+// and its components.
+pub fn inspector_lines(world: &World, selection: &Selection) -> Vec<String> {
+    let document = world.document();
+    let bounds = world.storage::<Bounds>();
+    let mut lines = Vec::new();
+    for entity in selection.entities() {
+        lines.push(entity.to_string());
+        if let Some(bounds) = bounds.as_ref().and_then(|storage| storage.get(*entity)) {
+            lines.push(format!("  size: {} x {}", document.format(bounds.rect.width()), document.format(bounds.rect.height())));
+        }
+        for (name, value) in world.registry().inspect(world, *entity) {
+            lines.push(format!("  {}: {}", name, value));
+        }
+    }
+    if lines.is_empty() {
+        lines.push("Nothing selected".to_string());
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn label_count(ui: &World) -> usize {
+        ui.query::<TextAlign>().iter().count()
+    }
+
+    #[test]
+    fn lines_replace_and_cap() {
+        let mut ui = World::new();
+        let mut panel = TextPanel::open(&mut ui, Point::new(10.0, 10.0), 200.0);
+        assert!(panel.set_lines(&mut ui, vec!["a".to_string(), "b".to_string()]).is_some());
+        assert_eq!(label_count(&ui), 2);
+        assert!(panel.set_lines(&mut ui, vec!["a".to_string(), "b".to_string()]).is_none());
+
+        let many: Vec<String> = (0..100).map(|index| index.to_string()).collect();
+        panel.set_lines(&mut ui, many);
+        assert_eq!(label_count(&ui), MAX_LINES);
+        let rect = panel.close(&mut ui);
+        assert_eq!(rect.height(), MAX_LINES as f32 * LINE_HEIGHT + PADDING * 2.0);
+        ui.flush();
+        assert_eq!(label_count(&ui), 0);
+    }
+}
@@ -0,0 +1,83 @@
+use std::{collections::BTreeMap, io};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::ecs::{Entity, World};
+
+// A named set of components in the same form scenes store them, keyed by
+// registered component name.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Prefab {
+    pub components: BTreeMap<String, Value>,
+}
+
+impl Prefab {
+    pub fn new() -> Self {
+        Prefab { components: BTreeMap::new() }
+    }
+
+    pub fn insert<T: Serialize>(&mut self, name: &str, component: &T) -> io::Result<()> {
+        self.components.insert(name.to_string(), serde_json::to_value(component)?);
+        Ok(())
+    }
+
+    pub fn with<T: Serialize>(mut self, name: &str, component: &T) -> io::Result<Self> {
+        self.insert(name, component)?;
+        Ok(self)
+    }
+
+    // Overrides are merged field by field, so `{"Slice": {"name": "ok"}}`
+    // keeps the rest of the prefab's Slice. A null override drops the
+    // component from the instance.
+    pub fn merged(&self, overrides: &BTreeMap<String, Value>) -> BTreeMap<String, Value> {
+        let mut components = self.components.clone();
+        for (name, value) in overrides {
+            if value.is_null() {
+                components.remove(name);
+                continue;
+            }
+            match components.get_mut(name) {
+                Some(base) => merge(base, value),
+                None => { components.insert(name.clone(), value.clone()); }
+            }
+        }
+        components
+    }
+}
+
+fn merge(base: &mut Value, value: &Value) {
+    match (base, value) {
+        (Value::Object(base), Value::Object(value)) => {
+            for (key, value) in value {
+                match base.get_mut(key) {
+                    Some(existing) => merge(existing, value),
+                    None => { base.insert(key.clone(), value.clone()); }
+                }
+            }
+        }
+        (base, value) => *base = value.clone(),
+    }
+}
+
+impl World {
+    pub fn register_prefab(&mut self, name: impl Into<String>, prefab: Prefab) {
+        self.prefabs_mut().insert(name.into(), prefab);
+    }
+
+    pub fn prefab(&self, name: &str) -> Option<&Prefab> {
+        self.prefabs().get(name)
+    }
+
+    pub fn instantiate(&mut self, name: &str, overrides: &BTreeMap<String, Value>) -> io::Result<Entity> {
+        let prefab = self.prefab(name)
+            .ok_or_else(|| io::Error::other(format!("unknown prefab {}", name)))?;
+        let components = prefab.merged(overrides);
+        let entity = self.spawn();
+        if let Err(e) = self.insert_components(entity, components) {
+            self.despawn(entity);
+            return Err(e);
+        }
+        Ok(entity)
+    }
+}
@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+// Consecutive frames over budget before effects are cut back.
+const SLOW_FRAMES: u32 = 8;
+// Consecutive frames with headroom before they come back; much longer than
+// SLOW_FRAMES so the level doesn't flip back and forth near the budget.
+const FAST_FRAMES: u32 = 60;
+// Fraction of the budget a frame has to stay under to count as headroom.
+const HEADROOM: f32 = 0.6;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Quality {
+    Full,
+    // Outlines are drawn as plain strokes instead of through morphology
+    // filters.
+    Reduced,
+}
+
+// Picks the draw quality from recent frame times. `forced` overrides it
+// for users who want one level regardless of load.
+pub struct QualityScaler {
+    pub forced: Option<Quality>,
+    auto: Quality,
+    slow_frames: u32,
+    fast_frames: u32,
+}
+
+impl QualityScaler {
+    pub fn new() -> Self {
+        QualityScaler { forced: None, auto: Quality::Full, slow_frames: 0, fast_frames: 0 }
+    }
+
+    pub fn level(&self) -> Quality {
+        self.forced.unwrap_or(self.auto)
+    }
+
+    // Feeds one frame's time against the frame budget. Returns true when the
+    // level changed, so the caller can repaint everything at the new one.
+    pub fn record(&mut self, frame_time: Duration, budget: Duration) -> bool {
+        let before = self.level();
+        if frame_time > budget {
+            self.slow_frames += 1;
+            self.fast_frames = 0;
+        } else if frame_time.as_secs_f32() < budget.as_secs_f32() * HEADROOM {
+            self.fast_frames += 1;
+            self.slow_frames = 0;
+        } else {
+            self.slow_frames = 0;
+            self.fast_frames = 0;
+        }
+        if self.slow_frames >= SLOW_FRAMES {
+            self.auto = Quality::Reduced;
+            self.slow_frames = 0;
+        } else if self.fast_frames >= FAST_FRAMES {
+            self.auto = Quality::Full;
+            self.fast_frames = 0;
+        }
+        self.level() != before
+    }
+
+    // For the status bar, e.g. "Quality: reduced (automatic)".
+    pub fn describe(&self) -> String {
+        let level = match self.level() {
+            Quality::Full => "full",
+            Quality::Reduced => "reduced",
+        };
+        let mode = if self.forced.is_some() { "forced" } else { "automatic" };
+        format!("Quality: {} ({})", level, mode)
+    }
+
+    // Automatic, then forced full, then forced reduced.
+    pub fn cycle_override(&mut self) {
+        self.forced = match self.forced {
+            None => Some(Quality::Full),
+            Some(Quality::Full) => Some(Quality::Reduced),
+            Some(Quality::Reduced) => None,
+        };
+    }
+}
@@ -0,0 +1,207 @@
+use std::{any::TypeId, collections::HashMap, fmt::Debug, io};
+
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::Value;
+
+use crate::ecs::{Adjustment, BlocksInput, Bounds, Children, Component, Constraint, DirtyVisual, Entity, Hidden, Icon, Interactable, InteractionPriority, Locked, Name, Opacity, Outline, Parallax, Parent, Quad, Shape, Slice, Sprite, Text, TextAlign, Transform, World, ZIndex};
+use crate::graphics::ParallaxOffset;
+
+// Components that hold Entity handles, which have to be rewritten when a
+// scene is loaded into fresh entities.
+pub trait MapEntities {
+    fn map_entities(&mut self, map: &HashMap<Entity, Entity>);
+}
+
+#[derive(Clone, Copy)]
+pub struct ComponentInfo {
+    pub name: &'static str,
+    pub type_id: TypeId,
+    debug: fn(&World, Entity) -> Option<String>,
+    remove: fn(&mut World, Entity),
+    serialize: Option<fn(&World, Entity) -> Option<io::Result<Value>>>,
+    deserialize: Option<fn(&mut World, Entity, Value) -> io::Result<()>>,
+    map_entities: Option<fn(&World, Entity, &HashMap<Entity, Entity>)>,
+}
+
+// Names, debug printing and serde hooks for component types, so code that
+// only has a World (scene files, the inspector, scripting) can enumerate
+// its components. Types registered without serde hooks are known by name
+// but never saved.
+pub struct ComponentRegistry {
+    components: Vec<ComponentInfo>,
+}
+
+fn debug_component<T: Component + Debug>(world: &World, entity: Entity) -> Option<String> {
+    let storage = world.storage::<T>()?;
+    storage.get(entity).map(|component| format!("{:?}", component))
+}
+
+fn remove_component<T: Component>(world: &mut World, entity: Entity) {
+    world.remove::<T>(entity);
+}
+
+fn serialize_component<T: Component + Serialize>(world: &World, entity: Entity) -> Option<io::Result<Value>> {
+    let storage = world.storage::<T>()?;
+    let component = storage.get(entity)?;
+    Some(serde_json::to_value(component).map_err(io::Error::from))
+}
+
+fn deserialize_component<T: Component + DeserializeOwned>(world: &mut World, entity: Entity, value: Value) -> io::Result<()> {
+    let component: T = serde_json::from_value(value)?;
+    world.insert(entity, component);
+    Ok(())
+}
+
+fn map_component_entities<T: Component + MapEntities>(world: &World, entity: Entity, map: &HashMap<Entity, Entity>) {
+    if let Some(mut component) = world.storage_mut::<T>().as_mut().and_then(|storage| storage.get_mut(entity)) {
+        component.map_entities(map);
+    }
+}
+
+impl ComponentInfo {
+    // None if `entity` doesn't have the component.
+    pub fn debug(&self, world: &World, entity: Entity) -> Option<String> {
+        (self.debug)(world, entity)
+    }
+
+    pub fn remove(&self, world: &mut World, entity: Entity) {
+        (self.remove)(world, entity);
+    }
+
+    pub fn is_serializable(&self) -> bool {
+        self.serialize.is_some()
+    }
+
+    pub fn serialize(&self, world: &World, entity: Entity) -> Option<io::Result<Value>> {
+        (self.serialize?)(world, entity)
+    }
+
+    pub fn deserialize(&self, world: &mut World, entity: Entity, value: Value) -> io::Result<()> {
+        let deserialize = self.deserialize
+            .ok_or_else(|| io::Error::other(format!("component {} is not serializable", self.name)))?;
+        deserialize(world, entity, value)
+    }
+
+    pub fn map_entities(&self, world: &World, entity: Entity, map: &HashMap<Entity, Entity>) {
+        if let Some(map_entities) = self.map_entities {
+            map_entities(world, entity, map);
+        }
+    }
+}
+
+impl ComponentRegistry {
+    pub fn new() -> Self {
+        ComponentRegistry { components: Vec::new() }
+    }
+
+    // Every component this crate defines.
+    pub fn with_builtin() -> Self {
+        let mut registry = ComponentRegistry::new();
+        registry.register_serde::<Bounds>("Bounds");
+        registry.register_serde::<Quad>("Quad");
+        registry.register_serde::<Transform>("Transform");
+        registry.register_serde::<Outline>("Outline");
+        registry.register_serde::<Slice>("Slice");
+        registry.register_serde::<Interactable>("Interactable");
+        registry.register_serde::<Parallax>("Parallax");
+        registry.register_serde::<Constraint>("Constraint");
+        registry.register_serde::<Locked>("Locked");
+        registry.register_serde::<Hidden>("Hidden");
+        registry.register_serde::<Name>("Name");
+        registry.register_serde::<Opacity>("Opacity");
+        registry.register_serde::<Adjustment>("Adjustment");
+        registry.register_serde::<Shape>("Shape");
+        registry.register_serde::<InteractionPriority>("InteractionPriority");
+        registry.register_serde::<BlocksInput>("BlocksInput");
+        registry.register_serde::<ZIndex>("ZIndex");
+        registry.register_serde::<Icon>("Icon");
+        registry.register_serde::<Text>("Text");
+        registry.register_serde::<TextAlign>("TextAlign");
+        registry.register_serde::<Sprite>("Sprite");
+        registry.register_entity_refs::<Parent>("Parent");
+        registry.register_entity_refs::<Children>("Children");
+        // Per-frame state, not part of a saved scene.
+        registry.register::<DirtyVisual>("DirtyVisual");
+        registry.register::<ParallaxOffset>("ParallaxOffset");
+        registry
+    }
+
+    fn add(&mut self, info: ComponentInfo) {
+        self.components.retain(|existing| existing.type_id != info.type_id && existing.name != info.name);
+        self.components.push(info);
+    }
+
+    pub fn register<T: Component + Debug>(&mut self, name: &'static str) {
+        self.add(ComponentInfo {
+            name,
+            type_id: TypeId::of::<T>(),
+            debug: debug_component::<T>,
+            remove: remove_component::<T>,
+            serialize: None,
+            deserialize: None,
+            map_entities: None,
+        });
+    }
+
+    pub fn register_serde<T: Component + Debug + Serialize + DeserializeOwned>(&mut self, name: &'static str) {
+        self.add(ComponentInfo {
+            name,
+            type_id: TypeId::of::<T>(),
+            debug: debug_component::<T>,
+            remove: remove_component::<T>,
+            serialize: Some(serialize_component::<T>),
+            deserialize: Some(deserialize_component::<T>),
+            map_entities: None,
+        });
+    }
+
+    pub fn register_entity_refs<T: Component + Debug + Serialize + DeserializeOwned + MapEntities>(&mut self, name: &'static str) {
+        self.add(ComponentInfo {
+            name,
+            type_id: TypeId::of::<T>(),
+            debug: debug_component::<T>,
+            remove: remove_component::<T>,
+            serialize: Some(serialize_component::<T>),
+            deserialize: Some(deserialize_component::<T>),
+            map_entities: Some(map_component_entities::<T>),
+        });
+    }
+
+    pub fn get(&self, type_id: TypeId) -> Option<&ComponentInfo> {
+        self.components.iter().find(|info| info.type_id == type_id)
+    }
+
+    pub fn get_by_name(&self, name: &str) -> Option<&ComponentInfo> {
+        self.components.iter().find(|info| info.name == name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ComponentInfo> {
+        self.components.iter()
+    }
+
+    // Every registered component on `entity`, debug printed, in
+    // registration order. Unregistered components don't show up.
+    pub fn inspect(&self, world: &World, entity: Entity) -> Vec<(&'static str, String)> {
+        self.components.iter()
+            .filter_map(|info| info.debug(world, entity).map(|value| (info.name, value)))
+            .collect()
+    }
+}
+
+impl MapEntities for Parent {
+    fn map_entities(&mut self, map: &HashMap<Entity, Entity>) {
+        if let Some(entity) = map.get(&self.0) {
+            self.0 = *entity;
+        }
+    }
+}
+
+impl MapEntities for Children {
+    fn map_entities(&mut self, map: &HashMap<Entity, Entity>) {
+        for child in &mut self.0 {
+            if let Some(entity) = map.get(child) {
+                *child = *entity;
+            }
+        }
+    }
+}
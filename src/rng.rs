@@ -0,0 +1,46 @@
+// SplitMix64: tiny, fast and fully determined by its seed, which is all the
+// effects need. Not suitable for anything security related.
+#[derive(Clone)]
+pub struct Rng {
+    seed: u64,
+    state: u64,
+}
+
+pub const DEFAULT_SEED: u64 = 0x5EED_CAFE_F00D_D00D;
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng { seed, state: seed }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn reseed(&mut self, seed: u64) {
+        *self = Rng::new(seed);
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in [0, 1).
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+
+    // Independent stream for one consumer (a particle emitter, a stroke, a
+    // filter pass), so adding draws in one place doesn't shift every other.
+    pub fn fork(&mut self, stream: u64) -> Rng {
+        Rng::new(self.next_u64() ^ stream.wrapping_mul(0xD6E8_FEB8_6659_FD93))
+    }
+}
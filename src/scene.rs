@@ -0,0 +1,536 @@
+use std::{collections::{BTreeMap, HashMap}, fs, io, path::{Path, PathBuf}};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::dynamic::DynamicLayout;
+use crate::ecs::{Entity, World};
+use crate::hierarchy::children_of;
+use crate::prefab::Prefab;
+use crate::units::Document;
+
+// A saved World: every entity that has at least one serializable component,
+// with components keyed by their registered name. `id` is the entity the
+// data was saved from; loading maps it to a fresh entity. An entity naming
+// a prefab starts from the prefab's components, with its own components
+// as overrides. Dynamic components are saved by name like typed ones,
+// with their layouts registered before any entity loads.
+#[derive(Serialize, Deserialize, Default)]
+pub struct SceneData {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub document: Option<Document>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub layouts: Vec<DynamicLayout>,
+    #[serde(default)]
+    pub prefabs: BTreeMap<String, Prefab>,
+    pub entities: Vec<EntityData>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct EntityData {
+    pub id: Entity,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefab: Option<String>,
+    #[serde(default)]
+    pub components: BTreeMap<String, Value>,
+}
+
+impl World {
+    pub fn save(&self) -> io::Result<SceneData> {
+        let mut scene = SceneData { document: Some(*self.document()), layouts: self.dynamic_layouts(), ..SceneData::default() };
+        for (name, prefab) in self.prefabs() {
+            scene.prefabs.insert(name.clone(), prefab.clone());
+        }
+        for entity in self.entities() {
+            let components = self.serialize_components(entity)?;
+            if !components.is_empty() {
+                scene.entities.push(EntityData { id: entity, prefab: None, components });
+            }
+        }
+        Ok(scene)
+    }
+
+    // Every registered and dynamic component of `entity`, by name.
+    pub fn serialize_components(&self, entity: Entity) -> io::Result<BTreeMap<String, Value>> {
+        let mut components = BTreeMap::new();
+        for info in self.registry().iter() {
+            if let Some(value) = info.serialize(self, entity) {
+                components.insert(info.name.to_string(), value?);
+            }
+        }
+        for name in self.dynamic_names() {
+            if let Some(value) = self.dynamic_storage(name).and_then(|storage| storage.get(entity).cloned()) {
+                components.insert(name.to_string(), value);
+            }
+        }
+        Ok(components)
+    }
+
+    // Sorted by name, so saving the same World twice gives the same file.
+    fn dynamic_layouts(&self) -> Vec<DynamicLayout> {
+        let mut layouts: Vec<DynamicLayout> = self.dynamic_names()
+            .filter_map(|name| Some(self.dynamic_storage(name)?.layout().clone()))
+            .collect();
+        layouts.sort_by(|a, b| a.name.cmp(&b.name));
+        layouts
+    }
+
+    // `entity` and everything inside it as a scene of its own, root first,
+    // with the layouts of any dynamic components. Components go through the
+    // registry, so only serializable ones are included. The root's Parent
+    // is left out, so loading it puts the copy at the top level.
+    pub fn copy_subtree(&self, entity: Entity) -> io::Result<SceneData> {
+        let mut subtree = vec![entity];
+        let mut next = 0;
+        while next < subtree.len() {
+            subtree.extend(children_of(self, subtree[next]));
+            next += 1;
+        }
+        let mut entities = Vec::with_capacity(subtree.len());
+        for id in subtree {
+            let mut components = self.serialize_components(id)?;
+            if id == entity {
+                components.remove("Parent");
+            }
+            entities.push(EntityData { id, prefab: None, components });
+        }
+        Ok(SceneData { layouts: self.dynamic_layouts(), entities, ..SceneData::default() })
+    }
+
+    // Moves `entity` and everything inside it into `dest`, e.g. from the UI
+    // world into the document, through copy_subtree. Returns the new
+    // entities, root first.
+    pub fn move_to(&mut self, entity: Entity, dest: &mut World) -> io::Result<Vec<Entity>> {
+        if !self.is_alive(entity) {
+            return Ok(Vec::new());
+        }
+        // Only once `dest` has them, so a failed load loses nothing.
+        let moved = dest.load(self.copy_subtree(entity)?)?;
+        self.despawn_recursive(entity);
+        Ok(moved)
+    }
+
+    // Spawns the scene's entities next to whatever the World already holds
+    // and returns them in scene order. Entity references inside components
+    // are rewritten to the new entities. The scene's document settings, if
+    // it has any, replace the World's. On error the World is left as it
+    // was: no new entities, and the document, prefabs and dynamic layouts
+    // it had before.
+    pub fn load(&mut self, mut scene: SceneData) -> io::Result<Vec<Entity>> {
+        // Layouts the World already has stay; only the staged ones are new
+        // and have to go again if the load fails.
+        scene.layouts = self.stage_dynamic(&scene.layouts)?;
+        let staged: Vec<String> = scene.layouts.iter().map(|layout| layout.name.clone()).collect();
+        let document = *self.document();
+        let prefabs = self.prefabs().clone();
+        let mut spawned = Vec::with_capacity(scene.entities.len());
+        if let Err(e) = self.load_entities(scene, &mut spawned) {
+            for entity in spawned {
+                self.despawn(entity);
+            }
+            for name in staged {
+                self.unregister_dynamic(&name);
+            }
+            *self.document_mut() = document;
+            *self.prefabs_mut() = prefabs;
+            return Err(e);
+        }
+        Ok(spawned)
+    }
+
+    fn load_entities(&mut self, scene: SceneData, spawned: &mut Vec<Entity>) -> io::Result<()> {
+        if let Some(document) = scene.document {
+            *self.document_mut() = document;
+        }
+        for layout in scene.layouts {
+            self.register_dynamic(layout)?;
+        }
+        for (name, prefab) in scene.prefabs {
+            self.register_prefab(name, prefab);
+        }
+
+        let mut map = HashMap::new();
+        for data in &scene.entities {
+            let entity = self.spawn();
+            map.insert(data.id, entity);
+            spawned.push(entity);
+        }
+
+        for (data, entity) in scene.entities.into_iter().zip(spawned.iter().copied()) {
+            let components = match &data.prefab {
+                Some(name) => self.prefab(name)
+                    .ok_or_else(|| io::Error::other(format!("unknown prefab {}", name)))?
+                    .merged(&data.components),
+                None => data.components,
+            };
+            self.insert_components(entity, components)?;
+        }
+
+        let infos: Vec<_> = self.registry().iter().copied().collect();
+        for entity in spawned.iter() {
+            for info in &infos {
+                info.map_entities(self, *entity, &map);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn insert_components(&mut self, entity: Entity, components: BTreeMap<String, Value>) -> io::Result<()> {
+        for (name, value) in components {
+            self.insert_by_name(entity, &name, value)?;
+        }
+        Ok(())
+    }
+
+    // A registered or dynamic component, by the name scenes use for it.
+    pub fn insert_by_name(&mut self, entity: Entity, name: &str, value: Value) -> io::Result<()> {
+        if let Some(info) = self.registry().get_by_name(name).copied() {
+            return info.deserialize(self, entity, value);
+        }
+        if self.dynamic_storage(name).is_none() {
+            return Err(io::Error::other(format!("unknown component {}", name)));
+        }
+        self.insert_dynamic(entity, name, value).map(|_| ())
+    }
+
+    pub fn remove_by_name(&mut self, entity: Entity, name: &str) {
+        match self.registry().get_by_name(name).copied() {
+            Some(info) => info.remove(self, entity),
+            None => { self.remove_dynamic(entity, name); }
+        }
+    }
+}
+
+pub fn save_scene(world: &World, path: &Path) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let json = serde_json::to_string_pretty(&world.save()?)?;
+    fs::write(path, json)
+}
+
+// Where to save a document that has never been saved: "untitled.json" in
+// `dir`, or "untitled 2.json" and so on if that's taken, so nothing already
+// on disk is overwritten.
+pub fn untitled_path(dir: &Path) -> PathBuf {
+    let mut path = dir.join("untitled.json");
+    let mut number = 2;
+    while path.exists() {
+        path = dir.join(format!("untitled {}.json", number));
+        number += 1;
+    }
+    path
+}
+
+pub fn load_scene(world: &mut World, path: &Path) -> io::Result<Vec<Entity>> {
+    load_scene_str(world, &fs::read_to_string(path)?)
+}
+
+pub fn load_scene_str(world: &mut World, json: &str) -> io::Result<Vec<Entity>> {
+    let scene: SceneData = serde_json::from_str(json)?;
+    world.load(scene)
+}
+
+// Replaces everything but `keep` (see World::clear_except) with the scene
+// at `path`. The scene loads next to the current one, which is only
+// cleared once that succeeded, so a broken file leaves it alone.
+pub fn switch_scene(world: &mut World, path: &Path, keep: &[Entity]) -> io::Result<Vec<Entity>> {
+    switch_scene_str(world, &fs::read_to_string(path)?, keep)
+}
+
+pub fn switch_scene_str(world: &mut World, json: &str, keep: &[Entity]) -> io::Result<Vec<Entity>> {
+    let scene: SceneData = serde_json::from_str(json)?;
+    let has_document = scene.document.is_some();
+    // Commands the old scene queued apply to it, not the new one.
+    world.flush();
+    let loaded = world.load(scene)?;
+    let kept: Vec<Entity> = keep.iter().chain(&loaded).copied().collect();
+    world.clear_except(&kept);
+    if !has_document {
+        *world.document_mut() = Document::default();
+    }
+    Ok(loaded)
+}
+
+// serde adapters for the skia types components use, as plain arrays.
+
+pub mod rect {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use skia_safe::Rect;
+
+    pub fn serialize<S: Serializer>(rect: &Rect, serializer: S) -> Result<S::Ok, S::Error> {
+        [rect.left, rect.top, rect.right, rect.bottom].serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Rect, D::Error> {
+        let [left, top, right, bottom] = <[f32; 4]>::deserialize(deserializer)?;
+        Ok(Rect::new(left, top, right, bottom))
+    }
+}
+
+pub mod option_rect {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use skia_safe::Rect;
+
+    pub fn serialize<S: Serializer>(rect: &Option<Rect>, serializer: S) -> Result<S::Ok, S::Error> {
+        rect.map(|rect| [rect.left, rect.top, rect.right, rect.bottom]).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Rect>, D::Error> {
+        Ok(Option::<[f32; 4]>::deserialize(deserializer)?.map(|[left, top, right, bottom]| Rect::new(left, top, right, bottom)))
+    }
+}
+
+pub mod point {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use skia_safe::Point;
+
+    pub fn serialize<S: Serializer>(point: &Point, serializer: S) -> Result<S::Ok, S::Error> {
+        [point.x, point.y].serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Point, D::Error> {
+        let [x, y] = <[f32; 2]>::deserialize(deserializer)?;
+        Ok(Point::new(x, y))
+    }
+}
+
+pub mod color {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use skia_safe::Color;
+
+    // 0xAARRGGBB, like skia's own SkColor.
+    pub fn serialize<S: Serializer>(color: &Color, serializer: S) -> Result<S::Ok, S::Error> {
+        u32::from(*color).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+        Ok(Color::from(u32::deserialize(deserializer)?))
+    }
+}
+
+pub mod option_color {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use skia_safe::Color;
+
+    pub fn serialize<S: Serializer>(color: &Option<Color>, serializer: S) -> Result<S::Ok, S::Error> {
+        color.map(u32::from).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Color>, D::Error> {
+        Ok(Option::<u32>::deserialize(deserializer)?.map(Color::from))
+    }
+}
+
+// (position, 0xAARRGGBB) pairs.
+pub mod gradient_stops {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use skia_safe::Color;
+
+    pub fn serialize<S: Serializer>(stops: &[(f32, Color)], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(stops.iter().map(|(position, color)| (*position, u32::from(*color))))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<(f32, Color)>, D::Error> {
+        let stops = Vec::<(f32, u32)>::deserialize(deserializer)?;
+        Ok(stops.into_iter().map(|(position, color)| (position, Color::from(color))).collect())
+    }
+}
+
+pub mod color4f {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use skia_safe::Color4f;
+
+    pub fn serialize<S: Serializer>(color: &Color4f, serializer: S) -> Result<S::Ok, S::Error> {
+        [color.r, color.g, color.b, color.a].serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color4f, D::Error> {
+        let [r, g, b, a] = <[f32; 4]>::deserialize(deserializer)?;
+        Ok(Color4f::new(r, g, b, a))
+    }
+}
+
+pub mod matrix {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use skia_safe::Matrix;
+
+    pub fn serialize<S: Serializer>(matrix: &Matrix, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut values = [0.0; 9];
+        matrix.get_9(&mut values);
+        values.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Matrix, D::Error> {
+        let values = <[f32; 9]>::deserialize(deserializer)?;
+        let mut matrix = Matrix::new_identity();
+        matrix.set_9(&values);
+        Ok(matrix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use skia_safe::{Color4f, Rect};
+
+    use super::*;
+    use crate::dynamic::FieldKind;
+    use crate::ecs::{Bounds, Name, Quad};
+    use crate::hierarchy::{children_of, group, parent_of};
+    use crate::units::Unit;
+
+    fn rect_of(world: &World, entity: Entity) -> Option<Rect> {
+        world.storage::<Bounds>()?.get(entity).map(|bounds| bounds.rect)
+    }
+
+    #[test]
+    fn save_then_load_round_trips() {
+        let mut world = World::new();
+        world.document_mut().unit = Unit::Mm;
+        let a = world.spawn();
+        world.insert(a, Bounds { rect: Rect::from_xywh(0.0, 0.0, 10.0, 10.0) });
+        world.insert(a, Quad { rect: Rect::from_xywh(0.0, 0.0, 10.0, 10.0), color: Color4f::new(1.0, 0.0, 0.0, 1.0) });
+        world.insert(a, Name("a".to_string()));
+        let b = world.spawn();
+        world.insert(b, Bounds { rect: Rect::from_xywh(20.0, 0.0, 10.0, 10.0) });
+        let parent = group(&mut world, &[a, b]).unwrap();
+
+        let json = serde_json::to_string(&world.save().unwrap()).unwrap();
+        let mut loaded = World::new();
+        let entities = load_scene_str(&mut loaded, &json).unwrap();
+
+        assert_eq!(entities.len(), 3);
+        assert_eq!(loaded.document(), world.document());
+        let a2 = loaded.find_by_name("a").unwrap();
+        assert_eq!(rect_of(&loaded, a2), rect_of(&world, a));
+        assert_eq!(loaded.storage::<Quad>().unwrap().get(a2).unwrap().color, Color4f::new(1.0, 0.0, 0.0, 1.0));
+        let parent2 = parent_of(&loaded, a2).unwrap();
+        assert_eq!(rect_of(&loaded, parent2), rect_of(&world, parent));
+        assert_eq!(children_of(&loaded, parent2).len(), 2);
+    }
+
+    #[test]
+    fn dynamic_components_round_trip_and_move() {
+        let mut world = World::new();
+        world.register_dynamic(DynamicLayout::new("Health").field("current", FieldKind::Number)).unwrap();
+        let a = world.spawn();
+        world.insert(a, Name("a".to_string()));
+        world.insert_dynamic(a, "Health", json!({ "current": 3.0 })).unwrap();
+
+        let json = serde_json::to_string(&world.save().unwrap()).unwrap();
+        let mut loaded = World::new();
+        load_scene_str(&mut loaded, &json).unwrap();
+        let a2 = loaded.find_by_name("a").unwrap();
+        assert_eq!(loaded.dynamic_storage("Health").unwrap().field(a2, "current"), Some(&json!(3.0)));
+
+        let mut dest = World::new();
+        let moved = loaded.move_to(a2, &mut dest).unwrap();
+        let values: Vec<_> = dest.query_dynamic("Health").iter().map(|(entity, value)| (entity, value.clone())).collect();
+        assert_eq!(values, vec![(moved[0], json!({ "current": 3.0 }))]);
+    }
+
+    #[test]
+    fn layouts_are_only_registered_by_a_scene_that_loads() {
+        let mut world = World::new();
+        let scene = json!({
+            "layouts": [{ "name": "Health", "fields": [["current", "number"]] }],
+            "entities": [
+                { "id": { "index": 0, "generation": 0 }, "components": { "Health": { "current": 1.0 } } },
+                { "id": { "index": 1, "generation": 0 }, "components": { "NoSuchComponent": {} } },
+            ],
+        });
+        assert!(load_scene_str(&mut world, &scene.to_string()).is_err());
+        assert!(world.dynamic_storage("Health").is_none());
+
+        // Clashing layouts are refused before anything is registered.
+        let clash = json!({
+            "layouts": [
+                { "name": "Tag", "fields": [["label", "text"]] },
+                { "name": "Health", "fields": [["current", "number"]] },
+                { "name": "Health", "fields": [["current", "text"]] },
+            ],
+            "entities": [],
+        });
+        assert!(load_scene_str(&mut world, &clash.to_string()).is_err());
+        assert_eq!(world.dynamic_names().count(), 0);
+
+        let scene = json!({
+            "layouts": [{ "name": "Health", "fields": [["current", "number"]] }],
+            "entities": [{ "id": { "index": 0, "generation": 0 }, "components": { "Health": { "current": 1.0 } } }],
+        });
+        let loaded = load_scene_str(&mut world, &scene.to_string()).unwrap();
+        assert_eq!(world.dynamic_storage("Health").unwrap().field(loaded[0], "current"), Some(&json!(1.0)));
+    }
+
+    #[test]
+    fn failed_switch_keeps_the_open_scene() {
+        let mut world = World::new();
+        world.document_mut().unit = Unit::Mm;
+        let existing = world.spawn();
+        world.insert(existing, Name("existing".to_string()));
+
+        let broken = json!({
+            "entities": [{ "id": { "index": 3, "generation": 0 }, "components": { "NoSuchComponent": {} } }],
+        });
+        assert!(switch_scene_str(&mut world, &broken.to_string(), &[]).is_err());
+        assert_eq!(world.entities().collect::<Vec<_>>(), vec![existing]);
+        assert_eq!(world.document().unit, Unit::Mm);
+
+        let scene = json!({
+            "entities": [{ "id": { "index": 3, "generation": 0 }, "components": { "Name": "loaded" } }],
+        });
+        let loaded = switch_scene_str(&mut world, &scene.to_string(), &[]).unwrap();
+        assert_eq!(world.entities().collect::<Vec<_>>(), loaded);
+        assert_eq!(world.find_by_name("loaded"), Some(loaded[0]));
+        assert_eq!(*world.document(), Document::default());
+    }
+
+    #[test]
+    fn failed_load_after_a_layout_leaves_world_unchanged() {
+        let mut world = World::new();
+        world.register_dynamic(DynamicLayout::new("Tag").field("label", FieldKind::Text)).unwrap();
+        let existing = world.spawn();
+        world.insert_dynamic(existing, "Tag", json!({ "label": "kept" })).unwrap();
+        let document = *world.document();
+
+        let scene = json!({
+            "document": { "dpi": 300.0, "unit": "mm" },
+            "layouts": [{ "name": "Health", "fields": [["current", "number"]] }],
+            "prefabs": { "Enemy": { "components": { "Health": {} } } },
+            "entities": [
+                { "id": { "index": 7, "generation": 0 }, "prefab": "Enemy", "components": {} },
+                { "id": { "index": 8, "generation": 0 }, "components": { "Tag": { "label": 3 } } },
+            ],
+        });
+        assert!(load_scene_str(&mut world, &scene.to_string()).is_err());
+
+        assert_eq!(world.entities().collect::<Vec<_>>(), vec![existing]);
+        assert_eq!(world.dynamic_names().collect::<Vec<_>>(), vec!["Tag"]);
+        assert_eq!(world.dynamic_storage("Tag").unwrap().field(existing, "label"), Some(&json!("kept")));
+        assert!(world.prefabs().is_empty());
+        assert_eq!(*world.document(), document);
+    }
+
+    #[test]
+    fn failed_load_leaves_world_unchanged() {
+        let mut world = World::new();
+        let existing = world.spawn();
+        world.insert(existing, Name("existing".to_string()));
+        let before = *world.document();
+
+        let scene = json!({
+            "document": { "dpi": 300.0, "unit": "mm" },
+            "entities": [
+                { "id": { "index": 7, "generation": 0 }, "components": { "Name": "loaded" } },
+                { "id": { "index": 8, "generation": 0 }, "components": { "NoSuchComponent": {} } },
+            ],
+        });
+        assert!(load_scene_str(&mut world, &scene.to_string()).is_err());
+
+        assert_eq!(world.entities().collect::<Vec<_>>(), vec![existing]);
+        assert_eq!(world.find_by_name("loaded"), None);
+        assert_eq!(world.find_by_name("existing"), Some(existing));
+        assert_eq!(*world.document(), before);
+    }
+}
@@ -0,0 +1,41 @@
+use std::{fs, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::export::IndexedExport;
+use crate::filters::GradientPreset;
+use crate::workspace::Workspace;
+
+pub const SETTINGS_PATH: &str = "settings.json";
+
+// Editor preferences that outlive a document. Every field has a default so
+// older settings files keep loading as fields are added.
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+pub struct Settings {
+    // Name of the active workspace.
+    #[serde(default)]
+    pub workspace: String,
+    // Saved layouts; one named like a preset replaces it.
+    #[serde(default)]
+    pub workspaces: Vec<Workspace>,
+    // Palette and dithering for indexed PNG export.
+    #[serde(default)]
+    pub indexed_export: IndexedExport,
+    // Gradient maps saved by the user, offered after the duotone presets.
+    #[serde(default)]
+    pub gradient_presets: Vec<GradientPreset>,
+}
+
+impl Settings {
+    // A missing file is the defaults.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        if !path.exists() {
+            return Ok(Settings::default());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+}
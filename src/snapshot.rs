@@ -0,0 +1,277 @@
+use std::{collections::{BTreeMap, HashMap, VecDeque}, io};
+
+use serde_json::Value;
+
+use crate::ecs::{Entity, World};
+use crate::units::Document;
+
+const UNDO_LIMIT: usize = 100;
+
+// The registered serializable and dynamic components of every entity, as
+// scene data. Unregistered and unserializable components (GPU resources,
+// per-frame state such as ParallaxOffset) and skipped fields (hover state)
+// aren't captured, so restoring leaves them alone and pointer motion is
+// never an edit.
+#[derive(Clone, PartialEq, Default)]
+pub struct WorldSnapshot {
+    entities: HashMap<Entity, BTreeMap<String, Value>>,
+}
+
+// One component that differs between two snapshots. `None` on either side
+// means the entity didn't have it.
+#[derive(Debug)]
+pub struct Change {
+    pub entity: Entity,
+    pub component: String,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+impl WorldSnapshot {
+    pub fn capture(world: &World) -> io::Result<Self> {
+        let mut entities = HashMap::new();
+        for entity in world.entities() {
+            let components = world.serialize_components(entity)?;
+            if !components.is_empty() {
+                entities.insert(entity, components);
+            }
+        }
+        Ok(WorldSnapshot { entities })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    // What changed going from `self` to `newer`.
+    pub fn diff(&self, newer: &WorldSnapshot) -> Vec<Change> {
+        let empty = BTreeMap::new();
+        let mut entities: Vec<Entity> = self.entities.keys().chain(newer.entities.keys()).copied().collect();
+        entities.sort_by_key(|entity| (entity.index, entity.generation));
+        entities.dedup();
+
+        let mut changes = Vec::new();
+        for entity in entities {
+            let before = self.entities.get(&entity).unwrap_or(&empty);
+            let after = newer.entities.get(&entity).unwrap_or(&empty);
+            let mut names: Vec<&String> = before.keys().chain(after.keys()).collect();
+            names.sort();
+            names.dedup();
+            for component in names {
+                let before = before.get(component);
+                let after = after.get(component);
+                if before != after {
+                    changes.push(Change { entity, component: component.clone(), before: before.cloned(), after: after.cloned() });
+                }
+            }
+        }
+        changes
+    }
+
+    // Puts the World back the way it was captured, writing only components
+    // that differ so change detection sees just those. Entities spawned
+    // since are despawned; despawned ones come back as new entities, with
+    // references to them rewritten.
+    pub fn restore(&self, world: &mut World) -> io::Result<()> {
+        let current = WorldSnapshot::capture(world)?;
+        for entity in current.entities.keys() {
+            if !self.entities.contains_key(entity) {
+                world.despawn(*entity);
+            }
+        }
+
+        let mut map = HashMap::new();
+        for entity in self.entities.keys() {
+            if !world.is_alive(*entity) {
+                map.insert(*entity, world.spawn());
+            }
+        }
+
+        let empty = BTreeMap::new();
+        for (entity, components) in &self.entities {
+            let target = map.get(entity).copied().unwrap_or(*entity);
+            let now = current.entities.get(entity).filter(|_| target == *entity).unwrap_or(&empty);
+            for (name, value) in components {
+                if now.get(name) != Some(value) {
+                    world.insert_by_name(target, name, value.clone())?;
+                }
+            }
+            for name in now.keys() {
+                if !components.contains_key(name) {
+                    world.remove_by_name(target, name);
+                }
+            }
+        }
+
+        if !map.is_empty() {
+            let infos: Vec<_> = world.registry().iter().copied().collect();
+            for entity in self.entities.keys() {
+                let target = map.get(entity).copied().unwrap_or(*entity);
+                for info in &infos {
+                    info.map_entities(world, target, &map);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// The document as of its last open or save, to tell whether closing it
+// would lose anything.
+pub struct SavePoint {
+    snapshot: WorldSnapshot,
+    document: Document,
+}
+
+impl SavePoint {
+    pub fn capture(world: &World) -> io::Result<Self> {
+        Ok(SavePoint { snapshot: WorldSnapshot::capture(world)?, document: *world.document() })
+    }
+
+    // A World that can't be captured counts as changed.
+    pub fn matches(&self, world: &World) -> bool {
+        *world.document() == self.document
+            && WorldSnapshot::capture(world).is_ok_and(|snapshot| snapshot == self.snapshot)
+    }
+}
+
+// Snapshot-based undo: each step is the World as it was before an edit.
+pub struct UndoHistory {
+    // Oldest first, so the oldest step can be dropped at the limit.
+    undo: VecDeque<WorldSnapshot>,
+    redo: Vec<WorldSnapshot>,
+}
+
+impl UndoHistory {
+    pub fn new() -> Self {
+        UndoHistory { undo: VecDeque::new(), redo: Vec::new() }
+    }
+
+    // Records `before` as an undo step if the World has changed since it
+    // was captured, so commands that did nothing don't leave empty steps.
+    pub fn commit(&mut self, before: WorldSnapshot, world: &World) -> io::Result<()> {
+        if WorldSnapshot::capture(world)? == before {
+            return Ok(());
+        }
+        if self.undo.len() == UNDO_LIMIT {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(before);
+        self.redo.clear();
+        Ok(())
+    }
+
+    pub fn undo(&mut self, world: &mut World) -> io::Result<bool> {
+        let Some(snapshot) = self.undo.pop_back() else { return Ok(false); };
+        self.redo.push(WorldSnapshot::capture(world)?);
+        snapshot.restore(world)?;
+        Ok(true)
+    }
+
+    pub fn redo(&mut self, world: &mut World) -> io::Result<bool> {
+        let Some(snapshot) = self.redo.pop() else { return Ok(false); };
+        self.undo.push_back(WorldSnapshot::capture(world)?);
+        snapshot.restore(world)?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use skia_safe::{Point, Rect};
+
+    use super::*;
+    use crate::dynamic::{DynamicLayout, FieldKind};
+    use crate::ecs::{Bounds, Events, Interactable, InteractableState, Name, Parallax, PointerState};
+    use crate::graphics::{ParallaxOffset, parallax_compute};
+    use crate::systems::hover_system;
+    use crate::testing::WorldBuilder;
+    use crate::units::Unit;
+
+    #[test]
+    fn save_point_sees_edits_and_their_undo() {
+        let mut world = World::new();
+        let entity = world.spawn();
+        world.insert(entity, Bounds { rect: Rect::from_wh(10.0, 10.0) });
+        let saved = SavePoint::capture(&world).unwrap();
+        assert!(saved.matches(&world));
+
+        let mut undo = UndoHistory::new();
+        let before = WorldSnapshot::capture(&world).unwrap();
+        world.insert(entity, Bounds { rect: Rect::from_wh(20.0, 10.0) });
+        undo.commit(before, &world).unwrap();
+        assert!(!saved.matches(&world));
+        undo.undo(&mut world).unwrap();
+        assert!(saved.matches(&world));
+
+        world.document_mut().unit = Unit::Mm;
+        assert!(!saved.matches(&world));
+    }
+
+    #[test]
+    fn hovering_does_not_dirty_the_save_point() {
+        let (mut world, entities) = WorldBuilder::new()
+            .button(Rect::from_xywh(0.0, 0.0, 10.0, 10.0))
+            .build();
+        let saved = SavePoint::capture(&world).unwrap();
+        let mut pointer = PointerState::new();
+        pointer.push_sample(Point::new(5.0, 5.0));
+        hover_system(&mut world, &pointer, &mut Events::new());
+        let state = world.storage::<Interactable>().unwrap().get(entities[0]).unwrap().state.clone();
+        assert_eq!(state, InteractableState::HOVERED);
+        assert!(saved.matches(&world));
+    }
+
+    #[test]
+    fn pointer_state_is_not_an_edit() {
+        let (mut world, entities) = WorldBuilder::new()
+            .button(Rect::from_xywh(0.0, 0.0, 10.0, 10.0))
+            .with(Parallax { strength: 0.5 })
+            .build();
+        let button = entities[0];
+        let mut undo = UndoHistory::new();
+        let before = WorldSnapshot::capture(&world).unwrap();
+        world.insert(button, Name("button".to_string()));
+        undo.commit(before, &world).unwrap();
+
+        let mut pointer = PointerState::new();
+        pointer.push_sample(Point::new(5.0, 5.0));
+        let moved = WorldSnapshot::capture(&world).unwrap();
+        hover_system(&mut world, &pointer, &mut Events::new());
+        parallax_compute(&mut world, pointer.position, Rect::from_wh(100.0, 100.0));
+        assert!(moved.diff(&WorldSnapshot::capture(&world).unwrap()).is_empty());
+
+        // Undoing the edit leaves the pointer's state alone.
+        undo.undo(&mut world).unwrap();
+        assert!(world.find_by_name("button").is_none());
+        let state = world.storage::<Interactable>().unwrap().get(button).unwrap().state.clone();
+        assert_eq!(state, InteractableState::HOVERED);
+        assert!(world.storage::<ParallaxOffset>().unwrap().get(button).is_some());
+    }
+
+    #[test]
+    fn undo_restores_dynamic_components() {
+        let mut world = World::new();
+        world.register_dynamic(DynamicLayout::new("Tag").field("label", FieldKind::Text)).unwrap();
+        let entity = world.spawn();
+        world.insert_dynamic(entity, "Tag", json!({ "label": "a" })).unwrap();
+
+        let mut undo = UndoHistory::new();
+        let before = WorldSnapshot::capture(&world).unwrap();
+        world.dynamic_storage_mut("Tag").unwrap().set(entity, "label", json!("b")).unwrap();
+        undo.commit(before, &world).unwrap();
+        let before = WorldSnapshot::capture(&world).unwrap();
+        world.remove_dynamic(entity, "Tag");
+        undo.commit(before, &world).unwrap();
+
+        undo.undo(&mut world).unwrap();
+        assert_eq!(world.dynamic_storage("Tag").unwrap().field(entity, "label"), Some(&json!("b")));
+        undo.undo(&mut world).unwrap();
+        assert_eq!(world.dynamic_storage("Tag").unwrap().field(entity, "label"), Some(&json!("a")));
+    }
+}
@@ -0,0 +1,225 @@
+use std::{fs, io, path::{Path, PathBuf}};
+
+use serde::{Deserialize, Serialize};
+use skia_safe::{Canvas, Color, Color4f, Data, Image, Matrix, Paint, Point, Rect, images, surfaces};
+
+use crate::ecs::{BlocksInput, Bounds, Entity, Icon, InteractionPriority, Quad, Transform, World, ZIndex};
+use crate::export::write_png;
+use crate::geometry::union_all;
+use crate::scene;
+use crate::systems::{ButtonBundle, entity_at, render_page, render_world};
+use crate::text::{FontManager, spawn_label, text_layout_system};
+
+pub const RECENT_PATH: &str = "recent.json";
+const MAX_RECENT: usize = 8;
+
+// Bundled scenes that show off what the editor can do; opened like any
+// other file but never saved over.
+pub const SAMPLES: &[(&str, &str)] = &[
+    ("Starter", include_str!("../assets/default_scene.json")),
+    ("Widgets", include_str!("../assets/samples/widgets.json")),
+    ("Shapes", include_str!("../assets/samples/shapes.json")),
+];
+
+const TILE: f32 = 128.0;
+const GAP: f32 = 16.0;
+const COLUMNS: usize = 4;
+const START_PRIORITY: i32 = 1000;
+const START_Z: i32 = 1000;
+// The strip along the bottom of a tile its name goes in.
+const LABEL_HEIGHT: f32 = 20.0;
+const LABEL_SIZE: f32 = 12.0;
+
+// Most recently opened or saved scene files, newest first.
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+pub struct RecentFiles {
+    pub paths: Vec<PathBuf>,
+}
+
+impl RecentFiles {
+    // A missing file is no recent files yet.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        if !path.exists() {
+            return Ok(RecentFiles::default());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn push(&mut self, scene: &Path) {
+        self.paths.retain(|path| path != scene);
+        self.paths.insert(0, scene.to_path_buf());
+        self.paths.truncate(MAX_RECENT);
+    }
+
+    // Skips files that were moved or deleted since.
+    pub fn existing(&self) -> impl Iterator<Item = &Path> {
+        self.paths.iter().map(|path| path.as_path()).filter(|path| path.exists())
+    }
+}
+
+// scene.json -> scene.thumb.png, next to the scene.
+pub fn thumbnail_path(scene: &Path) -> PathBuf {
+    scene.with_extension("thumb.png")
+}
+
+// The page, or all the content for an unbounded document, scaled to fit
+// `size` square. None for an empty document.
+pub fn render_thumbnail(world: &World, size: f32) -> Option<Image> {
+    let content = world.storage::<Bounds>().and_then(|bounds| union_all(bounds.iter().map(|(_, bounds)| bounds.rect)));
+    let rect = world.document().page().or(content)?;
+    let scale = size / rect.width().max(rect.height());
+    let mut surface = surfaces::raster_n32_premul((
+        ((rect.width() * scale).ceil() as i32).max(1),
+        ((rect.height() * scale).ceil() as i32).max(1),
+    ))?;
+    let canvas = surface.canvas();
+    canvas.clear(Color::from_rgb(200, 200, 200));
+    canvas.scale((scale, scale));
+    canvas.translate((-rect.left(), -rect.top()));
+    render_page(world, canvas);
+    render_world(world, canvas);
+    Some(surface.image_snapshot())
+}
+
+pub fn write_thumbnail(world: &World, scene: &Path) -> io::Result<()> {
+    let Some(image) = render_thumbnail(world, TILE) else { return Ok(()); };
+    write_png(&image, &thumbnail_path(scene))
+}
+
+fn load_thumbnail(scene: &Path) -> Option<Image> {
+    let bytes = fs::read(thumbnail_path(scene)).ok()?;
+    images::deferred_from_encoded_data(Data::new_copy(&bytes), None)
+}
+
+pub enum StartAction {
+    Pending,
+    Open(PathBuf),
+    New,
+    Sample(usize),
+}
+
+#[derive(Clone)]
+enum StartEntry {
+    New,
+    Sample(usize),
+    Recent(PathBuf),
+}
+
+impl StartEntry {
+    fn name(&self) -> String {
+        match self {
+            StartEntry::New => "New".to_string(),
+            StartEntry::Sample(sample) => SAMPLES[*sample].0.to_string(),
+            StartEntry::Recent(path) => path.file_stem().map_or_else(|| path.display().to_string(), |stem| stem.to_string_lossy().into_owned()),
+        }
+    }
+}
+
+// Shown while no document is open: a "new" tile (which leads to the
+// new-document dialog) and the bundled samples on the first row, then the
+// recent files. Tiles live in the UI world; their thumbnails are images,
+// drawn over them by draw_thumbnails.
+pub struct StartScreen {
+    tiles: Vec<(Entity, StartEntry)>,
+    thumbnails: Vec<(Rect, Image)>,
+    entities: Vec<Entity>,
+}
+
+impl StartScreen {
+    // `new_world` builds the World samples are previewed in, with whatever
+    // prefabs they use.
+    pub fn open(ui: &mut World, recent: &RecentFiles, new_world: fn() -> World, fonts: &mut FontManager, viewport: Rect) -> Self {
+        let mut entries = vec![StartEntry::New];
+        entries.extend((0..SAMPLES.len()).map(StartEntry::Sample));
+        // Recent files start on a row of their own.
+        let first_recent = entries.len().div_ceil(COLUMNS) * COLUMNS;
+        let recent = recent.existing().map(|path| StartEntry::Recent(path.to_path_buf()));
+        let slots = (0..entries.len()).chain(first_recent..);
+        let entries: Vec<(usize, StartEntry)> = slots.zip(entries.into_iter().chain(recent)).collect();
+
+        let backdrop = ui.spawn();
+        ui.insert(backdrop, Bounds { rect: viewport });
+        ui.insert(backdrop, Quad { rect: viewport, color: Color4f::new(0.9, 0.9, 0.9, 1.0) });
+        ui.insert(backdrop, Transform { local_to_parent: Matrix::new_identity(), z: 0.0 });
+        ui.insert(backdrop, ZIndex(START_Z));
+        ui.insert(backdrop, BlocksInput);
+        ui.insert(backdrop, InteractionPriority(START_PRIORITY));
+        let mut screen = StartScreen { tiles: Vec::new(), thumbnails: Vec::new(), entities: vec![backdrop] };
+
+        let width = COLUMNS as f32 * (TILE + GAP) - GAP;
+        let origin = Point::new(viewport.center_x() - width * 0.5, viewport.top + GAP * 2.0);
+        for (index, entry) in entries {
+            let column = (index % COLUMNS) as f32;
+            let row = (index / COLUMNS) as f32;
+            let rect = Rect::from_xywh(origin.x + column * (TILE + GAP), origin.y + row * (TILE + GAP), TILE, TILE);
+            let tile = ui.spawn_bundle(ButtonBundle::new(rect));
+            ui.insert(tile, ZIndex(START_Z + 1));
+            ui.insert(tile, InteractionPriority(START_PRIORITY));
+
+            let thumbnail = match &entry {
+                StartEntry::New => {
+                    ui.insert(tile, Icon { name: "plus".to_string(), tint: Color::BLACK, size: TILE * 0.4 });
+                    None
+                }
+                StartEntry::Sample(sample) => {
+                    let mut world = new_world();
+                    match scene::load_scene_str(&mut world, SAMPLES[*sample].1) {
+                        Ok(_) => {
+                            text_layout_system(&mut world, fonts);
+                            render_thumbnail(&world, TILE - GAP)
+                        }
+                        Err(e) => {
+                            eprintln!("Failed to load sample {}: {:?}", SAMPLES[*sample].0, e);
+                            None
+                        }
+                    }
+                }
+                StartEntry::Recent(path) => load_thumbnail(path),
+            };
+            let label = Rect::new(rect.left, rect.bottom - LABEL_HEIGHT, rect.right, rect.bottom);
+            screen.entities.push(spawn_label(ui, label, &entry.name(), LABEL_SIZE, START_Z + 2));
+            if let Some(image) = thumbnail {
+                let area = Rect::new(rect.left, rect.top, rect.right, label.top).with_inset((GAP * 0.5, GAP * 0.5));
+                screen.thumbnails.push((fit(area, &image), image));
+            }
+            screen.tiles.push((tile, entry));
+            screen.entities.push(tile);
+        }
+        screen
+    }
+
+    pub fn click(&self, ui: &World, point: Point) -> StartAction {
+        let Some(hit) = entity_at(ui, point) else { return StartAction::Pending; };
+        match self.tiles.iter().find(|(tile, _)| *tile == hit).map(|(_, entry)| entry.clone()) {
+            Some(StartEntry::New) => StartAction::New,
+            Some(StartEntry::Sample(sample)) => StartAction::Sample(sample),
+            Some(StartEntry::Recent(path)) => StartAction::Open(path),
+            None => StartAction::Pending,
+        }
+    }
+
+    pub fn draw_thumbnails(&self, canvas: &Canvas) {
+        let paint = Paint::default();
+        for (rect, image) in &self.thumbnails {
+            canvas.draw_image_rect(image, None, rect, &paint);
+        }
+    }
+
+    pub fn close(self, ui: &mut World) {
+        for entity in self.entities {
+            ui.despawn(entity);
+        }
+    }
+}
+
+// `image` scaled to fit inside `rect`, centered.
+fn fit(rect: Rect, image: &Image) -> Rect {
+    let (width, height) = (image.width() as f32, image.height() as f32);
+    let scale = (rect.width() / width).min(rect.height() / height);
+    Rect::from_xywh(0.0, 0.0, width * scale, height * scale)
+        .with_offset((rect.center_x() - width * scale * 0.5, rect.center_y() - height * scale * 0.5))
+}
@@ -0,0 +1,64 @@
+use skia_safe::{Color, Color4f, Matrix, Rect};
+
+use crate::ecs::{BlocksInput, Bounds, Constraint, Entity, InteractionPriority, Quad, Text, Transform, World, ZIndex};
+
+// Above docked panels, under dialogs and the start screen.
+const STATUS_PRIORITY: i32 = 600;
+const STATUS_Z: i32 = 600;
+pub const HEIGHT: f32 = 20.0;
+const TEXT_SIZE: f32 = 12.0;
+
+// A strip along the bottom of the window showing the last notice, e.g. a
+// setting a key just changed. It lives in the UI world, so it is never
+// saved and stays in window space.
+pub struct StatusBar {
+    entity: Entity,
+}
+
+impl StatusBar {
+    pub fn open(ui: &mut World, viewport: Rect) -> Self {
+        let rect = Rect::new(viewport.left, viewport.bottom - HEIGHT, viewport.right, viewport.bottom);
+        let entity = ui.spawn();
+        ui.insert(entity, Bounds { rect });
+        ui.insert(entity, Quad { rect, color: Color4f::new(0.2, 0.2, 0.22, 0.9) });
+        ui.insert(entity, Transform { local_to_parent: Matrix::new_identity(), z: 0.0 });
+        ui.insert(entity, ZIndex(STATUS_Z));
+        ui.insert(entity, BlocksInput);
+        ui.insert(entity, InteractionPriority(STATUS_PRIORITY));
+        ui.insert(entity, Constraint { pin_left: Some(0.0), pin_right: Some(0.0), pin_bottom: Some(0.0), ..Default::default() });
+        let status = StatusBar { entity };
+        status.show(ui, "");
+        status
+    }
+
+    // Replaces the notice; text_layout_system reshapes it next frame.
+    pub fn show(&self, ui: &mut World, message: &str) {
+        ui.insert(self.entity, Text { content: message.to_string(), size: TEXT_SIZE, color: Color::WHITE, font: "sans-serif".to_string() });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constraints::constraint_system;
+
+    #[test]
+    fn shows_the_last_message() {
+        let mut ui = World::new();
+        let status = StatusBar::open(&mut ui, Rect::from_wh(400.0, 300.0));
+        status.show(&mut ui, "first");
+        status.show(&mut ui, "second");
+        let message = ui.storage::<Text>().and_then(|storage| storage.get(status.entity).map(|text| text.content.clone()));
+        assert_eq!(message.as_deref(), Some("second"));
+    }
+
+    #[test]
+    fn follows_the_window_bottom() {
+        let mut ui = World::new();
+        let status = StatusBar::open(&mut ui, Rect::from_wh(400.0, 300.0));
+        ui.flush();
+        constraint_system(&ui, Rect::from_wh(600.0, 500.0));
+        let rect = ui.storage::<Bounds>().and_then(|storage| storage.get(status.entity).map(|bounds| bounds.rect));
+        assert_eq!(rect, Some(Rect::new(0.0, 500.0 - HEIGHT, 600.0, 500.0)));
+    }
+}
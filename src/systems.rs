@@ -0,0 +1,498 @@
+use std::{cmp::Reverse, io, time::Instant};
+
+use skia_safe::{Canvas, Color, Color4f, Matrix, Paint, PaintStyle, Point, Rect};
+
+use crate::assets::sprite_asset_system;
+use crate::atlas::atlas_icon_system;
+use crate::constraints::constraint_system;
+use crate::ecs::{Adjustment, BlocksInput, Bounds, Bundle, Children, Entity, Events, Interactable, InteractableState, InteractionEvent, InteractionPriority, PointerState, Quad, Resources, RubberBand, Selection, Shape, Slice, Sprite, Text, Transform, World, ZIndex, render_quads};
+use crate::geometry::contains_inclusive;
+use crate::graphics::parallax_compute;
+use crate::hierarchy::{ancestor_in_scope, is_group, is_hidden, is_locked, parent_of, update_group_bounds};
+use crate::prefab::Prefab;
+use crate::quality::Quality;
+use crate::text::text_layout_system;
+
+// Systems only see the World and plain input resources, never winit events
+// or the GpuState, so they can run against a World built without a window.
+
+pub struct ButtonBundle {
+    pub bounds: Bounds,
+    pub quad: Quad,
+    pub interactable: Interactable,
+    pub transform: Transform,
+}
+
+impl ButtonBundle {
+    pub fn new(rect: Rect) -> Self {
+        ButtonBundle {
+            bounds: Bounds { rect },
+            quad: Quad { color: InteractableState::DEFAULT.color(), rect },
+            interactable: Interactable { state: InteractableState::DEFAULT },
+            transform: Transform { local_to_parent: Matrix::new_identity(), z: 0.0 },
+        }
+    }
+}
+
+impl Bundle for ButtonBundle {
+    fn insert_into(self, world: &mut World, entity: Entity) {
+        (self.bounds, self.quad, self.interactable, self.transform).insert_into(world, entity);
+    }
+
+    fn insert_batch(batch: Vec<(Entity, Self)>, world: &mut World) {
+        let batch = batch.into_iter()
+            .map(|(entity, button)| (entity, (button.bounds, button.quad, button.interactable, button.transform)))
+            .collect();
+        Bundle::insert_batch(batch, world);
+    }
+}
+
+pub fn add_button(world: &mut World, rect: Rect) -> Entity {
+    world.spawn_bundle(ButtonBundle::new(rect))
+}
+
+// ButtonBundle as data plus a label, for World::instantiate("button", ...)
+// and scene files. Instances override Bounds and Quad to place it, and Text
+// to relabel it, or null it for an icon-only button.
+pub fn button_prefab() -> io::Result<Prefab> {
+    let button = ButtonBundle::new(Rect::from_wh(30.0, 30.0));
+    let label = Text { content: "OK".to_string(), size: 14.0, color: Color::BLACK, font: "sans-serif".to_string() };
+    Prefab::new()
+        .with("Bounds", &button.bounds)?
+        .with("Quad", &button.quad)?
+        .with("Interactable", &button.interactable)?
+        .with("Transform", &button.transform)?
+        .with("Text", &label)
+}
+
+pub fn render_system(world: &World, canvas: &Canvas, quality: Quality) {
+    canvas.clear(Color::from_rgb(200, 200, 200));
+    render_page(world, canvas);
+    render_quads(world, canvas, quality);
+}
+
+// The document's page, if it has a size: its background, or just the
+// outline for a transparent one.
+pub fn render_page(world: &World, canvas: &Canvas) {
+    let document = world.document();
+    let Some(page) = document.page() else { return; };
+    if let Some(background) = document.background {
+        canvas.draw_rect(page, &Paint::new(Color4f::from(background), None));
+    }
+    let mut paint = Paint::new(Color4f::new(0.0, 0.0, 0.0, 0.3), None);
+    paint.set_style(PaintStyle::Stroke);
+    paint.set_stroke_width(1.0);
+    canvas.draw_rect(page.with_outset((0.5, 0.5)), &paint);
+}
+
+// Everything that belongs to the document, without the window background.
+// Offscreen exports draw through this too, always at full quality.
+pub fn render_world(world: &World, canvas: &Canvas) {
+    render_quads(world, canvas, Quality::Full);
+}
+
+// Drawn inside the bounds so the damage of a selected entity covers it.
+pub fn render_selection(world: &World, selection: &Selection, canvas: &Canvas) {
+    let Some(bounds) = world.storage::<Bounds>() else { return; };
+    let mut paint = Paint::new(Color4f::new(0.1, 0.5, 1.0, 1.0), None);
+    paint.set_style(PaintStyle::Stroke);
+    paint.set_stroke_width(1.0);
+    for entity in selection.entities() {
+        if let Some(bounds) = bounds.get(*entity) {
+            canvas.draw_rect(bounds.rect.with_inset((0.5, 0.5)), &paint);
+        }
+    }
+}
+
+pub fn render_rubber_band(band: &RubberBand, canvas: &Canvas) {
+    let rect = band.rect();
+    let mut paint = Paint::new(Color4f::new(0.1, 0.5, 1.0, 0.15), None);
+    canvas.draw_rect(rect, &paint);
+    paint.set_color4f(Color4f::new(0.1, 0.5, 1.0, 1.0), None);
+    paint.set_style(PaintStyle::Stroke);
+    canvas.draw_rect(rect, &paint);
+}
+
+// Anything whose bounds the band touches counts, not only what it fully
+// encloses. Only entities at the selection's group level are picked.
+pub fn rubber_band_update(world: &World, band: &RubberBand, selection: &mut Selection) {
+    let rect = band.rect();
+    let hits: Vec<Entity> = world.query::<Bounds>().iter()
+        .filter(|(entity, bounds)| bounds.rect.intersects(rect) && parent_of(world, *entity) == selection.scope)
+        .filter(|(entity, _)| !is_hidden(world, *entity) && !is_locked(world, *entity))
+        .map(|(entity, _)| entity)
+        .collect();
+    selection.replace(band.apply(&hits));
+}
+
+pub fn hover_system(world: &mut World, pointer: &PointerState, events: &mut Events<InteractionEvent>) {
+    let hovered = hover_detect(world, pointer.document_position());
+    hover_update(world, &hovered, events);
+}
+
+// Blockers count as hits even when they aren't Interactable, so a modal
+// backdrop stops hover on what's under it.
+pub fn hover_detect(world: &World, cursor: Point) -> Vec<Entity> {
+    let interactables = world.storage::<Interactable>();
+    let blockers = world.storage::<BlocksInput>();
+    let is_interactable = |entity| interactables.as_ref().is_some_and(|storage| storage.contains(entity));
+    let mut results = Vec::new();
+    for (entity, bounds) in world.query::<Bounds>().sorted_by_component::<ZIndex>().into_iter().rev() {
+            if !is_interactable(entity) && !blockers.as_ref().is_some_and(|storage| storage.contains(entity)) { continue; }
+            if is_hidden(world, entity) { continue; }
+            if hit_test(world, entity, bounds.rect, cursor) { results.push(entity) }
+    }
+    let mut results = by_priority(world, results);
+    results.retain(|entity| is_interactable(*entity));
+    results
+}
+
+// Hits in the order they get input: highest InteractionPriority first,
+// ties in the order given (top-most first from the callers here), cut off
+// after the first entity that blocks input.
+pub fn by_priority(world: &World, mut hits: Vec<Entity>) -> Vec<Entity> {
+    let priorities = world.storage::<InteractionPriority>();
+    hits.sort_by_key(|entity| Reverse(priorities.as_ref().and_then(|storage| storage.get(*entity)).map_or(0, |priority| priority.0)));
+    let blockers = world.storage::<BlocksInput>();
+    if let Some(position) = hits.iter().position(|entity| blockers.as_ref().is_some_and(|storage| storage.contains(*entity))) {
+        hits.truncate(position + 1);
+    }
+    hits
+}
+
+// Whether `point` is on the entity, using its Shape if it has one.
+// Hover, pressing and picking all go through here so they agree with what
+// is drawn.
+pub fn hit_test(world: &World, entity: Entity, rect: Rect, point: Point) -> bool {
+    match world.storage::<Shape>().as_ref().and_then(|shapes| shapes.get(entity)) {
+        Some(shape) => shape.contains(rect, point),
+        None => contains_inclusive(rect, point),
+    }
+}
+
+// Groups only have Bounds around their children, so they are never hit
+// directly; click_select resolves hits to the right group level. Hidden and
+// locked entities are skipped.
+pub fn entity_at(world: &World, point: Point) -> Option<Entity> {
+    let hits = world.query::<Bounds>().without::<Children>().sorted_by_component::<ZIndex>().into_iter().rev()
+        .filter(|(entity, bounds)| hit_test(world, *entity, bounds.rect, point) && !is_hidden(world, *entity) && !is_locked(world, *entity))
+        .map(|(entity, _)| entity)
+        .collect();
+    by_priority(world, hits).first().copied()
+}
+
+// A click selects the outermost entity under `point` inside the group
+// being edited, leaving the group if the hit is outside it. A double-click
+// on a group enters it and selects the child under the cursor.
+pub fn click_select(world: &World, selection: &mut Selection, point: Point, toggle: bool, double_click: bool) -> Option<Entity> {
+    let hit = entity_at(world, point)?;
+    let target = match ancestor_in_scope(world, hit, selection.scope) {
+        Some(target) => target,
+        None => {
+            selection.scope = None;
+            ancestor_in_scope(world, hit, None)?
+        }
+    };
+    if double_click && is_group(world, target) {
+        selection.scope = Some(target);
+        let child = ancestor_in_scope(world, hit, Some(target))?;
+        selection.set(child);
+        return Some(child);
+    }
+    if toggle {
+        selection.toggle(target);
+    } else {
+        selection.set(target);
+    }
+    Some(target)
+}
+
+pub fn slice_at(world: &World, point: Point) -> Option<Rect> {
+    world.query::<Slice>().iter()
+        .find(|(_, slice)| slice.rect.contains(point))
+        .map(|(_, slice)| slice.rect)
+}
+
+// Exits are sent before enters, so moving straight from one entity onto
+// another ends with the enter.
+pub fn hover_update(world: &mut World, hovered: &[Entity], events: &mut Events<InteractionEvent>) {
+    let mut entered = Vec::new();
+    let mut exited = Vec::new();
+    world.query_mut::<Interactable, _>(|entity, mut interactable| {
+        let is_hovered = hovered.contains(&entity);
+        let next = match (is_hovered, &interactable.state) {
+            (true, InteractableState::PRESSED) => InteractableState::PRESSED,
+            (true, _) => InteractableState::HOVERED,
+            (false, _) => InteractableState::DEFAULT,
+        };
+        let was_hovered = interactable.state != InteractableState::DEFAULT;
+        if is_hovered && !was_hovered { entered.push(entity); }
+        if !is_hovered && was_hovered { exited.push(entity); }
+        // Only write on a real transition so Changed<Interactable> stays quiet.
+        if interactable.state != next {
+            interactable.state = next;
+        }
+    });
+    for entity in exited {
+        events.send(InteractionEvent::HoverExited(entity));
+    }
+    for entity in entered {
+        events.send(InteractionEvent::HoverEntered(entity));
+    }
+}
+
+// The document's systems for one frame, in the order the app runs them
+// before deciding whether to redraw: hover and the rubber band, timers and
+// queued commands, layout, then damage for whatever visibly changed. The
+// GpuState is only used, when there is one, to upload sprite images. The
+// caller ends the pointer frame, once the UI has seen it too.
+pub fn update_document(world: &mut World, resources: &mut Resources, viewport: Rect) {
+    if resources.pointer_state.moved() {
+        hover_system(world, &resources.pointer_state, &mut resources.interactions);
+        if let Some(band) = &mut resources.rubber_band {
+            let damage = &mut resources.damage;
+            damage.add(band.rect());
+            damage.add_entities(world, resources.selection.entities());
+            band.current = resources.pointer_state.document_position();
+            rubber_band_update(world, band, &mut resources.selection);
+            damage.add(band.rect());
+            damage.add_entities(world, resources.selection.entities());
+            resources.frame_scheduler.request_redraw();
+        }
+    }
+    resources.timers.update(Instant::now(), world);
+    world.flush();
+    resources.selection.retain_alive(world);
+    // Parallax layers draw outside their Bounds once shifted, so any
+    // move repaints everything.
+    if parallax_compute(world, resources.pointer_state.position, viewport) {
+        resources.damage.add_full();
+        resources.frame_scheduler.request_redraw();
+    }
+    for rect in constraint_system(world, viewport) {
+        resources.damage.add(rect);
+    }
+    update_group_bounds(world);
+    text_layout_system(world, &mut resources.fonts);
+    if let Some(atlas) = &resources.atlas {
+        atlas_icon_system(world, atlas);
+    }
+    let context = resources.gpu_state.as_mut().map(|gpu_state| &mut gpu_state.gr_context);
+    sprite_asset_system(world, &mut resources.assets, context);
+
+    let changed = changed_visuals(world);
+    resources.damage.add_entities(world, &changed);
+    if !changed.is_empty() {
+        resources.frame_scheduler.request_redraw();
+    }
+    world.clear_trackers();
+}
+
+// Entities whose appearance changed since the last World::clear_trackers,
+// whichever system touched them; their bounds need repainting.
+pub fn changed_visuals(world: &World) -> Vec<Entity> {
+    let mut entities: Vec<Entity> = world.query::<Quad>().changed::<Quad>().iter()
+        .map(|(entity, _)| entity)
+        .chain(world.query::<Transform>().changed::<Transform>().iter().map(|(entity, _)| entity))
+        // Hover and press change the fill; see Quad::fill.
+        .chain(world.query::<Quad>().changed::<Interactable>().iter().map(|(entity, _)| entity))
+        .chain(world.query::<Text>().changed::<Text>().iter().map(|(entity, _)| entity))
+        .chain(world.query::<Sprite>().changed::<Sprite>().iter().map(|(entity, _)| entity))
+        .chain(world.query::<Adjustment>().changed::<Adjustment>().iter().map(|(entity, _)| entity))
+        .collect();
+    entities.sort_by_key(|entity| (entity.index, entity.generation));
+    entities.dedup();
+    entities
+}
+
+#[cfg(test)]
+mod tests {
+    use skia_safe::{Point, Rect};
+
+    use super::*;
+    use crate::ecs::InteractableState;
+    use crate::testing::WorldBuilder;
+
+    fn pointer_at(x: f32, y: f32) -> PointerState {
+        let mut pointer = PointerState::new();
+        pointer.push_sample(Point::new(x, y));
+        pointer
+    }
+
+    fn state(world: &World, entity: Entity) -> InteractableState {
+        world.storage::<Interactable>().unwrap().get(entity).unwrap().state.clone()
+    }
+
+    #[test]
+    fn hover_enters_and_exits() {
+        let (mut world, entities) = WorldBuilder::new()
+            .button(Rect::from_xywh(0.0, 0.0, 10.0, 10.0))
+            .build();
+        let button = entities[0];
+        let mut events = Events::new();
+        let mut reader = events.reader();
+
+        hover_system(&mut world, &pointer_at(5.0, 5.0), &mut events);
+        assert_eq!(state(&world, button), InteractableState::HOVERED);
+        let fill = world.storage::<Quad>().unwrap().get(button).unwrap().fill(world.storage::<Interactable>().unwrap().get(button));
+        assert_eq!(fill, InteractableState::HOVERED.color());
+        assert!(matches!(reader.read(&events).collect::<Vec<_>>()[..], [InteractionEvent::HoverEntered(entity)] if *entity == button));
+
+        hover_system(&mut world, &pointer_at(50.0, 5.0), &mut events);
+        assert_eq!(state(&world, button), InteractableState::DEFAULT);
+        assert!(matches!(reader.read(&events).collect::<Vec<_>>()[..], [InteractionEvent::HoverExited(entity)] if *entity == button));
+    }
+
+    #[test]
+    fn hover_ignores_hidden_and_non_interactable() {
+        let (world, entities) = WorldBuilder::new()
+            .button(Rect::from_xywh(0.0, 0.0, 10.0, 10.0))
+            .with(crate::ecs::Hidden)
+            .region(Rect::from_xywh(0.0, 0.0, 10.0, 10.0))
+            .build();
+        assert!(hover_detect(&world, Point::new(5.0, 5.0)).is_empty());
+        assert_eq!(entity_at(&world, Point::new(5.0, 5.0)), Some(entities[1]));
+    }
+
+    // Three buttons stacked on the same spot.
+    fn stacked() -> WorldBuilder {
+        let rect = Rect::from_xywh(0.0, 0.0, 10.0, 10.0);
+        WorldBuilder::new().button(rect).button(rect).button(rect)
+    }
+
+    #[test]
+    fn priority_wins_over_z() {
+        let (mut world, entities) = stacked()
+            .with(ZIndex(10))
+            .build();
+        world.insert(entities[0], InteractionPriority(5));
+        world.insert(entities[1], InteractionPriority(1));
+        let point = Point::new(5.0, 5.0);
+        assert_eq!(entity_at(&world, point), Some(entities[0]));
+        assert_eq!(hover_detect(&world, point), vec![entities[0], entities[1], entities[2]]);
+    }
+
+    #[test]
+    fn blocker_stops_lower_hits() {
+        let rect = Rect::from_xywh(0.0, 0.0, 10.0, 10.0);
+        let (mut world, entities) = WorldBuilder::new()
+            .button(rect)
+            .with(ZIndex(0))
+            .region(rect)
+            .with(ZIndex(1))
+            .with(BlocksInput)
+            .button(rect)
+            .with(ZIndex(2))
+            .build();
+        let point = Point::new(5.0, 5.0);
+        assert_eq!(hover_detect(&world, point), vec![entities[2]]);
+        assert_eq!(entity_at(&world, point), Some(entities[2]));
+
+        // Raising the bottom button's priority lifts it over the blocker.
+        world.insert(entities[0], InteractionPriority(1));
+        assert_eq!(hover_detect(&world, point), vec![entities[0], entities[2]]);
+    }
+
+    #[test]
+    fn blocker_outranks_content_by_priority() {
+        let rect = Rect::from_xywh(0.0, 0.0, 10.0, 10.0);
+        let (world, entities) = WorldBuilder::new()
+            .region(rect)
+            .with(InteractionPriority(100))
+            .with(BlocksInput)
+            .button(rect)
+            .with(ZIndex(50))
+            .build();
+        let point = Point::new(5.0, 5.0);
+        // The modal backdrop wins and stops the button, though it's lower.
+        assert!(hover_detect(&world, point).is_empty());
+        assert_eq!(entity_at(&world, point), Some(entities[0]));
+        // Outside the backdrop nothing blocks.
+        assert_eq!(entity_at(&world, Point::new(50.0, 50.0)), None);
+    }
+
+    #[test]
+    fn equal_priority_falls_back_to_z() {
+        let (mut world, entities) = stacked().build();
+        world.insert(entities[0], ZIndex(3));
+        world.insert(entities[1], ZIndex(1));
+        world.insert(entities[2], ZIndex(2));
+        let point = Point::new(5.0, 5.0);
+        assert_eq!(entity_at(&world, point), Some(entities[0]));
+        assert_eq!(hover_detect(&world, point), vec![entities[0], entities[2], entities[1]]);
+    }
+
+    const VIEWPORT: Rect = Rect { left: 0.0, top: 0.0, right: 100.0, bottom: 100.0 };
+
+    // The document half of a frame as about_to_wait runs it, with
+    // RedrawRequested reduced to counting presented frames.
+    fn run_frame(world: &mut World, resources: &mut Resources) {
+        update_document(world, resources, VIEWPORT);
+        resources.pointer_state.end_frame();
+        if resources.frame_scheduler.take_redraw() {
+            resources.frame_scheduler.frames_presented += 1;
+        }
+    }
+
+    #[test]
+    fn static_scene_never_redraws() {
+        let (mut world, _) = WorldBuilder::new()
+            .button(Rect::from_xywh(0.0, 0.0, 10.0, 10.0))
+            .button(Rect::from_xywh(20.0, 0.0, 10.0, 10.0))
+            .build();
+        let mut resources = Resources::headless();
+        for _ in 0..60 {
+            run_frame(&mut world, &mut resources);
+        }
+        assert_eq!(resources.frame_scheduler.frames_presented, 0);
+
+        // Moving near the buttons without touching them changes nothing.
+        for x in 0..30 {
+            resources.pointer_state.push_sample(Point::new(x as f32, 15.0));
+            run_frame(&mut world, &mut resources);
+        }
+        assert_eq!(resources.frame_scheduler.frames_presented, 0);
+    }
+
+    #[test]
+    fn hover_change_redraws_once() {
+        let (mut world, entities) = WorldBuilder::new()
+            .button(Rect::from_xywh(0.0, 0.0, 10.0, 10.0))
+            .build();
+        let mut resources = Resources::headless();
+        let mut reader = resources.interactions.reader();
+        for x in 2..8 {
+            resources.pointer_state.push_sample(Point::new(x as f32, 5.0));
+            run_frame(&mut world, &mut resources);
+        }
+        for _ in 0..10 {
+            run_frame(&mut world, &mut resources);
+        }
+        assert_eq!(resources.frame_scheduler.frames_presented, 1);
+        let events: Vec<_> = reader.read(&resources.interactions).collect();
+        assert!(matches!(events[..], [InteractionEvent::HoverEntered(entity)] if *entity == entities[0]));
+    }
+
+    #[test]
+    fn frame_applies_queued_commands_and_layout() {
+        let (mut world, entities) = WorldBuilder::new()
+            .button(Rect::from_xywh(0.0, 0.0, 10.0, 10.0))
+            .button(Rect::from_xywh(20.0, 0.0, 10.0, 10.0))
+            .build();
+        let mut resources = Resources::headless();
+        resources.damage.begin_frame(1);
+        resources.selection.set(entities[1]);
+        world.commands().despawn(entities[1]);
+        world.storage_mut::<Quad>().unwrap().get_mut(entities[0]).unwrap().color = Color4f::new(1.0, 0.0, 0.0, 1.0);
+
+        run_frame(&mut world, &mut resources);
+        assert!(!world.is_alive(entities[1]));
+        assert!(resources.selection.entities().is_empty());
+        assert_eq!(resources.frame_scheduler.frames_presented, 1);
+        let damage = resources.damage.begin_frame(1).unwrap();
+        assert!(damage.contains(Point::new(5.0, 5.0)));
+
+        run_frame(&mut world, &mut resources);
+        assert_eq!(resources.frame_scheduler.frames_presented, 1);
+    }
+}
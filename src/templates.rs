@@ -0,0 +1,204 @@
+use std::{fs, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+use skia_safe::{Color, Color4f, Matrix, Point, Rect};
+
+use crate::ecs::{BlocksInput, Bounds, Component, Entity, Icon, InteractionPriority, Quad, Transform, World, ZIndex};
+use crate::systems::{ButtonBundle, entity_at};
+use crate::text::spawn_label;
+use crate::units::{DEFAULT_DPI, Document, Unit};
+
+pub const TEMPLATES_PATH: &str = "templates.json";
+
+const TILE: f32 = 96.0;
+const GAP: f32 = 12.0;
+const COLUMNS: usize = 4;
+const TOGGLE: f32 = 24.0;
+// The strip along the bottom of a tile its template's name goes in.
+const LABEL_HEIGHT: f32 = 16.0;
+const LABEL_SIZE: f32 = 10.0;
+// Above anything an editor overlay would use.
+pub const DIALOG_PRIORITY: i32 = 1000;
+pub const DIALOG_Z: i32 = 1000;
+
+// A named set of page settings to start a document from.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Template {
+    pub name: String,
+    pub document: Document,
+}
+
+fn screen(name: &str, width: f32, height: f32) -> Template {
+    let document = Document { width, height, background: Some(Color::WHITE), ..Document::default() };
+    Template { name: name.to_string(), document }
+}
+
+fn paper(name: &str, width_mm: f32, height_mm: f32, dpi: f32) -> Template {
+    let mut document = Document { dpi, unit: Unit::Mm, background: Some(Color::WHITE), ..Document::default() };
+    document.width = document.to_px(width_mm, Unit::Mm).round();
+    document.height = document.to_px(height_mm, Unit::Mm).round();
+    Template { name: name.to_string(), document }
+}
+
+// Screens, paper at print resolution and social media formats.
+pub fn presets() -> Vec<Template> {
+    vec![
+        screen("HD 1920x1080", 1920.0, 1080.0),
+        screen("Laptop 1440x900", 1440.0, 900.0),
+        screen("Phone 1170x2532", 1170.0, 2532.0),
+        screen("Web 1280x800", 1280.0, 800.0),
+        paper("A4 300 dpi", 210.0, 297.0, 300.0),
+        paper("A5 300 dpi", 148.0, 210.0, 300.0),
+        paper("US Letter 300 dpi", 215.9, 279.4, 300.0),
+        paper("A4 screen", 210.0, 297.0, DEFAULT_DPI),
+        screen("Square post 1080x1080", 1080.0, 1080.0),
+        screen("Story 1080x1920", 1080.0, 1920.0),
+        screen("Banner 1500x500", 1500.0, 500.0),
+        screen("Thumbnail 1280x720", 1280.0, 720.0),
+    ]
+}
+
+// A missing file is no templates yet.
+pub fn load_user_templates(path: &Path) -> io::Result<Vec<Template>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+// Adds `template` to the file, replacing one with the same name.
+pub fn save_user_template(path: &Path, template: Template) -> io::Result<()> {
+    let mut templates = load_user_templates(path)?;
+    templates.retain(|existing| existing.name != template.name);
+    templates.push(template);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, serde_json::to_string_pretty(&templates)?)
+}
+
+// Starts over with an empty document using `document`'s page settings.
+pub fn new_document(world: &mut World, document: Document) {
+    world.clear();
+    *world.document_mut() = document;
+}
+
+// Which template a dialog tile (or its preview) picks.
+pub struct TemplateChoice(pub usize);
+
+impl Component for TemplateChoice {}
+
+pub enum DialogAction {
+    Pending,
+    Create(Document),
+    Cancel,
+}
+
+// The new-document dialog, built from entities in the UI world: one tile
+// per template with a preview of its page shape, and a toggle for a
+// transparent background. Clicking outside the panel cancels.
+pub struct NewDocumentDialog {
+    templates: Vec<Template>,
+    transparent: bool,
+    backdrop: Entity,
+    toggle: Entity,
+    entities: Vec<Entity>,
+}
+
+pub fn spawn_rect(ui: &mut World, rect: Rect, color: Color4f, z: i32) -> Entity {
+    let entity = ui.spawn();
+    ui.insert(entity, Bounds { rect });
+    ui.insert(entity, Quad { color, rect });
+    ui.insert(entity, Transform { local_to_parent: Matrix::new_identity(), z: 0.0 });
+    ui.insert(entity, ZIndex(z));
+    entity
+}
+
+pub fn spawn_button(ui: &mut World, rect: Rect, z: i32) -> Entity {
+    let entity = ui.spawn_bundle(ButtonBundle::new(rect));
+    ui.insert(entity, ZIndex(z));
+    ui.insert(entity, InteractionPriority(DIALOG_PRIORITY));
+    entity
+}
+
+// A `width` x `height` rect scaled to fit inside `rect`, centered.
+fn fit(rect: Rect, width: f32, height: f32) -> Rect {
+    let scale = (rect.width() / width).min(rect.height() / height);
+    Rect::from_xywh(0.0, 0.0, width * scale, height * scale)
+        .with_offset((rect.center_x() - width * scale * 0.5, rect.center_y() - height * scale * 0.5))
+}
+
+impl NewDocumentDialog {
+    pub fn open(ui: &mut World, templates: Vec<Template>, viewport: Rect) -> Self {
+        let rows = templates.len().div_ceil(COLUMNS).max(1);
+        let width = COLUMNS as f32 * (TILE + GAP) + GAP;
+        let height = rows as f32 * (TILE + GAP) + GAP + TOGGLE + GAP;
+        let panel = Rect::from_xywh(viewport.center_x() - width * 0.5, viewport.center_y() - height * 0.5, width, height);
+
+        let mut entities = Vec::new();
+        let backdrop = spawn_rect(ui, viewport, Color4f::new(0.0, 0.0, 0.0, 0.4), DIALOG_Z);
+        let panel_entity = spawn_rect(ui, panel, Color4f::new(0.95, 0.95, 0.95, 1.0), DIALOG_Z + 1);
+        for entity in [backdrop, panel_entity] {
+            ui.insert(entity, BlocksInput);
+            ui.insert(entity, InteractionPriority(DIALOG_PRIORITY));
+            entities.push(entity);
+        }
+
+        for (index, template) in templates.iter().enumerate() {
+            let column = (index % COLUMNS) as f32;
+            let row = (index / COLUMNS) as f32;
+            let tile = Rect::from_xywh(panel.left + GAP + column * (TILE + GAP), panel.top + GAP + row * (TILE + GAP), TILE, TILE);
+            let button = spawn_button(ui, tile, DIALOG_Z + 2);
+            ui.insert(button, TemplateChoice(index));
+            entities.push(button);
+
+            let label = Rect::new(tile.left, tile.bottom - LABEL_HEIGHT, tile.right, tile.bottom);
+            entities.push(spawn_label(ui, label, &template.name, LABEL_SIZE, DIALOG_Z + 3));
+
+            let document = &template.document;
+            if document.page().is_some() {
+                let color = document.background.map_or(Color4f::new(1.0, 1.0, 1.0, 0.5), Color4f::from);
+                let area = Rect::new(tile.left, tile.top, tile.right, label.top).with_inset((GAP, GAP * 0.5));
+                let preview = spawn_rect(ui, fit(area, document.width, document.height), color, DIALOG_Z + 3);
+                ui.insert(preview, TemplateChoice(index));
+                entities.push(preview);
+            }
+        }
+
+        let toggle_rect = Rect::from_xywh(panel.left + GAP, panel.bottom - GAP - TOGGLE, TOGGLE, TOGGLE);
+        let toggle = spawn_button(ui, toggle_rect, DIALOG_Z + 2);
+        entities.push(toggle);
+
+        NewDocumentDialog { templates, transparent: false, backdrop, toggle, entities }
+    }
+
+    pub fn click(&mut self, ui: &mut World, point: Point) -> DialogAction {
+        let Some(hit) = entity_at(ui, point) else { return DialogAction::Pending; };
+        if hit == self.backdrop {
+            return DialogAction::Cancel;
+        }
+        let choice = ui.storage::<TemplateChoice>().and_then(|storage| storage.get(hit).map(|choice| choice.0));
+        if let Some(index) = choice {
+            let mut document = self.templates[index].document;
+            if self.transparent {
+                document.background = None;
+            }
+            return DialogAction::Create(document);
+        }
+        if hit == self.toggle {
+            self.transparent = !self.transparent;
+            if self.transparent {
+                ui.insert(self.toggle, Icon { name: "check".to_string(), tint: Color::BLACK, size: TOGGLE * 0.75 });
+            } else {
+                ui.remove::<Icon>(self.toggle);
+            }
+        }
+        DialogAction::Pending
+    }
+
+    pub fn close(self, ui: &mut World) {
+        for entity in self.entities {
+            ui.despawn(entity);
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use skia_safe::Rect;
+
+use crate::ecs::{Bounds, Component, Entity, World};
+use crate::systems::ButtonBundle;
+
+// Worlds for tests, built without a window or GL context. Each `with`
+// adds a component to the entity added last.
+pub struct WorldBuilder {
+    world: World,
+    entities: Vec<Entity>,
+}
+
+impl WorldBuilder {
+    pub fn new() -> Self {
+        WorldBuilder { world: World::new(), entities: Vec::new() }
+    }
+
+    // An interactable button, as main() spawns them.
+    pub fn button(mut self, rect: Rect) -> Self {
+        let entity = self.world.spawn_bundle(ButtonBundle::new(rect));
+        self.entities.push(entity);
+        self
+    }
+
+    // Something with Bounds only, which hit tests but isn't interactable.
+    pub fn region(mut self, rect: Rect) -> Self {
+        let entity = self.world.spawn();
+        self.world.insert(entity, Bounds { rect });
+        self.entities.push(entity);
+        self
+    }
+
+    pub fn with<T: Component>(mut self, component: T) -> Self {
+        let entity = *self.entities.last().expect("with() before any entity was added");
+        self.world.insert(entity, component);
+        self
+    }
+
+    // The World with its change trackers cleared, as after a frame, and
+    // the entities in the order they were added.
+    pub fn build(mut self) -> (World, Vec<Entity>) {
+        self.world.flush();
+        self.world.clear_trackers();
+        (self.world, self.entities)
+    }
+}
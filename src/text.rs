@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+
+use skia_safe::{Canvas, Color, Color4f, Font, FontMgr, FontStyle, Matrix, Paint, Point, Rect, TextBlob, Typeface};
+
+use crate::draw::DrawScope;
+use crate::ecs::{Entity, Quad, Text, TextAlign, TextLayout, Transform, World, ZIndex};
+
+// System fonts by family name. Lookups are cached, misses included, so a
+// missing family only costs one search.
+pub struct FontManager {
+    font_mgr: FontMgr,
+    typefaces: HashMap<String, Option<Typeface>>,
+}
+
+impl FontManager {
+    pub fn new() -> Self {
+        FontManager { font_mgr: FontMgr::new(), typefaces: HashMap::new() }
+    }
+
+    pub fn families(&self) -> Vec<String> {
+        self.font_mgr.family_names().collect()
+    }
+
+    // `family`, or the system default when it isn't installed. None only if
+    // the system has no fonts at all.
+    pub fn typeface(&mut self, family: &str) -> Option<Typeface> {
+        if let Some(typeface) = self.typefaces.get(family) {
+            return typeface.clone();
+        }
+        let typeface = self.font_mgr.match_family_style(family, FontStyle::normal())
+            .or_else(|| self.font_mgr.legacy_make_typeface(None, FontStyle::normal()));
+        self.typefaces.insert(family.to_string(), typeface.clone());
+        typeface
+    }
+
+    pub fn layout(&mut self, text: &Text) -> TextLayout {
+        let Some(typeface) = self.typeface(&text.font) else {
+            return TextLayout { blob: None, width: 0.0, ascent: 0.0, descent: 0.0 };
+        };
+        let font = Font::from_typeface(typeface, text.size);
+        let (width, _) = font.measure_str(&text.content, None);
+        let (_, metrics) = font.metrics();
+        TextLayout {
+            blob: TextBlob::from_str(&text.content, &font),
+            width,
+            ascent: -metrics.ascent,
+            descent: metrics.descent,
+        }
+    }
+}
+
+// Reshapes every Text added or changed since the last frame. Returns the
+// entities that were reshaped.
+pub fn text_layout_system(world: &mut World, fonts: &mut FontManager) -> Vec<Entity> {
+    let changed: Vec<(Entity, Text)> = world.query::<Text>().changed::<Text>().iter()
+        .map(|(entity, text)| (entity, text.clone()))
+        .collect();
+    for (entity, text) in &changed {
+        world.insert(*entity, fonts.layout(text));
+    }
+    changed.into_iter().map(|(entity, _)| entity).collect()
+}
+
+// A line of text over `rect`, drawn at `z`. It has no Bounds, so clicks
+// go to whatever it labels.
+pub fn spawn_label(world: &mut World, rect: Rect, content: &str, size: f32, z: i32) -> Entity {
+    let entity = world.spawn();
+    world.insert(entity, Quad { rect, color: Color4f::new(0.0, 0.0, 0.0, 0.0) });
+    world.insert(entity, Transform { local_to_parent: Matrix::new_identity(), z: 0.0 });
+    world.insert(entity, ZIndex(z));
+    world.insert(entity, Text { content: content.to_string(), size, color: Color::DARK_GRAY, font: "sans-serif".to_string() });
+    entity
+}
+
+pub fn draw_text(canvas: &Canvas, text: &Text, layout: &TextLayout, align: TextAlign, rect: Rect) {
+    let Some(blob) = &layout.blob else { return; };
+    let x = match align {
+        TextAlign::Left => rect.left,
+        TextAlign::Center => rect.center_x() - layout.width * 0.5,
+    };
+    let origin = Point::new(x, rect.center_y() + (layout.ascent - layout.descent) * 0.5);
+    let mut paint = Paint::new(Color4f::from(text.color), None);
+    paint.set_anti_alias(true);
+    let scope = DrawScope::new(canvas);
+    scope.clip(rect);
+    scope.canvas().draw_text_blob(blob, origin, &paint);
+}
@@ -0,0 +1,143 @@
+use std::time::{Duration, Instant};
+
+use crate::ecs::{Events, World};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct TimerId(u64);
+
+// Sent when a timer fires, for systems that would rather poll than own a
+// callback.
+#[derive(Clone, Copy, Debug)]
+pub struct TimerFired(pub TimerId);
+
+type Callback = Box<dyn FnMut(&mut World)>;
+
+// Repeating timers fire at most this often; a zero interval would never
+// get past its own deadline.
+const MIN_INTERVAL: Duration = Duration::from_millis(1);
+
+struct Timer {
+    id: TimerId,
+    deadline: Instant,
+    // Repeating timers are rescheduled by this much after firing.
+    interval: Option<Duration>,
+    callback: Option<Callback>,
+}
+
+// One-shot and repeating timers driven from the event loop, which sleeps
+// until Timers::next instead of polling. A timer fires at most once per
+// update, so a repeating timer that fell behind doesn't fire in a burst.
+pub struct Timers {
+    timers: Vec<Timer>,
+    next_id: u64,
+    pub fired: Events<TimerFired>,
+}
+
+impl Timers {
+    pub fn new() -> Self {
+        Timers { timers: Vec::new(), next_id: 0, fired: Events::new() }
+    }
+
+    fn add(&mut self, delay: Duration, interval: Option<Duration>, callback: Option<Callback>) -> TimerId {
+        let id = TimerId(self.next_id);
+        self.next_id += 1;
+        self.timers.push(Timer { id, deadline: Instant::now() + delay, interval, callback });
+        id
+    }
+
+    pub fn after(&mut self, delay: Duration) -> TimerId {
+        self.add(delay, None, None)
+    }
+
+    pub fn every(&mut self, interval: Duration) -> TimerId {
+        let interval = interval.max(MIN_INTERVAL);
+        self.add(interval, Some(interval), None)
+    }
+
+    pub fn after_with(&mut self, delay: Duration, callback: impl FnMut(&mut World) + 'static) -> TimerId {
+        self.add(delay, None, Some(Box::new(callback)))
+    }
+
+    pub fn every_with(&mut self, interval: Duration, callback: impl FnMut(&mut World) + 'static) -> TimerId {
+        let interval = interval.max(MIN_INTERVAL);
+        self.add(interval, Some(interval), Some(Box::new(callback)))
+    }
+
+    // Restarts a timer's countdown from now, e.g. a tooltip delay after the
+    // pointer moves again. False if the timer already finished.
+    pub fn restart(&mut self, id: TimerId, delay: Duration) -> bool {
+        let Some(timer) = self.timers.iter_mut().find(|timer| timer.id == id) else { return false; };
+        timer.deadline = Instant::now() + delay;
+        true
+    }
+
+    pub fn cancel(&mut self, id: TimerId) -> bool {
+        let before = self.timers.len();
+        self.timers.retain(|timer| timer.id != id);
+        self.timers.len() != before
+    }
+
+    pub fn is_pending(&self, id: TimerId) -> bool {
+        self.timers.iter().any(|timer| timer.id == id)
+    }
+
+    pub fn next(&self) -> Option<Instant> {
+        self.timers.iter().map(|timer| timer.deadline).min()
+    }
+
+    // Fires every timer that is due: runs its callback and sends
+    // TimerFired. Call once per frame.
+    pub fn update(&mut self, now: Instant, world: &mut World) {
+        self.fired.update();
+        let mut index = 0;
+        while index < self.timers.len() {
+            let timer = &mut self.timers[index];
+            if timer.deadline > now {
+                index += 1;
+                continue;
+            }
+            if let Some(callback) = &mut timer.callback {
+                callback(world);
+            }
+            self.fired.send(TimerFired(timer.id));
+            match timer.interval {
+                Some(interval) => {
+                    while timer.deadline <= now {
+                        timer.deadline += interval;
+                    }
+                    index += 1;
+                }
+                None => { self.timers.swap_remove(index); }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_interval_repeats_without_hanging() {
+        let mut timers = Timers::new();
+        let mut world = World::new();
+        let id = timers.every(Duration::ZERO);
+        let mut reader = timers.fired.reader();
+        let now = Instant::now() + Duration::from_millis(5);
+        timers.update(now, &mut world);
+        assert_eq!(reader.read(&timers.fired).count(), 1);
+        assert!(timers.next().is_some_and(|next| next > now));
+        assert!(timers.is_pending(id));
+    }
+
+    #[test]
+    fn one_shot_fires_once() {
+        let mut timers = Timers::new();
+        let mut world = World::new();
+        let id = timers.after(Duration::from_millis(1));
+        let now = Instant::now() + Duration::from_millis(5);
+        timers.update(now, &mut world);
+        assert!(!timers.is_pending(id));
+        assert_eq!(timers.next(), None);
+    }
+}
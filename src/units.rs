@@ -0,0 +1,117 @@
+use serde::{Deserialize, Serialize};
+use skia_safe::{Color, Rect};
+
+use crate::scene;
+
+// CSS pixels: what a document measures in when it doesn't say otherwise.
+pub const DEFAULT_DPI: f32 = 96.0;
+const MM_PER_INCH: f32 = 25.4;
+const POINTS_PER_INCH: f32 = 72.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Unit {
+    Px,
+    Mm,
+    In,
+    Pt,
+}
+
+impl Unit {
+    pub fn suffix(self) -> &'static str {
+        match self {
+            Unit::Px => "px",
+            Unit::Mm => "mm",
+            Unit::In => "in",
+            Unit::Pt => "pt",
+        }
+    }
+
+    pub fn parse(text: &str) -> Option<Unit> {
+        match text.trim().to_ascii_lowercase().as_str() {
+            "px" => Some(Unit::Px),
+            "mm" => Some(Unit::Mm),
+            "in" | "\"" => Some(Unit::In),
+            "pt" => Some(Unit::Pt),
+            _ => None,
+        }
+    }
+
+    // For cycling through units in the UI.
+    pub fn next(self) -> Unit {
+        match self {
+            Unit::Px => Unit::Mm,
+            Unit::Mm => Unit::In,
+            Unit::In => Unit::Pt,
+            Unit::Pt => Unit::Px,
+        }
+    }
+
+    fn per_inch(self, dpi: f32) -> f32 {
+        match self {
+            Unit::Px => dpi,
+            Unit::Mm => MM_PER_INCH,
+            Unit::In => 1.0,
+            Unit::Pt => POINTS_PER_INCH,
+        }
+    }
+}
+
+// Page settings saved with the scene. Geometry is always stored in px;
+// `unit` is only what dimensions are shown and typed in. A zero size is an
+// unbounded canvas, and a missing background a transparent one.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Document {
+    pub dpi: f32,
+    pub unit: Unit,
+    #[serde(default)]
+    pub width: f32,
+    #[serde(default)]
+    pub height: f32,
+    #[serde(default, with = "scene::option_color")]
+    pub background: Option<Color>,
+}
+
+impl Default for Document {
+    fn default() -> Self {
+        Document { dpi: DEFAULT_DPI, unit: Unit::Px, width: 0.0, height: 0.0, background: None }
+    }
+}
+
+impl Document {
+    pub fn to_px(&self, value: f32, unit: Unit) -> f32 {
+        value * self.dpi / unit.per_inch(self.dpi)
+    }
+
+    pub fn from_px(&self, px: f32, unit: Unit) -> f32 {
+        px * unit.per_inch(self.dpi) / self.dpi
+    }
+
+    // "12mm", "0.5 in" or a bare number in the document's unit, to px.
+    pub fn parse_length(&self, text: &str) -> Option<f32> {
+        let text = text.trim();
+        let split = text.find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-')).unwrap_or(text.len());
+        let value: f32 = text[..split].parse().ok()?;
+        let unit = match text[split..].trim() {
+            "" => self.unit,
+            suffix => Unit::parse(suffix)?,
+        };
+        Some(self.to_px(value, unit))
+    }
+
+    // `px` in the document's unit, e.g. "12.7 mm".
+    pub fn format(&self, px: f32) -> String {
+        let value = self.from_px(px, self.unit);
+        let decimals = if self.unit == Unit::Px { 1 } else { 2 };
+        format!("{:.*} {}", decimals, value, self.unit.suffix())
+    }
+
+    pub fn page(&self) -> Option<Rect> {
+        (self.width > 0.0 && self.height > 0.0).then(|| Rect::from_wh(self.width, self.height))
+    }
+
+    // Document px to PDF points, so pages print at their physical size.
+    pub fn points_per_px(&self) -> f32 {
+        POINTS_PER_INCH / self.dpi
+    }
+}
@@ -0,0 +1,65 @@
+use std::time::{Duration, Instant};
+
+use winit::event_loop::EventLoopProxy;
+
+// User events that wake the event loop. The loop sleeps in ControlFlow::Wait,
+// so work finishing on another thread has to come through here.
+#[derive(Clone, Copy, Debug)]
+pub enum Wakeup {
+    // Run the per-frame systems once more.
+    Tick,
+    // Repaint the whole window, e.g. after a background render finished.
+    Redraw,
+}
+
+// Cloneable and Send, so background threads can hold one.
+#[derive(Clone)]
+pub struct Waker {
+    proxy: EventLoopProxy<Wakeup>,
+}
+
+impl Waker {
+    pub fn new(proxy: EventLoopProxy<Wakeup>) -> Self {
+        Waker { proxy }
+    }
+
+    // False once the event loop has exited.
+    pub fn wake(&self) -> bool {
+        self.proxy.send_event(Wakeup::Tick).is_ok()
+    }
+
+    pub fn request_redraw(&self) -> bool {
+        self.proxy.send_event(Wakeup::Redraw).is_ok()
+    }
+}
+
+// Timed wakeups for animations and timers. The event loop waits until the
+// earliest deadline instead of polling.
+pub struct WakeSchedule {
+    deadlines: Vec<Instant>,
+}
+
+impl WakeSchedule {
+    pub fn new() -> Self {
+        WakeSchedule { deadlines: Vec::new() }
+    }
+
+    pub fn wake_at(&mut self, deadline: Instant) {
+        self.deadlines.push(deadline);
+    }
+
+    pub fn wake_after(&mut self, delay: Duration) {
+        self.wake_at(Instant::now() + delay);
+    }
+
+    pub fn next(&self) -> Option<Instant> {
+        self.deadlines.iter().min().copied()
+    }
+
+    // Drops deadlines that have passed; true if any had.
+    pub fn take_due(&mut self, now: Instant) -> bool {
+        let before = self.deadlines.len();
+        self.deadlines.retain(|deadline| *deadline > now);
+        self.deadlines.len() != before
+    }
+}
@@ -0,0 +1,219 @@
+use serde::{Deserialize, Serialize};
+use skia_safe::{Color, Color4f, Matrix, Rect};
+
+use crate::ecs::{BlocksInput, Bounds, Component, Entity, InteractionPriority, Quad, Text, Transform, World, ZIndex};
+use crate::settings::Settings;
+
+// Panels sit under dialogs but above everything else in the UI world.
+const PANEL_PRIORITY: i32 = 500;
+const PANEL_Z: i32 = 500;
+const TITLE_SIZE: f32 = 13.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Panel {
+    Tools,
+    Layers,
+    Properties,
+    Swatches,
+    Brushes,
+    Timeline,
+}
+
+impl Panel {
+    pub fn title(self) -> &'static str {
+        match self {
+            Panel::Tools => "Tools",
+            Panel::Layers => "Layers",
+            Panel::Properties => "Properties",
+            Panel::Swatches => "Swatches",
+            Panel::Brushes => "Brushes",
+            Panel::Timeline => "Timeline",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Dock {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+// Where a panel is docked in a workspace. `size` is its width on the left
+// and right, its height on the top and bottom.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct DockedPanel {
+    pub panel: Panel,
+    pub dock: Dock,
+    pub size: f32,
+}
+
+// A named arrangement of panels for one kind of task.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Workspace {
+    pub name: String,
+    pub panels: Vec<DockedPanel>,
+}
+
+// Marks the UI entity showing a panel, so a layout can be found and
+// replaced. `order` is the panel's place in its workspace, which decides
+// how panels on the same side stack.
+#[derive(Clone, Copy, Debug)]
+pub struct PanelView {
+    pub docked: DockedPanel,
+    pub order: usize,
+}
+
+impl Component for PanelView {}
+
+fn docked(panel: Panel, dock: Dock, size: f32) -> DockedPanel {
+    DockedPanel { panel, dock, size }
+}
+
+pub fn presets() -> Vec<Workspace> {
+    vec![
+        Workspace {
+            name: "Painting".to_string(),
+            panels: vec![
+                docked(Panel::Tools, Dock::Left, 48.0),
+                docked(Panel::Brushes, Dock::Right, 220.0),
+                docked(Panel::Swatches, Dock::Right, 220.0),
+                docked(Panel::Layers, Dock::Right, 220.0),
+            ],
+        },
+        Workspace {
+            name: "Vector".to_string(),
+            panels: vec![
+                docked(Panel::Tools, Dock::Left, 48.0),
+                docked(Panel::Layers, Dock::Left, 200.0),
+                docked(Panel::Properties, Dock::Right, 240.0),
+            ],
+        },
+        Workspace {
+            name: "Animation".to_string(),
+            panels: vec![
+                docked(Panel::Tools, Dock::Left, 48.0),
+                docked(Panel::Properties, Dock::Right, 240.0),
+                docked(Panel::Timeline, Dock::Bottom, 160.0),
+            ],
+        },
+    ]
+}
+
+// The presets, with saved layouts replacing presets of the same name and
+// any others after them.
+pub fn workspaces(settings: &Settings) -> Vec<Workspace> {
+    let mut workspaces = presets();
+    for saved in &settings.workspaces {
+        match workspaces.iter_mut().find(|workspace| workspace.name == saved.name) {
+            Some(workspace) => *workspace = saved.clone(),
+            None => workspaces.push(saved.clone()),
+        }
+    }
+    workspaces
+}
+
+// Top and bottom panels span the window, stacked inwards from their edge in
+// order; left and right ones share a column as wide as the widest of them,
+// between the top and bottom bands, split evenly.
+pub fn dock_layout(panels: &[DockedPanel], viewport: Rect) -> Vec<Rect> {
+    let mut rects = vec![Rect::default(); panels.len()];
+    let mut top = viewport.top;
+    let mut bottom = viewport.bottom;
+    for (index, docked) in panels.iter().enumerate() {
+        match docked.dock {
+            Dock::Top => {
+                rects[index] = Rect::new(viewport.left, top, viewport.right, top + docked.size);
+                top += docked.size;
+            }
+            Dock::Bottom => {
+                rects[index] = Rect::new(viewport.left, bottom - docked.size, viewport.right, bottom);
+                bottom -= docked.size;
+            }
+            Dock::Left | Dock::Right => {}
+        }
+    }
+    for side in [Dock::Left, Dock::Right] {
+        let column: Vec<usize> = (0..panels.len()).filter(|index| panels[*index].dock == side).collect();
+        let width = column.iter().map(|index| panels[*index].size).fold(0.0, f32::max);
+        let height = (bottom - top) / column.len().max(1) as f32;
+        let (left, right) = match side {
+            Dock::Left => (viewport.left, viewport.left + width),
+            _ => (viewport.right - width, viewport.right),
+        };
+        for (slot, index) in column.into_iter().enumerate() {
+            let y = top + slot as f32 * height;
+            rects[index] = Rect::new(left, y, right, y + height);
+        }
+    }
+    rects
+}
+
+// Replaces the panels in the UI world with `workspace`'s. Returns the new
+// panel entities.
+pub fn apply_workspace(ui: &mut World, workspace: &Workspace, viewport: Rect) -> Vec<Entity> {
+    for (entity, _) in panel_views(ui) {
+        ui.despawn(entity);
+    }
+    let rects = dock_layout(&workspace.panels, viewport);
+    workspace.panels.iter().zip(rects).enumerate().map(|(order, (docked, rect))| {
+        let entity = ui.spawn();
+        ui.insert(entity, Bounds { rect });
+        ui.insert(entity, Quad { rect, color: Color4f::new(0.85, 0.85, 0.87, 1.0) });
+        ui.insert(entity, Transform { local_to_parent: Matrix::new_identity(), z: 0.0 });
+        ui.insert(entity, ZIndex(PANEL_Z));
+        ui.insert(entity, BlocksInput);
+        ui.insert(entity, InteractionPriority(PANEL_PRIORITY));
+        ui.insert(entity, Text { content: docked.panel.title().to_string(), size: TITLE_SIZE, color: Color::DARK_GRAY, font: "sans-serif".to_string() });
+        ui.insert(entity, PanelView { docked: *docked, order });
+        entity
+    }).collect()
+}
+
+fn panel_views(ui: &World) -> Vec<(Entity, DockedPanel)> {
+    let mut views: Vec<(Entity, PanelView)> = ui.query::<PanelView>().iter()
+        .map(|(entity, view)| (entity, *view))
+        .collect();
+    views.sort_by_key(|(_, view)| view.order);
+    views.into_iter().map(|(entity, view)| (entity, view.docked)).collect()
+}
+
+// The layout as currently shown, e.g. to save it.
+pub fn current_panels(ui: &World) -> Vec<DockedPanel> {
+    panel_views(ui).into_iter().map(|(_, docked)| docked).collect()
+}
+
+// Moves the panel shown by `entity` to `dock`, at the end of that side.
+// False if `entity` isn't a panel.
+pub fn redock(ui: &World, entity: Entity, dock: Dock) -> bool {
+    let order = ui.query::<PanelView>().iter().map(|(_, view)| view.order + 1).max().unwrap_or(0);
+    let Some(mut views) = ui.storage_mut::<PanelView>() else { return false; };
+    let Some(mut view) = views.get_mut(entity) else { return false; };
+    if view.docked.dock != dock {
+        view.docked.dock = dock;
+        view.order = order;
+    }
+    true
+}
+
+// Keeps panels where their layout puts them as the window resizes or
+// panels move. Returns the rects panels moved away from, which still need
+// repainting.
+pub fn dock_system(ui: &World, viewport: Rect) -> Vec<Rect> {
+    let views = panel_views(ui);
+    let panels: Vec<DockedPanel> = views.iter().map(|(_, docked)| *docked).collect();
+    let mut vacated = Vec::new();
+    for ((entity, _), rect) in views.into_iter().zip(dock_layout(&panels, viewport)) {
+        let current = ui.storage::<Bounds>().and_then(|storage| storage.get(entity).map(|bounds| bounds.rect));
+        let Some(current) = current.filter(|current| *current != rect) else { continue; };
+        vacated.push(current);
+        if let Some(mut bounds) = ui.storage_mut::<Bounds>().as_mut().and_then(|storage| storage.get_mut(entity)) {
+            bounds.rect = rect;
+        }
+        if let Some(mut quad) = ui.storage_mut::<Quad>().as_mut().and_then(|storage| storage.get_mut(entity)) {
+            quad.rect = rect;
+        }
+    }
+    vacated
+}